@@ -1,123 +1,19 @@
-// Validates moves in PGNs.
-// Usage: cargo run --release --example validate -- [PGN]...
+// Validates moves in PGNs. Uncompressed files are split into chunks that are
+// parsed in parallel. Compressed files are decompressed sequentially, and
+// the games are validated in parallel.
+// Usage: cargo run --release --example parallel_validate -- [PGN]...
 
 use std::{
     env,
     fs::File,
-    io, mem,
+    io,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
 };
 
-use pgn_reader::{BufferedReader, RawTag, San, SanPlus, Skip, Visitor};
-use shakmaty::{fen::Fen, CastlingMode, Chess, Position};
-
-struct Game {
-    index: usize,
-    pos: Chess,
-    sans: Vec<San>,
-    success: bool,
-}
-
-impl Game {
-    fn validate(mut self) -> bool {
-        self.success && {
-            for san in self.sans {
-                let m = match san.to_move(&self.pos) {
-                    Ok(m) => m,
-                    Err(_) => return false,
-                };
-
-                self.pos.play_unchecked(m);
-            }
-            true
-        }
-    }
-}
-
-struct Validator {
-    games: usize,
-    game: Game,
-}
-
-impl Validator {
-    fn new() -> Validator {
-        Validator {
-            games: 0,
-            game: Game {
-                index: 0,
-                pos: Chess::default(),
-                sans: Vec::new(),
-                success: true,
-            },
-        }
-    }
-}
-
-impl Visitor for Validator {
-    type Result = Game;
-
-    fn begin_tags(&mut self) {
-        self.games += 1;
-    }
-
-    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
-        // Support games from a non-standard starting position.
-        if name == b"FEN" {
-            let fen = match Fen::from_ascii(value.as_bytes()) {
-                Ok(fen) => fen,
-                Err(err) => {
-                    eprintln!(
-                        "invalid fen tag in game {}: {} ({:?})",
-                        self.games, err, value
-                    );
-                    self.game.success = false;
-                    return;
-                }
-            };
-
-            self.game.pos = match fen.into_position(CastlingMode::Chess960) {
-                Ok(pos) => pos,
-                Err(err) => {
-                    eprintln!(
-                        "illegal fen tag in game {}: {} ({:?})",
-                        self.games, err, value
-                    );
-                    self.game.success = false;
-                    return;
-                }
-            };
-        }
-    }
-
-    fn begin_movetext(&mut self) -> Skip {
-        Skip(!self.game.success)
-    }
-
-    fn begin_variation(&mut self) -> Skip {
-        Skip(true) // stay in the mainline
-    }
-
-    fn san(&mut self, san_plus: SanPlus) {
-        if self.game.success {
-            self.game.sans.push(san_plus.san);
-        }
-    }
-
-    fn end_game(&mut self) -> Self::Result {
-        mem::replace(
-            &mut self.game,
-            Game {
-                index: self.games,
-                pos: Chess::default(),
-                sans: Vec::with_capacity(80),
-                success: true,
-            },
-        )
-    }
-}
+use pgn_reader::{BufferedReader, GameBuilder, ParallelReader, Validator};
 
 fn main() {
     let mut complete_success = true;
@@ -138,16 +34,42 @@ fn main() {
         } else if arg.ends_with(".lz4") {
             Box::new(lz4::Decoder::new(file).expect("lz4 decoder"))
         } else {
-            Box::new(file)
+            let mut index = 0;
+            ParallelReader::new()
+                .read_file(
+                    || File::open(&arg),
+                    Validator::new,
+                    |result| {
+                        index += 1;
+                        match result {
+                            Ok(Ok(())) => {}
+                            Ok(Err(err)) => {
+                                eprintln!("error in game {index}: {err}");
+                                success.store(false, Ordering::SeqCst);
+                            }
+                            Err(err) => {
+                                eprintln!("error after game {}: {err}", index - 1);
+                                success.store(false, Ordering::SeqCst);
+                            }
+                        }
+                    },
+                )
+                .expect("read");
+
+            let success = success.load(Ordering::SeqCst);
+            println!("{}: {}", arg, if success { "success" } else { "errors" });
+            complete_success &= success;
+            continue;
         };
 
-        let mut validator = Validator::new();
+        let mut builder = GameBuilder::new();
         let (send, recv) = crossbeam::channel::bounded(128);
 
         crossbeam::scope(|scope| {
             scope.spawn(move |_| {
-                for game in BufferedReader::new(uncompressed).into_iter(&mut validator) {
-                    send.send(game.expect("io")).unwrap();
+                let games = BufferedReader::new(uncompressed).into_iter(&mut builder);
+                for (index, game) in games.enumerate() {
+                    send.send((index + 1, game.expect("io"))).unwrap();
                 }
             });
 
@@ -155,10 +77,10 @@ fn main() {
                 let recv = recv.clone();
                 let success = success.clone();
                 scope.spawn(move |_| {
-                    for game in recv {
-                        let index = game.index;
-                        if !game.validate() {
-                            eprintln!("illegal move in game {index}");
+                    let mut validator = Validator::new();
+                    for (index, game) in recv {
+                        if let Err(err) = game.visit(&mut validator) {
+                            eprintln!("error in game {index}: {err}");
                             success.store(false, Ordering::SeqCst);
                         }
                     }
@@ -3,87 +3,7 @@
 
 use std::{env, fs::File, io, process};
 
-use pgn_reader::{BufferedReader, RawTag, SanPlus, Skip, Visitor};
-use shakmaty::{fen::Fen, CastlingMode, Chess, Position};
-
-struct Validator {
-    games: usize,
-    pos: Chess,
-    success: bool,
-}
-
-impl Validator {
-    fn new() -> Validator {
-        Validator {
-            games: 0,
-            pos: Chess::default(),
-            success: true,
-        }
-    }
-}
-
-impl Visitor for Validator {
-    type Result = bool;
-
-    fn begin_tags(&mut self) {
-        self.games += 1;
-        self.pos = Chess::default();
-        self.success = true;
-    }
-
-    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
-        // Support games from a non-standard starting position.
-        if name == b"FEN" {
-            let fen = match Fen::from_ascii(value.as_bytes()) {
-                Ok(fen) => fen,
-                Err(err) => {
-                    eprintln!(
-                        "invalid fen tag in game {}: {} ({:?})",
-                        self.games, err, value
-                    );
-                    self.success = false;
-                    return;
-                }
-            };
-
-            self.pos = match fen.into_position(CastlingMode::Chess960) {
-                Ok(pos) => pos,
-                Err(err) => {
-                    eprintln!(
-                        "illegal fen tag in game {}: {} ({:?})",
-                        self.games, err, value
-                    );
-                    self.success = false;
-                    return;
-                }
-            };
-        }
-    }
-
-    fn begin_movetext(&mut self) -> Skip {
-        Skip(!self.success)
-    }
-
-    fn begin_variation(&mut self) -> Skip {
-        Skip(true) // stay in the mainline
-    }
-
-    fn san(&mut self, san_plus: SanPlus) {
-        if self.success {
-            match san_plus.san.to_move(&self.pos) {
-                Ok(m) => self.pos.play_unchecked(m),
-                Err(err) => {
-                    eprintln!("error in game {}: {} {}", self.games, err, san_plus);
-                    self.success = false;
-                }
-            }
-        }
-    }
-
-    fn end_game(&mut self) -> Self::Result {
-        self.success
-    }
-}
+use pgn_reader::{BufferedReader, Validator};
 
 fn main() -> io::Result<()> {
     let mut all_ok = true;
@@ -110,8 +30,13 @@ fn main() -> io::Result<()> {
         let mut reader = BufferedReader::new(uncompressed);
 
         let mut validator = Validator::new();
-        while let Some(ok) = reader.read_game(&mut validator)? {
-            file_ok &= ok;
+        let mut games = 0;
+        while let Some(result) = reader.read_game(&mut validator)? {
+            games += 1;
+            if let Err(err) = result {
+                eprintln!("error in game {games}: {err}");
+                file_ok = false;
+            }
         }
 
         println!("{}: {}", arg, if file_ok { "success" } else { "errors" });
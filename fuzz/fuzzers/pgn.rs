@@ -2,7 +2,7 @@
 
 use libfuzzer_sys::fuzz_target;
 
-use pgn_reader::{BufferedReader, Visitor};
+use pgn_reader::{BufferedReader, SliceReader, Tokenizer, Visitor};
 use std::io::Cursor;
 
 struct MyVisitor;
@@ -15,4 +15,8 @@ impl Visitor for MyVisitor {
 fuzz_target!(|data: &[u8]| {
     let mut visitor = MyVisitor;
     let _ = BufferedReader::new(Cursor::new(data)).read_all(&mut visitor);
+    let _ = SliceReader::new(data).read_all(&mut visitor);
+
+    let joined: Vec<u8> = Tokenizer::new(data).flat_map(|token| token.text.iter().copied()).collect();
+    assert_eq!(joined, data);
 });
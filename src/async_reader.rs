@@ -0,0 +1,385 @@
+use std::{io, ops::Range};
+
+use futures_io::AsyncRead;
+use futures_util::{
+    io::{AsyncReadExt as _, Chain, Cursor},
+    stream::{self, Stream},
+};
+
+use crate::{
+    buffer::Buffer,
+    error::Error,
+    machine::{Machine, Status},
+    reader::ReaderBuilder,
+    visitor::{SkipVisitor, Visitor},
+};
+
+impl ReaderBuilder {
+    /// Creates an asynchronous reader with the configured settings.
+    pub fn build_async<R: AsyncRead + Unpin>(&self, reader: R) -> AsyncBufferedReader<R> {
+        AsyncBufferedReader {
+            reader,
+            machine: Machine::new(self),
+            finished: false,
+            stopped: false,
+        }
+    }
+
+    /// Creates an asynchronous reader for a [`tokio::io::AsyncRead`] with
+    /// the configured settings.
+    #[cfg(feature = "tokio")]
+    pub fn build_tokio<R>(&self, reader: R) -> AsyncBufferedReader<TokioReader<R>>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        self.build_async(TokioReader(reader))
+    }
+}
+
+/// An asynchronous counterpart of [`BufferedReader`](crate::BufferedReader),
+/// reading from a [`futures_io::AsyncRead`] (or, with the `tokio` feature,
+/// a [`tokio::io::AsyncRead`]).
+///
+/// Uses the same [`Visitor`] trait, and parses exactly like the synchronous
+/// reader. Requires the `async` feature.
+///
+/// # Examples
+///
+/// ```
+/// use futures::{executor::block_on, io::Cursor, StreamExt as _};
+/// use pgn_reader::{AsyncBufferedReader, SanPlus, Visitor};
+///
+/// struct MoveCounter {
+///     moves: usize,
+/// }
+///
+/// impl Visitor for MoveCounter {
+///     type Result = usize;
+///
+///     fn san(&mut self, _san_plus: SanPlus) {
+///         self.moves += 1;
+///     }
+///
+///     fn end_game(&mut self) -> usize {
+///         std::mem::take(&mut self.moves)
+///     }
+/// }
+///
+/// block_on(async {
+///     let pgn = Cursor::new(b"1. e4 e5 2. Nf3 *\n\n1. d4 *");
+///     let mut counter = MoveCounter { moves: 0 };
+///
+///     let games = AsyncBufferedReader::new(pgn).into_stream(&mut counter);
+///     let moves: Vec<_> = games.map(|result| result.expect("valid pgn")).collect().await;
+///     assert_eq!(moves, [3, 1]);
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncBufferedReader<R> {
+    reader: R,
+    machine: Machine,
+    finished: bool,
+    // Set when a `TryVisitor` stopped reading.
+    pub(crate) stopped: bool,
+}
+
+impl AsyncBufferedReader<()> {
+    /// Creates a [`ReaderBuilder`] to configure the buffer size and length
+    /// limits.
+    pub fn builder() -> ReaderBuilder {
+        ReaderBuilder::new()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> AsyncBufferedReader<TokioReader<R>> {
+    /// Creates an asynchronous reader for a [`tokio::io::AsyncRead`].
+    pub fn from_tokio(reader: R) -> AsyncBufferedReader<TokioReader<R>> {
+        ReaderBuilder::new().build_tokio(reader)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufferedReader<R> {
+    pub fn new(reader: R) -> AsyncBufferedReader<R> {
+        ReaderBuilder::new().build_async(reader)
+    }
+
+    async fn fill(&mut self) -> io::Result<()> {
+        let len = loop {
+            match self.reader.read(self.machine.buffer_mut().spare()).await {
+                Ok(len) => break len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+        if len == 0 {
+            self.finished = true;
+        } else {
+            self.machine.buffer_mut().fill(len);
+        }
+        Ok(())
+    }
+
+    /// Read a single game, if any.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub async fn read_game<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<Option<V::Result>, Error> {
+        self.read_game_until(visitor, |_| false).await
+    }
+
+    /// Like [`AsyncBufferedReader::read_game()`], but skips the rest of the
+    /// movetext as soon as `skip_movetext` returns `true`.
+    pub(crate) async fn read_game_until<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+        skip_movetext: impl Fn(&V) -> bool,
+    ) -> Result<Option<V::Result>, Error> {
+        loop {
+            match self.machine.advance(self.finished) {
+                Status::Event => {
+                    if let Some(result) = self.machine.visit(visitor) {
+                        return Ok(Some(result));
+                    }
+                    if skip_movetext(visitor) {
+                        self.machine.skip_movetext();
+                    }
+                }
+                Status::Error(err) => return Err(Error::Syntax(err)),
+                Status::NeedMore => self.fill().await?,
+                Status::Done => return Ok(None),
+            }
+        }
+    }
+
+    /// Skip a single game, if any.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub async fn skip_game(&mut self) -> Result<bool, Error> {
+        self.read_game(&mut SkipVisitor).await.map(|r| r.is_some())
+    }
+
+    /// Read all games.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub async fn read_all<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Error> {
+        while self.read_game(visitor).await?.is_some() {}
+        Ok(())
+    }
+
+    /// Converts the reader into a [`Stream`] of game results.
+    pub fn into_stream<'a, V: Visitor>(
+        self,
+        visitor: &'a mut V,
+    ) -> impl Stream<Item = Result<V::Result, Error>> + 'a
+    where
+        R: 'a,
+    {
+        stream::unfold((self, visitor), |(mut reader, visitor)| async move {
+            match reader.read_game(visitor).await {
+                Ok(Some(result)) => Some((Ok(result), (reader, visitor))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), (reader, visitor))),
+            }
+        })
+    }
+
+    /// Absolute byte offset of the next unread byte, counted from the start
+    /// of the input.
+    pub fn position(&self) -> u64 {
+        self.machine.position()
+    }
+
+    /// The byte range of the last game that was read or skipped. See
+    /// [`BufferedReader::last_game_span()`](crate::BufferedReader::last_game_span).
+    pub fn last_game_span(&self) -> Option<Range<u64>> {
+        self.machine.last_game_span()
+    }
+
+    /// Returns whether the reader has another game to parse, but does not
+    /// actually parse it. Escape lines before the game are skipped and not
+    /// reported to any visitor.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    pub async fn has_more(&mut self) -> io::Result<bool> {
+        loop {
+            match self.machine.has_more(self.finished) {
+                Some(more) => return Ok(more),
+                None => self.fill().await?,
+            }
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader. Reading from it
+    /// directly skips input that has not been buffered yet.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Gets the remaining bytes in the buffer and the underlying reader.
+    pub fn into_inner(self) -> Chain<Cursor<Buffer>, R> {
+        Cursor::new(self.machine.into_buffer()).chain(self.reader)
+    }
+}
+
+/// Adapts a [`tokio::io::AsyncRead`] for use with [`AsyncBufferedReader`].
+/// Requires the `tokio` feature.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct TokioReader<R>(pub R);
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> AsyncRead for TokioReader<R> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        let mut read_buf = tokio::io::ReadBuf::new(buf);
+        std::pin::Pin::new(&mut self.0)
+            .poll_read(cx, &mut read_buf)
+            .map_ok(|()| read_buf.filled().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cmp::min,
+        fs,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures::{executor::block_on, StreamExt as _};
+
+    use super::*;
+    use crate::{game::GameBuilder, reader::BufferedReader};
+
+    /// Yields data in small pieces, and is pending before every piece.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        piece: usize,
+        pending: bool,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = min(min(self.piece, buf.len()), self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    fn sync_results(builder: &ReaderBuilder, data: &[u8]) -> Vec<String> {
+        let mut reader = builder.build(data);
+        let mut game_builder = GameBuilder::new();
+        let mut results = Vec::new();
+        loop {
+            match reader.read_game(&mut game_builder) {
+                Ok(None) => break results,
+                result => results.push(format!("{:?}", result.map(Option::unwrap))),
+            }
+        }
+    }
+
+    fn async_results(builder: &ReaderBuilder, data: &[u8], piece: usize) -> Vec<String> {
+        let trickle = Trickle {
+            data,
+            piece,
+            pending: false,
+        };
+        let mut game_builder = GameBuilder::new();
+        let games = builder.build_async(trickle).into_stream(&mut game_builder);
+        block_on(games.map(|result| format!("{result:?}")).collect())
+    }
+
+    #[test]
+    fn test_same_as_buffered_reader() {
+        let mut builder = BufferedReader::builder();
+        builder
+            .buffer_capacity(64)
+            .max_tag_line_length(40)
+            .max_comment_length(30);
+        let mut streaming = builder;
+        streaming.stream_long_comments(true);
+
+        for entry in fs::read_dir("fuzz/corpus/pgn").unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            for builder in [&ReaderBuilder::new(), &builder, &streaming] {
+                let expected = sync_results(builder, &data);
+                for piece in [1, 7, 1000] {
+                    assert_eq!(async_results(builder, &data, piece), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_has_more_and_into_inner() {
+        let pgn = b"\xef\xbb\xbf%escape\n\n1. e4 *\n%after\n\n1. d4 *\n\n%trailing\n";
+        let mut reader = AsyncBufferedReader::new(&pgn[..]);
+        block_on(async {
+            assert!(reader.has_more().await.unwrap());
+            assert!(reader.skip_game().await.unwrap());
+            assert!(reader.has_more().await.unwrap());
+
+            let mut rest = Vec::new();
+            reader.into_inner().read_to_end(&mut rest).await.unwrap();
+            assert_eq!(rest, b"1. d4 *\n\n%trailing\n");
+        });
+
+        let mut reader = AsyncBufferedReader::new(&pgn[..]);
+        block_on(async {
+            assert!(reader.skip_game().await.unwrap());
+            assert!(reader.skip_game().await.unwrap());
+            assert!(!reader.has_more().await.unwrap());
+            assert!(!reader.skip_game().await.unwrap());
+        });
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_tokio() {
+        let pgn = b"[Event \"?\"]\n\n1. e4 e5 *\n\n1. d4 { unterminated";
+        let mut reader = AsyncBufferedReader::from_tokio(&pgn[..]);
+        block_on(async {
+            let mut builder = GameBuilder::new();
+            let game = reader.read_game(&mut builder).await.unwrap().unwrap();
+            assert!(game.tag(b"Event").is_some());
+            assert!(matches!(
+                reader.read_game(&mut builder).await,
+                Err(Error::Syntax(_))
+            ));
+            assert!(!reader.has_more().await.unwrap());
+        });
+    }
+}
@@ -8,6 +8,20 @@ pub struct Buffer {
     buf: Box<[u8]>,
     pos: usize,
     filled: usize,
+    // Absolute offset of buf[0].
+    base: u64,
+    // Number of line breaks before buf[0].
+    lines: u64,
+    // Absolute offset of the start of the line that contains buf[0].
+    line_start: u64,
+}
+
+/// Location of a byte in the input.
+#[derive(Debug, Copy, Clone)]
+pub struct Location {
+    pub offset: u64,
+    pub line: u64,
+    pub column: u64,
 }
 
 impl Buffer {
@@ -16,6 +30,23 @@ impl Buffer {
             buf: vec![0; capacity].into_boxed_slice(),
             pos: 0,
             filled: 0,
+            base: 0,
+            lines: 0,
+            line_start: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Grows the buffer to at least `capacity` bytes, keeping the data.
+    pub fn reserve(&mut self, capacity: usize) {
+        if self.buf.len() < capacity {
+            self.backshift();
+            let mut buf = vec![0; capacity].into_boxed_slice();
+            buf[..self.filled].copy_from_slice(&self.buf[..self.filled]);
+            self.buf = buf;
         }
     }
 
@@ -27,6 +58,7 @@ impl Buffer {
 
     #[inline]
     pub fn discard_data(&mut self) {
+        self.forget(self.filled);
         self.pos = 0;
         self.filled = 0;
     }
@@ -50,26 +82,71 @@ impl Buffer {
         debug_assert!(n < self.buf.len());
 
         while self.data().len() < n {
-            if self.pos > 0 {
-                self.backshift();
-            }
-
-            let len = reader.read(&mut self.buf[self.filled..])?;
+            let len = reader.read(self.spare())?;
             if len == 0 {
                 break;
             }
 
-            self.filled += len;
+            self.fill(len);
         }
         Ok(self.data())
     }
 
+    /// Makes room for more data, and returns the unfilled part of the
+    /// buffer.
+    pub fn spare(&mut self) -> &mut [u8] {
+        if self.pos > 0 {
+            self.backshift();
+        }
+        &mut self.buf[self.filled..]
+    }
+
+    /// Marks `n` bytes of the unfilled part of the buffer as filled.
+    pub fn fill(&mut self, n: usize) {
+        self.filled = cmp::min(self.filled + n, self.buf.len());
+    }
+
     pub fn backshift(&mut self) {
+        self.forget(self.pos);
         let range = self.pos..self.filled;
         self.pos = 0;
         self.filled = range.len();
         self.buf.copy_within(range, 0);
     }
+
+    /// Accounts for the first `n` bytes of the underlying buffer, before they
+    /// are overwritten.
+    fn forget(&mut self, n: usize) {
+        let forgotten = &self.buf[..n];
+        self.lines += memchr::memchr_iter(b'\n', forgotten).count() as u64;
+        if let Some(newline) = memchr::memrchr(b'\n', forgotten) {
+            self.line_start = self.base + newline as u64 + 1;
+        }
+        self.base += n as u64;
+    }
+
+    /// Absolute offset of the current position.
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.base + self.pos as u64
+    }
+
+    /// Returns the location of the byte `n` positions before the current
+    /// position. These bytes must not have been discarded yet.
+    pub fn location_before(&self, n: usize) -> Location {
+        let pos = self.pos.saturating_sub(n);
+        let before = &self.buf[..pos];
+        let offset = self.base + pos as u64;
+        let line_start = match memchr::memrchr(b'\n', before) {
+            Some(newline) => self.base + newline as u64 + 1,
+            None => self.line_start,
+        };
+        Location {
+            offset,
+            line: self.lines + memchr::memchr_iter(b'\n', before).count() as u64 + 1,
+            column: offset - line_start + 1,
+        }
+    }
 }
 
 impl AsRef<[u8]> for Buffer {
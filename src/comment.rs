@@ -0,0 +1,479 @@
+use std::{fmt, time::Duration};
+
+use shakmaty::Square;
+
+/// A part of a comment, as returned by
+/// [`RawComment::parts()`](crate::RawComment::parts).
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub enum CommentPart<'a> {
+    /// Free text outside of commands, with surrounding whitespace trimmed.
+    Text(&'a [u8]),
+    /// Remaining time on the clock after the move (`[%clk 1:05:23]`).
+    Clock(Duration),
+    /// Time spent on the move (`[%emt 0:00:07.2]`).
+    ElapsedMoveTime(Duration),
+    /// Engine evaluation from the point of view of White, with optional
+    /// search depth (`[%eval -0.35,22]` or `[%eval #3]`).
+    Eval {
+        /// The score.
+        score: Score,
+        /// The search depth in plies, if given.
+        depth: Option<u32>,
+    },
+    /// Coloured square or arrow (`[%csl Ga1]` or `[%cal Ra2a4]`). A single
+    /// command can produce multiple shapes.
+    Shape(Shape),
+    /// An unknown or malformed command. A malformed entry of a `%csl` or
+    /// `%cal` command is returned on its own, with just that entry as
+    /// `args`, so that the other entries can still be parsed as shapes.
+    Command {
+        /// Name of the command without `%`, like `clk`.
+        name: &'a [u8],
+        /// Arguments of the command, with surrounding whitespace trimmed.
+        args: &'a [u8],
+    },
+}
+
+impl<'a> fmt::Debug for CommentPart<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CommentPart::Text(text) => f
+                .debug_tuple("Text")
+                .field(&String::from_utf8_lossy(text))
+                .finish(),
+            CommentPart::Clock(duration) => f.debug_tuple("Clock").field(&duration).finish(),
+            CommentPart::ElapsedMoveTime(duration) => {
+                f.debug_tuple("ElapsedMoveTime").field(&duration).finish()
+            }
+            CommentPart::Eval { score, depth } => f
+                .debug_struct("Eval")
+                .field("score", &score)
+                .field("depth", &depth)
+                .finish(),
+            CommentPart::Shape(shape) => f.debug_tuple("Shape").field(&shape).finish(),
+            CommentPart::Command { name, args } => f
+                .debug_struct("Command")
+                .field("name", &String::from_utf8_lossy(name))
+                .field("args", &String::from_utf8_lossy(args))
+                .finish(),
+        }
+    }
+}
+
+/// An engine score from the point of view of White.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Score {
+    /// Advantage in centipawns.
+    Centipawns(i32),
+    /// Mate in the given number of moves. Negative if Black is mating.
+    Mate(i32),
+}
+
+/// Colour of a [`Shape`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ShapeColor {
+    /// `G`
+    Green,
+    /// `R`
+    Red,
+    /// `Y`
+    Yellow,
+    /// `B`
+    Blue,
+}
+
+impl ShapeColor {
+    fn from_ascii(ch: u8) -> Option<ShapeColor> {
+        Some(match ch {
+            b'G' => ShapeColor::Green,
+            b'R' => ShapeColor::Red,
+            b'Y' => ShapeColor::Yellow,
+            b'B' => ShapeColor::Blue,
+            _ => return None,
+        })
+    }
+}
+
+/// A coloured square (if `orig == dest`) or arrow.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Shape {
+    /// The colour.
+    pub color: ShapeColor,
+    /// The square, or the tail of the arrow.
+    pub orig: Square,
+    /// The square, or the head of the arrow.
+    pub dest: Square,
+}
+
+/// Iterator returned by [`RawComment::parts()`](crate::RawComment::parts).
+#[derive(Clone)]
+#[must_use]
+pub struct CommentParts<'a> {
+    rest: &'a [u8],
+    shapes: &'a [u8],
+    arrows: bool,
+}
+
+impl<'a> CommentParts<'a> {
+    pub(crate) fn new(comment: &'a [u8]) -> CommentParts<'a> {
+        CommentParts {
+            rest: comment,
+            shapes: b"",
+            arrows: false,
+        }
+    }
+
+    fn next_shape(&mut self) -> Option<CommentPart<'a>> {
+        while !self.shapes.is_empty() {
+            let (token, rest) = match memchr::memchr(b',', self.shapes) {
+                Some(comma) => (&self.shapes[..comma], &self.shapes[comma + 1..]),
+                None => (self.shapes, &b""[..]),
+            };
+            self.shapes = rest;
+            let token = trim(token);
+            if token.is_empty() {
+                continue;
+            }
+            return Some(match parse_shape(token, self.arrows) {
+                Some(shape) => CommentPart::Shape(shape),
+                None => CommentPart::Command {
+                    name: if self.arrows { b"cal" } else { b"csl" },
+                    args: token,
+                },
+            });
+        }
+        None
+    }
+}
+
+impl<'a> fmt::Debug for CommentParts<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommentParts").finish_non_exhaustive()
+    }
+}
+
+impl<'a> Iterator for CommentParts<'a> {
+    type Item = CommentPart<'a>;
+
+    fn next(&mut self) -> Option<CommentPart<'a>> {
+        if let Some(shape) = self.next_shape() {
+            return Some(shape);
+        }
+
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+
+            match memchr::memmem::find(self.rest, b"[%") {
+                Some(0) => (),
+                Some(start) => {
+                    let text = trim(&self.rest[..start]);
+                    self.rest = &self.rest[start..];
+                    if text.is_empty() {
+                        continue;
+                    }
+                    return Some(CommentPart::Text(text));
+                }
+                None => {
+                    let text = trim(self.rest);
+                    self.rest = b"";
+                    if text.is_empty() {
+                        return None;
+                    }
+                    return Some(CommentPart::Text(text));
+                }
+            }
+
+            let end = match memchr::memchr(b']', self.rest) {
+                Some(end) => end,
+                None => {
+                    // Not a command after all.
+                    let text = trim(self.rest);
+                    self.rest = b"";
+                    return Some(CommentPart::Text(text));
+                }
+            };
+
+            let command = &self.rest[2..end];
+            self.rest = &self.rest[end + 1..];
+
+            let (name, args) = match command.iter().position(|ch| ch.is_ascii_whitespace()) {
+                Some(space) => (&command[..space], trim(&command[space..])),
+                None => (command, &b""[..]),
+            };
+
+            let part = match name {
+                b"clk" => parse_duration(args).map(CommentPart::Clock),
+                b"emt" => parse_duration(args).map(CommentPart::ElapsedMoveTime),
+                b"eval" => parse_eval(args),
+                b"csl" | b"cal" => {
+                    self.shapes = args;
+                    self.arrows = name == b"cal";
+                    if let Some(shape) = self.next_shape() {
+                        return Some(shape);
+                    }
+                    continue;
+                }
+                _ => None,
+            };
+
+            return Some(part.unwrap_or(CommentPart::Command { name, args }));
+        }
+    }
+}
+
+fn trim(mut s: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = s {
+        if first.is_ascii_whitespace() {
+            s = rest;
+        } else {
+            break;
+        }
+    }
+    while let [rest @ .., last] = s {
+        if last.is_ascii_whitespace() {
+            s = rest;
+        } else {
+            break;
+        }
+    }
+    s
+}
+
+/// Parses durations like `1:05:23`, `0:00:07.25` or `05:23`.
+fn parse_duration(s: &[u8]) -> Option<Duration> {
+    let (s, frac) = match memchr::memchr(b'.', s) {
+        Some(dot) => (&s[..dot], Some(&s[dot + 1..])),
+        None => (s, None),
+    };
+
+    let mut seconds = 0u64;
+    let mut components = 0;
+    for component in s.split(|&ch| ch == b':') {
+        if component.is_empty() {
+            return None;
+        }
+        seconds = seconds
+            .checked_mul(60)?
+            .checked_add(btoi::btou(component).ok()?)?;
+        components += 1;
+    }
+    if components > 3 {
+        return None;
+    }
+
+    let nanos = match frac {
+        Some(frac) => parse_fraction(frac, 9)?,
+        None => 0,
+    };
+
+    Some(Duration::new(seconds, nanos))
+}
+
+/// Parses up to `digits` decimal places. Further digits are truncated.
+fn parse_fraction(frac: &[u8], digits: usize) -> Option<u32> {
+    if frac.is_empty() || !frac.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let mut value = 0;
+    for i in 0..digits {
+        value = value * 10 + frac.get(i).map_or(0, |&ch| u32::from(ch - b'0'));
+    }
+    Some(value)
+}
+
+/// Parses evaluations like `0.25`, `-1.5,20`, `#3` or `#-2,30`.
+fn parse_eval(s: &[u8]) -> Option<CommentPart<'static>> {
+    let (s, depth) = match memchr::memchr(b',', s) {
+        Some(comma) => (
+            trim(&s[..comma]),
+            Some(btoi::btou(trim(&s[comma + 1..])).ok()?),
+        ),
+        None => (s, None),
+    };
+
+    let score = if let Some(mate) = s.strip_prefix(b"#") {
+        Score::Mate(btoi::btoi(mate).ok()?)
+    } else {
+        let (negative, s) = match s {
+            [b'-', rest @ ..] => (true, rest),
+            [b'+', rest @ ..] => (false, rest),
+            _ => (false, s),
+        };
+        let (pawns, centipawns) = match memchr::memchr(b'.', s) {
+            Some(dot) => (&s[..dot], parse_fraction(&s[dot + 1..], 2)?),
+            None => (s, 0),
+        };
+        let pawns: i32 = btoi::btou(pawns).ok()?;
+        let score = pawns
+            .checked_mul(100)?
+            .checked_add(i32::try_from(centipawns).ok()?)?;
+        Score::Centipawns(if negative { -score } else { score })
+    };
+
+    Some(CommentPart::Eval { score, depth })
+}
+
+fn parse_shape(s: &[u8], arrow: bool) -> Option<Shape> {
+    match (s, arrow) {
+        ([color, orig @ ..], false) if orig.len() == 2 => {
+            let orig = Square::from_ascii(orig).ok()?;
+            Some(Shape {
+                color: ShapeColor::from_ascii(*color)?,
+                orig,
+                dest: orig,
+            })
+        }
+        ([color, squares @ ..], true) if squares.len() == 4 => Some(Shape {
+            color: ShapeColor::from_ascii(*color)?,
+            orig: Square::from_ascii(&squares[..2]).ok()?,
+            dest: Square::from_ascii(&squares[2..]).ok()?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RawComment;
+
+    fn parts(comment: &[u8]) -> Vec<CommentPart<'_>> {
+        RawComment(comment).parts().collect()
+    }
+
+    #[test]
+    fn test_lichess() {
+        assert_eq!(
+            parts(b" [%eval -0.35,22] [%clk 1:05:23] Inaccuracy. Nf3 was best. "),
+            vec![
+                CommentPart::Eval {
+                    score: Score::Centipawns(-35),
+                    depth: Some(22)
+                },
+                CommentPart::Clock(Duration::from_secs(3923)),
+                CommentPart::Text(b"Inaccuracy. Nf3 was best."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(
+            parts(b"[%eval #-3]"),
+            vec![CommentPart::Eval {
+                score: Score::Mate(-3),
+                depth: None
+            }]
+        );
+        assert_eq!(
+            parts(b"[%eval 1.5]"),
+            vec![CommentPart::Eval {
+                score: Score::Centipawns(150),
+                depth: None
+            }]
+        );
+        assert_eq!(
+            parts(b"[%eval +12]"),
+            vec![CommentPart::Eval {
+                score: Score::Centipawns(1200),
+                depth: None
+            }]
+        );
+        assert_eq!(
+            parts(b"[%eval 0.17, 20]"),
+            vec![CommentPart::Eval {
+                score: Score::Centipawns(17),
+                depth: Some(20)
+            }]
+        );
+        assert_eq!(
+            parts(b"[%eval 0.x]"),
+            vec![CommentPart::Command {
+                name: b"eval",
+                args: b"0.x"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(
+            parts(b"[%emt 0:00:07.25][%clk 05:02]"),
+            vec![
+                CommentPart::ElapsedMoveTime(Duration::from_millis(7250)),
+                CommentPart::Clock(Duration::from_secs(302)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shapes() {
+        assert_eq!(
+            parts(b"Threat [%csl Ga1,Rh8][%cal Ye2e4,Bb1c3, Xa1a2]"),
+            vec![
+                CommentPart::Text(b"Threat"),
+                CommentPart::Shape(Shape {
+                    color: ShapeColor::Green,
+                    orig: Square::A1,
+                    dest: Square::A1
+                }),
+                CommentPart::Shape(Shape {
+                    color: ShapeColor::Red,
+                    orig: Square::H8,
+                    dest: Square::H8
+                }),
+                CommentPart::Shape(Shape {
+                    color: ShapeColor::Yellow,
+                    orig: Square::E2,
+                    dest: Square::E4
+                }),
+                CommentPart::Shape(Shape {
+                    color: ShapeColor::Blue,
+                    orig: Square::B1,
+                    dest: Square::C3
+                }),
+                CommentPart::Command {
+                    name: b"cal",
+                    args: b"Xa1a2"
+                },
+            ]
+        );
+        assert_eq!(
+            parts(b"[%csl Gz9, Ga,Ya8 ,]"),
+            vec![
+                CommentPart::Command {
+                    name: b"csl",
+                    args: b"Gz9"
+                },
+                CommentPart::Command {
+                    name: b"csl",
+                    args: b"Ga"
+                },
+                CommentPart::Shape(Shape {
+                    color: ShapeColor::Yellow,
+                    orig: Square::A8,
+                    dest: Square::A8
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert_eq!(
+            parts(b"before [%tqu \"En\",\"\",\"\",\"\",\"e2e4\",\"\",10] after [%unterminated"),
+            vec![
+                CommentPart::Text(b"before"),
+                CommentPart::Command {
+                    name: b"tqu",
+                    args: b"\"En\",\"\",\"\",\"\",\"e2e4\",\"\",10"
+                },
+                CommentPart::Text(b"after"),
+                CommentPart::Text(b"[%unterminated"),
+            ]
+        );
+        assert_eq!(parts(b"   "), vec![]);
+    }
+}
@@ -0,0 +1,173 @@
+use std::{error, fmt, io};
+
+/// Error when reading games.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// I/O error from the underlying reader.
+    Io(io::Error),
+    /// Irrecoverable syntax error in the input.
+    Syntax(SyntaxError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => err.fmt(f),
+            Error::Syntax(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Syntax(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<SyntaxError> for Error {
+    fn from(err: SyntaxError) -> Error {
+        Error::Syntax(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        match err {
+            Error::Io(err) => err,
+            Error::Syntax(err) => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// The kind of a [`SyntaxError`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
+pub enum SyntaxErrorKind {
+    /// A tag pair is not closed before the end of the line or input.
+    UnterminatedTag,
+    /// A `{` comment is not closed before the end of the input.
+    UnterminatedComment,
+    /// A tag line exceeds the configured maximum length.
+    TagTooLong,
+    /// A `{` comment exceeds the configured maximum length.
+    CommentTooLong,
+    /// A movetext token was not recognized, and the visitor decided to
+    /// abort the game.
+    InvalidToken,
+}
+
+impl SyntaxErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SyntaxErrorKind::UnterminatedTag => "unterminated tag",
+            SyntaxErrorKind::UnterminatedComment => "unterminated comment",
+            SyntaxErrorKind::TagTooLong => "tag too long",
+            SyntaxErrorKind::CommentTooLong => "comment too long",
+            SyntaxErrorKind::InvalidToken => "invalid token",
+        }
+    }
+}
+
+impl fmt::Display for SyntaxErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+/// A syntax error, with the location of the offending token.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SyntaxError {
+    kind: SyntaxErrorKind,
+    offset: u64,
+    line: u64,
+    column: u64,
+    game: u64,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(
+        kind: SyntaxErrorKind,
+        offset: u64,
+        line: u64,
+        column: u64,
+        game: u64,
+    ) -> SyntaxError {
+        SyntaxError {
+            kind,
+            offset,
+            line,
+            column,
+            game,
+        }
+    }
+
+    /// Shifts the offset of an error found in a part of the input that
+    /// starts at `start`.
+    pub(crate) fn rebase(mut self, start: u64) -> SyntaxError {
+        self.offset += start;
+        self
+    }
+
+    /// Shifts the line number and game index of an error found in a part of
+    /// the input that starts at the beginning of a line, after `lines` line
+    /// breaks and `games` games.
+    pub(crate) fn relocate(mut self, lines: u64, games: u64) -> SyntaxError {
+        self.line += lines;
+        self.game += games;
+        self
+    }
+
+    /// Replaces the game index.
+    pub(crate) fn with_game(mut self, game: u64) -> SyntaxError {
+        self.game = game;
+        self
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> SyntaxErrorKind {
+        self.kind
+    }
+
+    /// Absolute byte offset of the offending token, counted from the start
+    /// of the input.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Line number of the offending token, starting at 1.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// Column of the offending token in bytes, starting at 1.
+    pub fn column(&self) -> u64 {
+        self.column
+    }
+
+    /// Index of the game that contains the offending token, starting at 0.
+    pub fn game(&self) -> u64 {
+        self.game
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {} (byte offset {}, game index {})",
+            self.kind, self.line, self.column, self.offset, self.game
+        )
+    }
+}
+
+impl error::Error for SyntaxError {}
@@ -0,0 +1,402 @@
+use std::{
+    io::{self, Read},
+    ops::Range,
+};
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    error::Error,
+    machine::{Machine, Status},
+    reader::ReaderBuilder,
+    types::{InvalidTokenKind, MoveNumber, Nag, RawComment, RawTag},
+    visitor::Visitor,
+};
+
+impl ReaderBuilder {
+    /// Creates an event reader with the configured settings.
+    pub fn build_events<R: Read>(&self, reader: R) -> EventReader<R> {
+        EventReader {
+            reader,
+            machine: Machine::new(self),
+            finished: false,
+        }
+    }
+}
+
+/// A parser event, as yielded by [`EventReader::next_event()`].
+///
+/// Events correspond to the methods of the [`Visitor`] and arrive in the
+/// same order. Tag and comment payloads borrow from the buffer of the
+/// reader, until the next event is requested.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Event<'a> {
+    /// Start of a game. See [`Visitor::begin_tags()`].
+    BeginTags,
+    /// A tag pair. See [`Visitor::tag()`].
+    Tag { name: &'a [u8], value: RawTag<'a> },
+    /// End of the tags and start of the movetext. See
+    /// [`Visitor::begin_movetext()`] and [`EventReader::skip_movetext()`].
+    BeginMovetext,
+    /// See [`Visitor::move_number()`].
+    MoveNumber(MoveNumber),
+    /// See [`Visitor::san()`].
+    San(SanPlus),
+    /// See [`Visitor::invalid_token()`]. To abort the game, use
+    /// [`EventReader::abort()`].
+    InvalidToken {
+        token: &'a [u8],
+        kind: InvalidTokenKind,
+    },
+    /// See [`Visitor::nag()`].
+    Nag(Nag),
+    /// See [`Visitor::comment()`].
+    Comment(RawComment<'a>),
+    /// See [`Visitor::line_comment()`].
+    LineComment(RawComment<'a>),
+    /// See [`Visitor::escape_line()`].
+    EscapeLine(&'a [u8]),
+    /// See [`Visitor::begin_long_comment()`].
+    BeginLongComment,
+    /// See [`Visitor::partial_comment()`].
+    PartialComment(RawComment<'a>),
+    /// See [`Visitor::end_long_comment()`].
+    EndLongComment,
+    /// See [`Visitor::begin_variation()`] and
+    /// [`EventReader::skip_variation()`].
+    BeginVariation,
+    /// See [`Visitor::end_variation()`].
+    EndVariation,
+    /// See [`Visitor::outcome()`].
+    Outcome(Option<Outcome>),
+    /// End of a game. See [`Visitor::end_game()`].
+    EndGame,
+}
+
+/// A pull-based reader that yields [events](Event) instead of calling a
+/// [`Visitor`].
+///
+/// Parses exactly like the [`BufferedReader`](crate::BufferedReader) with
+/// the same settings, except that long comments (see
+/// [`ReaderBuilder::stream_long_comments()`]) may be split into different
+/// pieces.
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::{Event, EventReader};
+///
+/// let pgn = b"[White \"Deep Blue\"]\n[Black \"Kasparov\"]\n\n1. e4 (1. d4) c5 *";
+///
+/// let mut reader = EventReader::new(&pgn[..]);
+/// let mut sans = 0;
+/// while let Some(event) = reader.next_event()? {
+///     match event {
+///         Event::Tag { name: b"White", value } => {
+///             assert_eq!(value.decode_utf8()?, "Deep Blue");
+///         }
+///         Event::San(_) => sans += 1,
+///         Event::BeginVariation => reader.skip_variation(),
+///         _ => (),
+///     }
+/// }
+///
+/// assert_eq!(sans, 2);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct EventReader<R> {
+    reader: R,
+    machine: Machine,
+    finished: bool,
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(reader: R) -> EventReader<R> {
+        ReaderBuilder::new().build_events(reader)
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Absolute byte offset of the next byte that has not been processed,
+    /// counted from the start of the input.
+    pub fn position(&self) -> u64 {
+        self.machine.position()
+    }
+
+    /// The byte range of the last game that was completed. See
+    /// [`BufferedReader::last_game_span()`](crate::BufferedReader::last_game_span).
+    pub fn last_game_span(&self) -> Option<Range<u64>> {
+        self.machine.last_game_span()
+    }
+
+    /// Reads the next event, or returns `None` at the end of the input.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors. The rest of the game is skipped, and
+    ///   reading can continue with the next game.
+    pub fn next_event(&mut self) -> Result<Option<Event<'_>>, Error> {
+        loop {
+            match self.machine.advance(self.finished) {
+                Status::Event => return Ok(Some(self.machine.event())),
+                Status::Error(err) => return Err(Error::Syntax(err)),
+                Status::NeedMore => self.fill()?,
+                Status::Done => return Ok(None),
+            }
+        }
+    }
+
+    /// Skips the rest of the movetext of the current game. Call this after
+    /// [`Event::BeginMovetext`] to go straight to [`Event::EndGame`].
+    pub fn skip_movetext(&mut self) {
+        self.machine.skip_movetext();
+    }
+
+    /// Aborts the current game, like [`TokenAction::Abort`]. Call this after
+    /// [`Event::InvalidToken`] to skip the rest of the game and get a
+    /// [`SyntaxErrorKind::InvalidToken`] error from the next call to
+    /// [`EventReader::next_event()`]. Has no effect after other events.
+    ///
+    /// [`TokenAction::Abort`]: crate::TokenAction::Abort
+    /// [`SyntaxErrorKind::InvalidToken`]: crate::SyntaxErrorKind::InvalidToken
+    pub fn abort(&mut self) {
+        self.machine.abort();
+    }
+
+    /// Skips the current variation. Call this after
+    /// [`Event::BeginVariation`] to go straight to the matching
+    /// [`Event::EndVariation`].
+    pub fn skip_variation(&mut self) {
+        self.machine.skip_variation();
+    }
+
+    /// Reads the rest of the current game (or the next game) by passing
+    /// events to a visitor, like
+    /// [`BufferedReader::read_game()`](crate::BufferedReader::read_game).
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub fn read_game<V: Visitor>(&mut self, visitor: &mut V) -> Result<Option<V::Result>, Error> {
+        loop {
+            match self.machine.advance(self.finished) {
+                Status::Event => {
+                    if let Some(result) = self.machine.visit(visitor) {
+                        return Ok(Some(result));
+                    }
+                }
+                Status::Error(err) => return Err(Error::Syntax(err)),
+                Status::NeedMore => self.fill()?,
+                Status::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let len = loop {
+            match self.reader.read(self.machine.buffer_mut().spare()) {
+                Ok(len) => break len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+        if len == 0 {
+            self.finished = true;
+        } else {
+            self.machine.buffer_mut().fill(len);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{error::SyntaxErrorKind, reader::BufferedReader, types::Skip};
+
+    /// Records visitor calls in the shape of events.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl Visitor for Recorder {
+        type Result = ();
+
+        fn begin_tags(&mut self) {
+            self.events.push(format!("{:?}", Event::BeginTags));
+        }
+
+        fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+            self.events
+                .push(format!("{:?}", Event::Tag { name, value }));
+        }
+
+        fn begin_movetext(&mut self) -> Skip {
+            self.events.push(format!("{:?}", Event::BeginMovetext));
+            Skip(false)
+        }
+
+        fn move_number(&mut self, move_number: MoveNumber) {
+            self.events
+                .push(format!("{:?}", Event::MoveNumber(move_number)));
+        }
+
+        fn san(&mut self, san_plus: SanPlus) {
+            self.events.push(format!("{:?}", Event::San(san_plus)));
+        }
+
+        fn nag(&mut self, nag: Nag) {
+            self.events.push(format!("{:?}", Event::Nag(nag)));
+        }
+
+        fn comment(&mut self, comment: RawComment<'_>) {
+            self.events.push(format!("{:?}", Event::Comment(comment)));
+        }
+
+        fn line_comment(&mut self, comment: RawComment<'_>) {
+            self.events
+                .push(format!("{:?}", Event::LineComment(comment)));
+        }
+
+        fn escape_line(&mut self, line: &[u8]) {
+            self.events.push(format!("{:?}", Event::EscapeLine(line)));
+        }
+
+        fn begin_variation(&mut self) -> Skip {
+            self.events.push(format!("{:?}", Event::BeginVariation));
+            Skip(self.events.len() & 1 == 0)
+        }
+
+        fn end_variation(&mut self) {
+            self.events.push(format!("{:?}", Event::EndVariation));
+        }
+
+        fn outcome(&mut self, outcome: Option<Outcome>) {
+            self.events.push(format!("{:?}", Event::Outcome(outcome)));
+        }
+
+        fn end_game(&mut self) {
+            self.events.push(format!("{:?}", Event::EndGame));
+        }
+    }
+
+    #[test]
+    fn test_same_as_buffered_reader() {
+        let mut small = BufferedReader::builder();
+        small
+            .buffer_capacity(64)
+            .max_tag_line_length(40)
+            .max_comment_length(30);
+
+        let mut inputs: Vec<Vec<u8>> = fs::read_dir("fuzz/corpus/pgn")
+            .unwrap()
+            .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+            .collect();
+        inputs.push(
+            b"%a\n[A \"1\"] %b\n%c\n\n1. e4 ; line\n%d\ne5 ;0123456789012345678901234567890123456789\n\n%e"
+                .to_vec(),
+        );
+
+        for data in inputs {
+            for builder in [&ReaderBuilder::new(), &small] {
+                let mut expected = Recorder::default();
+                let mut reader = builder.build(&data[..]);
+                while !matches!(reader.read_game(&mut expected), Ok(None)) {
+                    expected.events.push("end".to_owned());
+                }
+
+                let mut actual = Recorder::default();
+                let mut events = builder.build_events(&data[..]);
+                while !matches!(events.read_game(&mut actual), Ok(None)) {
+                    actual.events.push("end".to_owned());
+                }
+
+                assert_eq!(actual.events, expected.events);
+            }
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let pgn = b"[Event \"Test\"]\n\n1. e4 $1 { good } (1. d4 (1. c4)) 1-0\n\n1. Nf3 *\n";
+        let mut reader = EventReader::new(&pgn[..]);
+        let mut events = Vec::new();
+        while let Some(event) = reader.next_event().unwrap() {
+            events.push(format!("{event:?}"));
+            if let Event::Comment(comment) = event {
+                assert_eq!(comment.as_bytes(), b" good ");
+            }
+            if events.len() == 8 {
+                reader.skip_variation();
+            }
+        }
+
+        assert_eq!(
+            events,
+            [
+                "BeginTags",
+                "Tag { name: [69, 118, 101, 110, 116], value: \"Test\" }",
+                "BeginMovetext",
+                "MoveNumber(MoveNumber { number: 1, dots: 1 })",
+                "San(SanPlus { san: Normal { role: Pawn, file: None, rank: None, capture: false, to: E4, promotion: None }, suffix: None })",
+                "Nag(Nag(1))",
+                "Comment(\" good \")",
+                "BeginVariation",
+                "EndVariation",
+                "Outcome(Some(Decisive { winner: White }))",
+                "EndGame",
+                "BeginTags",
+                "BeginMovetext",
+                "MoveNumber(MoveNumber { number: 1, dots: 1 })",
+                "San(SanPlus { san: Normal { role: Knight, file: None, rank: None, capture: false, to: F3, promotion: None }, suffix: None })",
+                "Outcome(None)",
+                "EndGame",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_abort() {
+        let pgn = b"1. e4 & e5 *\n\n1. d4 *\n";
+        let mut reader = EventReader::new(&pgn[..]);
+        let mut events = Vec::new();
+        let err = loop {
+            match reader.next_event() {
+                Ok(Some(Event::InvalidToken { token, .. })) => {
+                    assert_eq!(token, b"&");
+                    reader.abort();
+                }
+                Ok(Some(Event::San(san_plus))) => events.push(san_plus.to_string()),
+                Ok(Some(_)) => (),
+                Ok(None) => panic!("expected syntax error"),
+                Err(Error::Syntax(err)) => break err,
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+        };
+        assert_eq!(err.kind(), SyntaxErrorKind::InvalidToken);
+        assert_eq!(err.column(), 7);
+        assert_eq!(err.game(), 0);
+        assert_eq!(events, ["e4"]);
+
+        while let Some(event) = reader.next_event().unwrap() {
+            if let Event::San(san_plus) = event {
+                events.push(san_plus.to_string());
+            }
+        }
+        assert_eq!(events, ["e4", "d4"]);
+    }
+}
@@ -0,0 +1,427 @@
+use std::mem;
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    types::{Nag, RawComment, RawTag, Skip},
+    visitor::Visitor,
+};
+
+/// A game with its complete move tree, owned in memory.
+///
+/// Build games with [`GameBuilder`] and replay them through any
+/// [`Visitor`] with [`Game::visit()`].
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, GameBuilder, SanPlus};
+///
+/// let pgn = b"[White \"Garry Kasparov\"]\n\n1. e4 (1. d4 d5) 1... c5 { Sicilian } *";
+///
+/// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let game = reader.read_game(&mut GameBuilder::new())?.expect("game");
+///
+/// assert_eq!(game.tags.len(), 1);
+/// let sans: Vec<SanPlus> = game.root.mainline().map(|node| node.san_plus).collect();
+/// assert_eq!(sans, vec!["e4".parse().unwrap(), "c5".parse().unwrap()]);
+/// assert_eq!(game.root.children.len(), 2);
+/// # Ok::<_, io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Game {
+    /// Tag pairs in their original order. Values are raw, i.e., still
+    /// escaped as in [`RawTag`].
+    pub tags: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Comments that are not attached to any move, for example in a game
+    /// without moves.
+    pub comments: Vec<Vec<u8>>,
+    /// The starting position of the game.
+    pub root: Node,
+    /// The game termination marker. Replayed as `*` if there was none.
+    pub outcome: Option<Outcome>,
+}
+
+impl Game {
+    /// Returns the raw value of the first tag with the given name.
+    pub fn tag(&self, name: &[u8]) -> Option<RawTag<'_>> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| RawTag(value))
+    }
+
+    /// Replays the game through a visitor, as if it had been read by a
+    /// reader. Honors [`Skip`] returned by the visitor.
+    pub fn visit<V: Visitor>(&self, visitor: &mut V) -> V::Result {
+        visitor.begin_tags();
+        for (name, value) in &self.tags {
+            visitor.tag(name, RawTag(value));
+        }
+
+        if let Skip(false) = visitor.begin_movetext() {
+            for comment in &self.comments {
+                visitor.comment(RawComment(comment));
+            }
+            self.root.visit(visitor);
+            visitor.outcome(self.outcome);
+        }
+
+        visitor.end_game()
+    }
+}
+
+/// A position in the game tree.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Node {
+    /// Moves from this position. The first child is the main continuation,
+    /// followed by alternatives in their original order.
+    pub children: Vec<ChildNode>,
+}
+
+impl Node {
+    /// Iterates over the main continuation starting at this node.
+    pub fn mainline(&self) -> Mainline<'_> {
+        Mainline { node: self }
+    }
+
+    fn visit<V: Visitor>(&self, visitor: &mut V) {
+        let mut node = self;
+        while let Some((main, alternatives)) = node.children.split_first() {
+            main.visit_move(visitor);
+
+            for alternative in alternatives {
+                if let Skip(false) = visitor.begin_variation() {
+                    alternative.visit_move(visitor);
+                    alternative.node.visit(visitor);
+                }
+                visitor.end_variation();
+            }
+
+            node = &main.node;
+        }
+    }
+}
+
+/// A move in the game tree, along with its annotations and the resulting
+/// position.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChildNode {
+    /// Comments before the move, like at the start of a variation.
+    pub starting_comments: Vec<Vec<u8>>,
+    /// The move.
+    pub san_plus: SanPlus,
+    /// Numeric annotation glyphs of the move.
+    pub nags: Vec<Nag>,
+    /// Comments after the move.
+    pub comments: Vec<Vec<u8>>,
+    /// The position after the move.
+    pub node: Node,
+}
+
+impl ChildNode {
+    fn visit_move<V: Visitor>(&self, visitor: &mut V) {
+        for comment in &self.starting_comments {
+            visitor.comment(RawComment(comment));
+        }
+        visitor.san(self.san_plus);
+        for &nag in &self.nags {
+            visitor.nag(nag);
+        }
+        for comment in &self.comments {
+            visitor.comment(RawComment(comment));
+        }
+    }
+}
+
+/// Iterator returned by [`Node::mainline()`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Mainline<'a> {
+    node: &'a Node,
+}
+
+impl<'a> Iterator for Mainline<'a> {
+    type Item = &'a ChildNode;
+
+    fn next(&mut self) -> Option<&'a ChildNode> {
+        let child = self.node.children.first()?;
+        self.node = &child.node;
+        Some(child)
+    }
+}
+
+/// A move that is not yet linked into the tree. Alternatives to the move are
+/// kept alongside it, until the line is complete.
+#[derive(Debug, Clone)]
+struct Entry {
+    starting_comments: Vec<Vec<u8>>,
+    san_plus: SanPlus,
+    nags: Vec<Nag>,
+    comments: Vec<Vec<u8>>,
+    variations: Vec<Line>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Line {
+    entries: Vec<Entry>,
+    pending_comments: Vec<Vec<u8>>,
+}
+
+impl Line {
+    fn into_node(self) -> Node {
+        let mut node = Node::default();
+        for entry in self.entries.into_iter().rev() {
+            let mut children = Vec::with_capacity(1 + entry.variations.len());
+            children.push(ChildNode {
+                starting_comments: entry.starting_comments,
+                san_plus: entry.san_plus,
+                nags: entry.nags,
+                comments: entry.comments,
+                node,
+            });
+            for variation in entry.variations {
+                children.extend(variation.into_node().children);
+            }
+            node = Node { children };
+        }
+        node
+    }
+}
+
+/// A [`Visitor`] that builds a [`Game`].
+///
+/// Each `(` starts an alternative to the preceding move, so that
+/// variations become siblings of that move in the tree. Unmatched `)` are
+/// ignored, and variations that are still open at the end of the game are
+/// closed implicitly.
+#[derive(Debug, Default)]
+pub struct GameBuilder {
+    game: Game,
+    stack: Vec<Line>,
+    long_comment: Vec<u8>,
+    skipped_variation: bool,
+}
+
+impl GameBuilder {
+    /// Creates a new game builder.
+    pub fn new() -> GameBuilder {
+        GameBuilder::default()
+    }
+
+    fn line(&mut self) -> &mut Line {
+        if self.stack.is_empty() {
+            self.stack.push(Line::default());
+        }
+        self.stack.last_mut().expect("non-empty stack")
+    }
+
+    fn push_comment(&mut self, comment: Vec<u8>) {
+        let line = self.line();
+        match line.entries.last_mut() {
+            Some(entry) => entry.comments.push(comment),
+            None => line.pending_comments.push(comment),
+        }
+    }
+
+    fn close_variation(&mut self) {
+        if self.stack.len() > 1 {
+            let variation = self.stack.pop().expect("variation");
+            let parent = self.line().entries.last_mut().expect("parent move");
+            if variation.entries.is_empty() {
+                parent.comments.extend(variation.pending_comments);
+            } else {
+                parent.variations.push(variation);
+            }
+        }
+    }
+}
+
+impl Visitor for GameBuilder {
+    type Result = Game;
+
+    fn begin_tags(&mut self) {
+        self.game = Game::default();
+        self.stack.clear();
+        self.skipped_variation = false;
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        self.game
+            .tags
+            .push((name.to_owned(), value.as_bytes().to_owned()));
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        let line = self.line();
+        let starting_comments = mem::take(&mut line.pending_comments);
+        line.entries.push(Entry {
+            starting_comments,
+            san_plus,
+            nags: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+        });
+    }
+
+    fn nag(&mut self, nag: Nag) {
+        if let Some(entry) = self.line().entries.last_mut() {
+            entry.nags.push(nag);
+        }
+    }
+
+    fn comment(&mut self, comment: RawComment<'_>) {
+        self.push_comment(comment.as_bytes().to_owned());
+    }
+
+    fn begin_long_comment(&mut self) {
+        self.long_comment.clear();
+    }
+
+    fn partial_comment(&mut self, chunk: RawComment<'_>) {
+        self.long_comment.extend_from_slice(chunk.as_bytes());
+    }
+
+    fn end_long_comment(&mut self) {
+        let comment = mem::take(&mut self.long_comment);
+        self.push_comment(comment);
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        if self.line().entries.is_empty() {
+            // There is no move that the variation could be an alternative to.
+            self.skipped_variation = true;
+            return Skip(true);
+        }
+        self.stack.push(Line::default());
+        Skip(false)
+    }
+
+    fn end_variation(&mut self) {
+        if mem::take(&mut self.skipped_variation) {
+            return;
+        }
+        self.close_variation();
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.game.outcome = outcome;
+    }
+
+    fn end_game(&mut self) -> Game {
+        while self.stack.len() > 1 {
+            self.close_variation();
+        }
+        let mut game = mem::take(&mut self.game);
+        if let Some(line) = self.stack.pop() {
+            game.comments = line.pending_comments;
+            game.root = Line {
+                entries: line.entries,
+                pending_comments: Vec::new(),
+            }
+            .into_node();
+        }
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::{BufferedReader, Writer};
+
+    fn read_game(pgn: &[u8]) -> Game {
+        BufferedReader::new(io::Cursor::new(pgn))
+            .read_game(&mut GameBuilder::new())
+            .expect("read")
+            .expect("game")
+    }
+
+    fn write_game(game: &Game) -> Vec<u8> {
+        let mut writer = Writer::new(Vec::new());
+        game.visit(&mut writer).expect("write");
+        writer.into_inner()
+    }
+
+    fn san(san: &str) -> SanPlus {
+        san.parse().expect("valid san")
+    }
+
+    #[test]
+    fn test_variations() {
+        let game = read_game(b"1. e4 (1. d4 d5 (1... Nf6) (1... f5)) (1. c4) 1... e5 2. Nf3 *");
+
+        let children: Vec<SanPlus> = game.root.children.iter().map(|c| c.san_plus).collect();
+        assert_eq!(children, vec![san("e4"), san("d4"), san("c4")]);
+
+        let d4 = &game.root.children[1];
+        let replies: Vec<SanPlus> = d4.node.children.iter().map(|c| c.san_plus).collect();
+        assert_eq!(replies, vec![san("d5"), san("Nf6"), san("f5")]);
+
+        let mainline: Vec<SanPlus> = game.root.mainline().map(|c| c.san_plus).collect();
+        assert_eq!(mainline, vec![san("e4"), san("e5"), san("Nf3")]);
+    }
+
+    #[test]
+    fn test_comments_and_nags() {
+        let game = read_game(b"{ intro } 1. e4! { best by test } (1. d4 $2) 1... e5 *");
+
+        let e4 = &game.root.children[0];
+        assert_eq!(e4.starting_comments, vec![b" intro ".to_vec()]);
+        assert_eq!(e4.nags, vec![Nag::GOOD_MOVE]);
+        assert_eq!(e4.comments, vec![b" best by test ".to_vec()]);
+        assert_eq!(game.root.children[1].nags, vec![Nag::MISTAKE]);
+
+        let game = read_game(b"{ no moves } *");
+        assert_eq!(game.comments, vec![b" no moves ".to_vec()]);
+        assert!(game.root.children.is_empty());
+    }
+
+    #[test]
+    fn test_unbalanced_variations() {
+        let game = read_game(b"1. e4 ) e5 ( 1... c5 (");
+        let mainline: Vec<SanPlus> = game.root.mainline().map(|c| c.san_plus).collect();
+        assert_eq!(mainline, vec![san("e4"), san("e5")]);
+        assert_eq!(game.root.children[0].node.children.len(), 2);
+
+        let game = read_game(b"( 1. e4 ) 1. d4");
+        let mainline: Vec<SanPlus> = game.root.mainline().map(|c| c.san_plus).collect();
+        assert_eq!(mainline, vec![san("d4")]);
+
+        let game = read_game(b"1. e4 ( ( 1. d4 ) 1. c4 ) e5 *");
+        let mainline: Vec<SanPlus> = game.root.mainline().map(|c| c.san_plus).collect();
+        assert_eq!(mainline, vec![san("e4"), san("e5")]);
+        let children: Vec<SanPlus> = game.root.children.iter().map(|c| c.san_plus).collect();
+        assert_eq!(children, vec![san("e4"), san("c4")]);
+    }
+
+    #[test]
+    fn test_visit() {
+        let pgn = b"[Event \"Test\"]\n\n{A} 1. e4 $14 {B} ( {C} 1. d4 d5 ( 1... Nf6 ) ) 1... e5 2. Nf3 1-0\n\n";
+        let game = read_game(pgn);
+        assert_eq!(
+            game.tag(b"Event").map(|v| v.decode_utf8_lossy()),
+            Some("Test".into())
+        );
+        assert_eq!(write_game(&game), pgn);
+        assert_eq!(read_game(&write_game(&game)), game);
+    }
+
+    #[test]
+    fn test_long_comments() {
+        let long = "x".repeat(500);
+        let pgn = format!("1. e4 {{{long}}} *");
+        let game = BufferedReader::builder()
+            .max_tag_line_length(64)
+            .max_comment_length(64)
+            .stream_long_comments(true)
+            .build(io::Cursor::new(pgn))
+            .read_game(&mut GameBuilder::new())
+            .expect("read")
+            .expect("game");
+        assert_eq!(game.root.children[0].comments, vec![long.into_bytes()]);
+    }
+}
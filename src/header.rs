@@ -0,0 +1,374 @@
+use std::mem;
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    types::{InvalidTokenKind, MoveNumber, Nag, RawComment, RawTag, Skip, TokenAction},
+    visitor::Visitor,
+};
+
+/// The tag pairs of a game, owned in memory.
+///
+/// Tags are kept in their original order, including duplicates.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Header {
+    /// Tag pairs in their original order. Values are raw, i.e., still
+    /// escaped as in [`RawTag`].
+    pub tags: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Header {
+    /// Creates an empty header.
+    pub fn new() -> Header {
+        Header::default()
+    }
+
+    /// Appends a tag pair, even if a tag with the same name already exists.
+    pub fn push(&mut self, name: &[u8], value: RawTag<'_>) {
+        self.tags
+            .push((name.to_owned(), value.as_bytes().to_owned()));
+    }
+
+    /// Returns the raw value of the first tag with the given name.
+    pub fn tag(&self, name: &[u8]) -> Option<RawTag<'_>> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| RawTag(value))
+    }
+
+    /// Returns the raw values of all tags with the given name, in their
+    /// original order.
+    pub fn tags_named<'a>(&'a self, name: &'a [u8]) -> impl Iterator<Item = RawTag<'a>> + 'a {
+        self.tags
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, value)| RawTag(value))
+    }
+
+    /// Iterates over all tag pairs in their original order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], RawTag<'_>)> {
+        self.tags
+            .iter()
+            .map(|(name, value)| (&name[..], RawTag(value)))
+    }
+
+    /// The number of tag pairs.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Tests if there are no tag pairs.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+}
+
+/// A [`Visitor`] that collects the [`Header`] of each game and skips the
+/// movetext.
+///
+/// To process the movetext of some of the games, use [`HeaderFilter`].
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, HeaderVisitor};
+///
+/// let pgn = b"[White \"Deep Blue\"]\n[Black \"Garry Kasparov\"]\n\n1. e4 c5 1-0";
+///
+/// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let header = reader.read_game(&mut HeaderVisitor::new())?.expect("game");
+///
+/// assert_eq!(header.len(), 2);
+/// assert_eq!(header.tag(b"Black").expect("tag").decode_utf8_lossy(), "Garry Kasparov");
+/// # Ok::<_, io::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct HeaderVisitor {
+    header: Header,
+}
+
+impl HeaderVisitor {
+    /// Creates a new header visitor.
+    pub fn new() -> HeaderVisitor {
+        HeaderVisitor::default()
+    }
+}
+
+impl Visitor for HeaderVisitor {
+    type Result = Header;
+
+    fn begin_tags(&mut self) {
+        self.header.tags.clear();
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        self.header.push(name, value);
+    }
+
+    fn begin_movetext(&mut self) -> Skip {
+        Skip(true)
+    }
+
+    fn end_game(&mut self) -> Header {
+        mem::take(&mut self.header)
+    }
+}
+
+/// A [`Visitor`] that collects the [`Header`] of each game, and passes only
+/// the games that match a predicate on to another visitor.
+///
+/// The predicate is called after the tags of a game have been read. If it
+/// returns `false`, the movetext is skipped without tokenizing it, and the
+/// inner visitor does not see the game at all. Otherwise the inner visitor
+/// sees the complete game, including the tags, but not `;` comments or `%`
+/// escape lines among the tags.
+///
+/// The result is the header, and the result of the inner visitor if the
+/// game matched.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, Header, HeaderFilter, SanPlus, Visitor};
+///
+/// struct Moves(usize);
+///
+/// impl Visitor for Moves {
+///     type Result = usize;
+///
+///     fn begin_tags(&mut self) {
+///         self.0 = 0;
+///     }
+///
+///     fn san(&mut self, _san_plus: SanPlus) {
+///         self.0 += 1;
+///     }
+///
+///     fn end_game(&mut self) -> usize {
+///         self.0
+///     }
+/// }
+///
+/// let pgn = b"[WhiteElo \"2600\"]\n\n1. e4 e5 *\n\n[WhiteElo \"1500\"]\n\n1. d4 *";
+///
+/// let strong = |header: &Header| {
+///     header
+///         .tag(b"WhiteElo")
+///         .and_then(|elo| elo.elo().ok().flatten())
+///         .is_some_and(|elo| elo > 2500)
+/// };
+///
+/// let reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let mut filter = HeaderFilter::new(strong, Moves(0));
+/// let moves: Vec<_> = reader
+///     .into_iter(&mut filter)
+///     .map(|game| game.map(|(_header, moves)| moves))
+///     .collect::<Result<_, _>>()?;
+///
+/// assert_eq!(moves, [Some(2), None]);
+/// # Ok::<_, pgn_reader::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct HeaderFilter<F, V> {
+    predicate: F,
+    visitor: V,
+    header: Header,
+    matched: bool,
+}
+
+impl<F: FnMut(&Header) -> bool, V: Visitor> HeaderFilter<F, V> {
+    /// Creates a filter that passes games for which `predicate` returns
+    /// `true` on to `visitor`.
+    pub fn new(predicate: F, visitor: V) -> HeaderFilter<F, V> {
+        HeaderFilter {
+            predicate,
+            visitor,
+            header: Header::new(),
+            matched: false,
+        }
+    }
+
+    /// Gets a reference to the inner visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.visitor
+    }
+
+    /// Gets a mutable reference to the inner visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.visitor
+    }
+
+    /// Unwraps the inner visitor.
+    pub fn into_inner(self) -> V {
+        self.visitor
+    }
+}
+
+impl<F: FnMut(&Header) -> bool, V: Visitor> Visitor for HeaderFilter<F, V> {
+    type Result = (Header, Option<V::Result>);
+
+    fn begin_tags(&mut self) {
+        self.header.tags.clear();
+        self.matched = false;
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        self.header.push(name, value);
+    }
+
+    fn begin_movetext(&mut self) -> Skip {
+        self.matched = (self.predicate)(&self.header);
+        if !self.matched {
+            return Skip(true);
+        }
+
+        self.visitor.begin_tags();
+        for (name, value) in self.header.iter() {
+            self.visitor.tag(name, value);
+        }
+        self.visitor.begin_movetext()
+    }
+
+    fn move_number(&mut self, move_number: MoveNumber) {
+        self.visitor.move_number(move_number);
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        self.visitor.san(san_plus);
+    }
+
+    fn invalid_token(&mut self, token: &[u8], kind: InvalidTokenKind) -> TokenAction {
+        self.visitor.invalid_token(token, kind)
+    }
+
+    fn nag(&mut self, nag: Nag) {
+        self.visitor.nag(nag);
+    }
+
+    fn comment(&mut self, comment: RawComment<'_>) {
+        self.visitor.comment(comment);
+    }
+
+    fn begin_long_comment(&mut self) {
+        self.visitor.begin_long_comment();
+    }
+
+    fn partial_comment(&mut self, chunk: RawComment<'_>) {
+        self.visitor.partial_comment(chunk);
+    }
+
+    fn end_long_comment(&mut self) {
+        self.visitor.end_long_comment();
+    }
+
+    fn line_comment(&mut self, comment: RawComment<'_>) {
+        if self.matched {
+            self.visitor.line_comment(comment);
+        }
+    }
+
+    fn escape_line(&mut self, line: &[u8]) {
+        if self.matched {
+            self.visitor.escape_line(line);
+        }
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        self.visitor.begin_variation()
+    }
+
+    fn end_variation(&mut self) {
+        self.visitor.end_variation();
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.visitor.outcome(outcome);
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        let result = if self.matched {
+            Some(self.visitor.end_game())
+        } else {
+            None
+        };
+        (mem::take(&mut self.header), result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::BufferedReader;
+
+    #[test]
+    fn test_duplicates_and_order() {
+        let pgn = b"[White \"A\"]\n[Black \"B\"]\n[White \"C\"]\n\n1. e4 *\n\n[Event \"?\"]\n\n*";
+
+        let reader = BufferedReader::new(io::Cursor::new(pgn));
+        let headers: Vec<Header> = reader
+            .into_iter(&mut HeaderVisitor::new())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(headers.len(), 2);
+        let names: Vec<&[u8]> = headers[0].iter().map(|(name, _)| name).collect();
+        assert_eq!(names, [&b"White"[..], b"Black", b"White"]);
+        assert_eq!(headers[0].tag(b"White"), Some(RawTag(b"A")));
+        let whites: Vec<_> = headers[0].tags_named(b"White").collect();
+        assert_eq!(whites, [RawTag(b"A"), RawTag(b"C")]);
+        assert_eq!(headers[1].len(), 1);
+    }
+
+    #[test]
+    fn test_filter() {
+        #[derive(Default)]
+        struct Events(Vec<String>);
+
+        impl Visitor for Events {
+            type Result = Vec<String>;
+
+            fn tag(&mut self, name: &[u8], _value: RawTag<'_>) {
+                self.0.push(String::from_utf8_lossy(name).into_owned());
+            }
+
+            fn san(&mut self, san_plus: SanPlus) {
+                self.0.push(san_plus.to_string());
+            }
+
+            fn escape_line(&mut self, _line: &[u8]) {
+                self.0.push("%".to_owned());
+            }
+
+            fn end_game(&mut self) -> Self::Result {
+                mem::take(&mut self.0)
+            }
+        }
+
+        let pgn = b"[Round \"1\"]\n\n1. e4 *\n%x\n\n[Round \"2\"]\n\n1. d4 *\n%y\n\n[Round \"3\"]\n\n1. c4 *";
+
+        let mut filter = HeaderFilter::new(
+            |header: &Header| header.tag(b"Round") != Some(RawTag(b"2")),
+            Events::default(),
+        );
+        let reader = BufferedReader::new(io::Cursor::new(pgn));
+        let results: Vec<_> = reader
+            .into_iter(&mut filter)
+            .map(|game| game.unwrap().1)
+            .collect();
+
+        assert_eq!(
+            results,
+            [
+                Some(vec!["Round".to_owned(), "e4".to_owned(), "%".to_owned()]),
+                None,
+                Some(vec!["Round".to_owned(), "c4".to_owned()]),
+            ]
+        );
+        assert!(filter.get_ref().0.is_empty());
+    }
+}
@@ -0,0 +1,505 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use crate::{
+    error::Error,
+    reader::{BufferedReader, ReaderBuilder},
+    types::{RawTag, Skip},
+    visitor::Visitor,
+};
+
+const MAGIC: &[u8; 8] = b"PGNIDX\x01\x00";
+
+/// Maps game numbers, and optionally the values of selected tags, to the
+/// byte ranges of the games in a PGN file.
+///
+/// Games that have syntax errors are included, so that game numbers match
+/// the order of the games in the file (and [`SyntaxError::game()`](crate::SyntaxError::game)).
+///
+/// # Index file format
+///
+/// An index can be stored with [`GameIndex::write_to()`] and loaded with
+/// [`GameIndex::read_from()`]. All integers are unsigned LEB128 varints.
+///
+/// * The magic bytes `PGNIDX\x01\x00`.
+/// * The number of tag names, followed by each tag name as its length
+///   and bytes.
+/// * The number of games, followed by each game as:
+///   * the distance from the end of the previous game (or the start of the
+///     file) to the start of the game,
+///   * the length of the game,
+///   * for each tag name: `0` if the tag is missing, or the length of the
+///     decoded value plus one, followed by its bytes.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use pgn_reader::{BufferedReader, GameIndex};
+///
+/// let pgn = b"[White \"Carlsen\"]\n\n1. e4 *\n\n[White \"Caruana\"]\n\n1. d4 *\n";
+///
+/// let index = GameIndex::build(&mut BufferedReader::new(&pgn[..]), &[b"White"])?;
+/// assert_eq!(index.len(), 2);
+/// assert_eq!(index.tag(1, b"White"), Some(&b"Caruana"[..]));
+/// assert_eq!(index.find(b"White", b"Carlsen").collect::<Vec<_>>(), [0]);
+///
+/// let mut file = Vec::new();
+/// index.write_to(&mut file)?;
+/// assert_eq!(GameIndex::read_from(Cursor::new(file))?, index);
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct GameIndex {
+    tag_names: Vec<Box<[u8]>>,
+    spans: Vec<Range<u64>>,
+    // Tag values of each game, in the order of tag_names.
+    values: Vec<Option<Box<[u8]>>>,
+}
+
+struct TagCollector<'a> {
+    tag_names: &'a [Box<[u8]>],
+    values: Vec<Option<Box<[u8]>>>,
+}
+
+impl Visitor for TagCollector<'_> {
+    type Result = ();
+
+    fn begin_tags(&mut self) {
+        self.values.clear();
+        self.values.resize(self.tag_names.len(), None);
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        if let Some(i) = self.tag_names.iter().position(|n| **n == *name) {
+            self.values[i] = Some(value.decode().into());
+        }
+    }
+
+    fn begin_movetext(&mut self) -> Skip {
+        Skip(true)
+    }
+
+    fn end_game(&mut self) {}
+}
+
+impl GameIndex {
+    /// Reads all remaining games to build an index. The values of the tags
+    /// in `tag_names` are included in the index.
+    ///
+    /// # Errors
+    ///
+    /// I/O error from the underlying reader. Syntax errors are not fatal.
+    pub fn build<R: Read>(
+        reader: &mut BufferedReader<R>,
+        tag_names: &[&[u8]],
+    ) -> io::Result<GameIndex> {
+        let mut index = GameIndex {
+            tag_names: tag_names.iter().map(|&name| name.into()).collect(),
+            ..GameIndex::default()
+        };
+
+        let mut collector = TagCollector {
+            tag_names: &index.tag_names,
+            values: Vec::new(),
+        };
+
+        loop {
+            match reader.read_game(&mut collector) {
+                Ok(Some(())) | Err(Error::Syntax(_)) => {}
+                Ok(None) => break,
+                Err(Error::Io(err)) => return Err(err),
+            }
+            index
+                .spans
+                .push(reader.last_game_span().expect("span of game"));
+            index.values.append(&mut collector.values);
+        }
+
+        Ok(index)
+    }
+
+    /// The number of indexed games.
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Tests if there are no indexed games.
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// The names of the tags whose values are included in the index.
+    pub fn tag_names(&self) -> impl Iterator<Item = &[u8]> {
+        self.tag_names.iter().map(|name| &name[..])
+    }
+
+    /// The byte range of a game, counted from the start of the input that
+    /// was indexed.
+    pub fn span(&self, game: usize) -> Option<Range<u64>> {
+        self.spans.get(game).cloned()
+    }
+
+    /// The decoded value of a tag of a game. `None` if the game does not
+    /// exist, the tag is missing, or the tag is not included in the index.
+    pub fn tag(&self, game: usize, name: &[u8]) -> Option<&[u8]> {
+        let i = self.tag_names.iter().position(|n| **n == *name)?;
+        self.game_values(game)?[i].as_deref()
+    }
+
+    /// The numbers of all games where the tag `name` has the decoded value
+    /// `value`.
+    ///
+    /// The values are not indexed, so this scans the values of all games.
+    /// Build a map from [`GameIndex::tag()`] to look up many values.
+    pub fn find<'a>(&'a self, name: &[u8], value: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let i = self.tag_names.iter().position(|n| **n == *name);
+        (0..self.len()).filter(move |&game| {
+            i.is_some_and(|i| self.game_values(game).expect("game")[i].as_deref() == Some(value))
+        })
+    }
+
+    fn game_values(&self, game: usize) -> Option<&[Option<Box<[u8]>>]> {
+        let n = self.tag_names.len();
+        self.values.get(game * n..(game + 1) * n)
+    }
+
+    /// Writes the index in the [index file format](GameIndex#index-file-format).
+    ///
+    /// # Errors
+    ///
+    /// I/O error from the underlying writer.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+
+        write_varint(&mut writer, self.tag_names.len() as u64)?;
+        for name in &self.tag_names {
+            write_bytes(&mut writer, name)?;
+        }
+
+        write_varint(&mut writer, self.spans.len() as u64)?;
+        let mut end = 0;
+        for (game, span) in self.spans.iter().enumerate() {
+            write_varint(&mut writer, span.start - end)?;
+            write_varint(&mut writer, span.end - span.start)?;
+            end = span.end;
+
+            for value in self.game_values(game).expect("game") {
+                match value {
+                    Some(value) => {
+                        write_varint(&mut writer, value.len() as u64 + 1)?;
+                        writer.write_all(value)?;
+                    }
+                    None => write_varint(&mut writer, 0)?,
+                }
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Reads an index in the [index file format](GameIndex#index-file-format).
+    ///
+    /// # Errors
+    ///
+    /// I/O error from the underlying reader, or [`io::ErrorKind::InvalidData`]
+    /// if the data is not a valid index.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<GameIndex> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(invalid_data("not a pgn index"));
+        }
+
+        let mut index = GameIndex::default();
+
+        for _ in 0..read_varint(&mut reader)? {
+            let len = read_varint(&mut reader)?;
+            index.tag_names.push(read_bytes(&mut reader, len)?);
+        }
+
+        let mut end = 0u64;
+        for _ in 0..read_varint(&mut reader)? {
+            let start = end
+                .checked_add(read_varint(&mut reader)?)
+                .ok_or_else(|| invalid_data("game offset overflow"))?;
+            end = start
+                .checked_add(read_varint(&mut reader)?)
+                .ok_or_else(|| invalid_data("game offset overflow"))?;
+            index.spans.push(start..end);
+
+            for _ in 0..index.tag_names.len() {
+                let value = match read_varint(&mut reader)? {
+                    0 => None,
+                    len => Some(read_bytes(&mut reader, len - 1)?),
+                };
+                index.values.push(value);
+            }
+        }
+
+        Ok(index)
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut n: u64) -> io::Result<()> {
+    let mut buf = [0; 10];
+    let mut len = 0;
+    loop {
+        buf[len] = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            len += 1;
+            break;
+        }
+        buf[len] |= 0x80;
+        len += 1;
+    }
+    writer.write_all(&buf[..len])
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_varint(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        n |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(invalid_data("varint too long"))
+}
+
+fn read_bytes<R: Read>(reader: &mut R, len: u64) -> io::Result<Box<[u8]>> {
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes.into())
+}
+
+/// Reads individual games from a seekable file, using a [`GameIndex`] to
+/// seek directly to the start of each game.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use pgn_reader::{BufferedReader, GameIndex, IndexedReader, SanPlus, Visitor};
+///
+/// struct LastMove(Option<SanPlus>);
+///
+/// impl Visitor for LastMove {
+///     type Result = Option<SanPlus>;
+///
+///     fn san(&mut self, san_plus: SanPlus) {
+///         self.0 = Some(san_plus);
+///     }
+///
+///     fn end_game(&mut self) -> Self::Result {
+///         self.0.take()
+///     }
+/// }
+///
+/// let pgn = b"1. e4 e5 *\n\n1. d4 d5 2. c4 *\n\n1. Nf3 *\n";
+/// let index = GameIndex::build(&mut BufferedReader::new(&pgn[..]), &[])?;
+///
+/// let mut reader = IndexedReader::new(Cursor::new(&pgn[..]), index);
+/// let last_move = reader.read_game(1, &mut LastMove(None))?;
+/// assert_eq!(last_move, Some(Some("c4".parse()?)));
+///
+/// assert_eq!(reader.read_game(3, &mut LastMove(None))?, None);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct IndexedReader<R> {
+    inner: R,
+    index: GameIndex,
+    builder: ReaderBuilder,
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    /// Creates a reader for `inner`, which must be the input that was
+    /// indexed.
+    pub fn new(inner: R, index: GameIndex) -> IndexedReader<R> {
+        IndexedReader {
+            inner,
+            index,
+            builder: ReaderBuilder::new(),
+        }
+    }
+
+    /// Sets the configuration of the [`BufferedReader`] used for each game.
+    pub fn reader_builder(&mut self, builder: ReaderBuilder) -> &mut IndexedReader<R> {
+        self.builder = builder;
+        self
+    }
+
+    /// The index.
+    pub fn index(&self) -> &GameIndex {
+        &self.index
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying reader and the index.
+    pub fn into_inner(self) -> (R, GameIndex) {
+        (self.inner, self.index)
+    }
+
+    /// Seeks to the game with the given number and reads only that game.
+    /// Returns `None` if there is no such game in the index.
+    ///
+    /// Syntax errors report absolute byte offsets and the number of the game
+    /// in the index. The index does not record line numbers, so
+    /// [`SyntaxError::line()`](crate::SyntaxError::line) is relative to the
+    /// start of the game: line 1 is the first line of the game.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub fn read_game<V: Visitor>(
+        &mut self,
+        game: usize,
+        visitor: &mut V,
+    ) -> Result<Option<V::Result>, Error> {
+        let Some(span) = self.index.span(game) else {
+            return Ok(None);
+        };
+
+        self.inner.seek(SeekFrom::Start(span.start))?;
+        let mut reader = self
+            .builder
+            .build((&mut self.inner).take(span.end - span.start));
+        match reader.read_game(visitor) {
+            Ok(result) => Ok(result),
+            Err(Error::Syntax(err)) => {
+                Err(Error::Syntax(err.rebase(span.start).with_game(game as u64)))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{error::SyntaxErrorKind, game::GameBuilder};
+
+    fn pgn() -> Vec<u8> {
+        let mut pgn = Vec::new();
+        pgn.extend_from_slice(
+            b"\xef\xbb\xbf[Event \"A\"]\r\n[White \"Carlsen\"]\r\n\r\n1. e4 e5 *\r\n\r\n",
+        );
+        pgn.extend_from_slice(b"[Event \"B \\\"quoted\\\"\"]\n\n1. d4 {");
+        pgn.extend_from_slice(&[b'x'; 5000]);
+        pgn.extend_from_slice(b"} d5 *\n\n");
+        pgn.extend_from_slice(b"[Event \"C\"]\n[White \"Carlsen\"]\n\n1. c4 (1. Nf3) 1-0\n\n");
+        pgn.extend_from_slice(b"1. f4 *");
+        pgn
+    }
+
+    #[test]
+    fn test_build() -> io::Result<()> {
+        let pgn = pgn();
+        let index = GameIndex::build(&mut BufferedReader::new(&pgn[..]), &[b"White", b"Event"])?;
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.tag_names().collect::<Vec<_>>(), [b"White", b"Event"]);
+
+        assert_eq!(index.tag(0, b"Event"), Some(&b"A"[..]));
+        assert_eq!(index.tag(1, b"Event"), Some(&b"B \"quoted\""[..]));
+        assert_eq!(index.tag(1, b"White"), None);
+        assert_eq!(index.tag(3, b"Event"), None);
+        assert_eq!(index.tag(0, b"Black"), None);
+        assert_eq!(index.tag(4, b"Event"), None);
+        assert_eq!(index.find(b"White", b"Carlsen").collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(index.find(b"Black", b"Carlsen").count(), 0);
+
+        for game in 0..index.len() {
+            let span = index.span(game).unwrap();
+            let text = &pgn[span.start as usize..span.end as usize];
+            assert!(text.starts_with(b"[Event") || text.starts_with(b"1. f4"));
+        }
+        assert_eq!(index.span(3).unwrap().end, pgn.len() as u64);
+
+        let mut file = Vec::new();
+        index.write_to(&mut file)?;
+        assert_eq!(GameIndex::read_from(&file[..])?, index);
+
+        assert_eq!(
+            GameIndex::read_from(&file[..file.len() - 1])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            GameIndex::read_from(&b"PGNIDX\x02\x00"[..])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_indexed_reader() -> Result<(), Error> {
+        let pgn = pgn();
+        let index = GameIndex::build(&mut BufferedReader::new(&pgn[..]), &[])?;
+        let mut reader = IndexedReader::new(Cursor::new(&pgn), index);
+        let mut builder = GameBuilder::new();
+
+        for game in [3, 0, 2] {
+            let mut sequential = BufferedReader::new(&pgn[..]);
+            for _ in 0..game {
+                let _ = sequential.skip_game();
+            }
+            let expected = sequential.read_game(&mut builder)?;
+            assert_eq!(reader.read_game(game, &mut builder)?, expected);
+        }
+
+        let mut sequential = BufferedReader::new(&pgn[..]);
+        let _ = sequential.skip_game();
+        let expected = match sequential.read_game(&mut builder) {
+            Err(Error::Syntax(err)) => err,
+            _ => panic!("expected syntax error"),
+        };
+        match reader.read_game(1, &mut builder) {
+            Err(Error::Syntax(err)) => {
+                assert_eq!(err.kind(), SyntaxErrorKind::CommentTooLong);
+                assert_eq!(err.offset(), expected.offset());
+                assert_eq!(err.line(), 3);
+                assert_eq!(err.game(), 1);
+            }
+            _ => panic!("expected syntax error"),
+        }
+
+        assert!(reader.read_game(4, &mut builder)?.is_none());
+        Ok(())
+    }
+}
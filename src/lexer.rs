@@ -0,0 +1,342 @@
+//! Tokenizer shared by the readers.
+//!
+//! The functions in this module look at a window of the input and never
+//! refill it. Readers are responsible for providing enough lookahead, and for
+//! skipping over unbounded runs like `;` comments or `%` escapes.
+
+use std::cmp::min;
+
+use shakmaty::{
+    san::{San, SanPlus, Suffix},
+    CastlingSide, Color, Outcome,
+};
+
+use crate::types::{InvalidTokenKind, MoveNumber, Nag};
+
+/// A token in the tag section of a game.
+pub(crate) enum TagToken<'a> {
+    /// A tag pair like `[White "Deep Blue"]`. Trailing `]` and whitespace
+    /// should be skipped up to the end of the line.
+    Tag { name: &'a [u8], value: &'a [u8] },
+    /// A `[` line without a tag value.
+    Malformed,
+    /// A `[` that is not closed within the window. Only the `[` is
+    /// consumed.
+    Unclosed,
+    /// A `%` escape line.
+    Escape,
+    /// Anything else ends the tag section. Nothing is consumed.
+    End,
+}
+
+/// Reads the next token in the tag section. `data` must not be empty.
+///
+/// Returns the token and the number of bytes consumed.
+#[inline]
+pub(crate) fn tag_token(data: &[u8], max_tag_line_length: usize) -> (TagToken<'_>, usize) {
+    match data[0] {
+        b'[' => {
+            let data = &data[1..];
+            let window = min(data.len(), max_tag_line_length.saturating_sub(1));
+
+            let left_quote = match memchr::memchr3(b'"', b'\n', b']', &data[..window]) {
+                Some(left_quote) if data[left_quote] == b'"' => left_quote,
+                Some(eol) => return (TagToken::Malformed, 1 + eol + 1),
+                None => return (TagToken::Unclosed, 1),
+            };
+
+            let space = if left_quote > 0 && data[left_quote - 1] == b' ' {
+                left_quote - 1
+            } else {
+                left_quote
+            };
+
+            let value_start = left_quote + 1;
+            let mut right_quote = value_start;
+            let consumed = loop {
+                match memchr::memchr3(b'\\', b'"', b'\n', &data[right_quote..window]) {
+                    Some(delta) if data[right_quote + delta] == b'"' => {
+                        right_quote += delta;
+                        break right_quote + 1;
+                    }
+                    Some(delta) if data[right_quote + delta] == b'\n' => {
+                        right_quote += delta;
+                        break right_quote;
+                    }
+                    Some(delta) => {
+                        // Skip escaped character.
+                        right_quote = min(right_quote + delta + 2, window);
+                    }
+                    None => return (TagToken::Unclosed, 1),
+                }
+            };
+
+            (
+                TagToken::Tag {
+                    name: &data[..space],
+                    value: &data[value_start..right_quote],
+                },
+                1 + consumed,
+            )
+        }
+        b'%' => (TagToken::Escape, 0),
+        _ => (TagToken::End, 0),
+    }
+}
+
+/// A token in the movetext of a game.
+pub(crate) enum Token<'a> {
+    San(SanPlus),
+    Nag(Nag),
+    /// A `{ comment }`, excluding the braces.
+    Comment(&'a [u8]),
+    /// A `{` that is not closed within the window (or within the maximum
+    /// comment length). Only the `{` is consumed.
+    UnclosedComment,
+    BeginVariation,
+    EndVariation,
+    Outcome(Option<Outcome>),
+    /// A `;` comment. The rest of the line should be skipped, leaving the
+    /// line break.
+    LineComment,
+    /// A `%` escape at the start of a line. The rest of the line should be
+    /// skipped, including the line break.
+    Escape,
+    /// A blank line or a new tag section ends the movetext.
+    End,
+    /// A move number like `12.` or `12...`.
+    MoveNumber(MoveNumber),
+    /// A token that is not recognized.
+    Invalid(InvalidTokenKind),
+    /// Whitespace, and move numbers that are out of range.
+    Ignored,
+}
+
+fn is_token_end(ch: u8) -> bool {
+    matches!(
+        ch,
+        b' ' | b'\t' | b'\n' | b'\r' | b'{' | b'}' | b'(' | b')' | b'!' | b'?' | b'$' | b';' | b'.'
+    )
+}
+
+fn find_token_end(data: &[u8], start: usize) -> usize {
+    data[start..]
+        .iter()
+        .position(|&ch| is_token_end(ch))
+        .map_or(data.len(), |end| start + end)
+}
+
+fn skip_digits(data: &[u8], mut pos: usize) -> usize {
+    while data.get(pos).is_some_and(|ch| ch.is_ascii_digit()) {
+        pos += 1;
+    }
+    pos
+}
+
+pub(crate) fn skip_move_number(data: &[u8], start: usize) -> usize {
+    let mut pos = skip_digits(data, start);
+    while data.get(pos) == Some(&b'.') {
+        pos += 1;
+    }
+    pos
+}
+
+fn move_number(data: &[u8]) -> (Token<'_>, usize) {
+    // Parse while skipping the digits, instead of a second pass with btoi.
+    // This is on the hot path of every reader.
+    let mut number = Some(0u32);
+    let mut digits = 0;
+    while let Some(&ch @ b'0'..=b'9') = data.get(digits) {
+        number = number.and_then(|n| n.checked_mul(10)?.checked_add(u32::from(ch - b'0')));
+        digits += 1;
+    }
+    let end = skip_move_number(data, digits);
+    match number {
+        Some(number) => (
+            Token::MoveNumber(MoveNumber {
+                number,
+                dots: end - digits,
+            }),
+            end,
+        ),
+        None => (Token::Ignored, end),
+    }
+}
+
+/// Reads the next token in the movetext. `data` must not be empty.
+///
+/// Returns the token and the number of bytes consumed.
+#[inline]
+pub(crate) fn token(data: &[u8], max_comment_length: usize) -> (Token<'_>, usize) {
+    match data[0] {
+        b'{' => {
+            let window = min(data.len() - 1, max_comment_length.saturating_add(1));
+            match memchr::memchr(b'}', &data[1..1 + window]) {
+                Some(right_brace) => (Token::Comment(&data[1..1 + right_brace]), right_brace + 2),
+                None => (Token::UnclosedComment, 1),
+            }
+        }
+        b'\n' => match data.get(1) {
+            Some(b'%') => (Token::Escape, 2),
+            Some(b'[') | Some(b'\n') => (Token::End, 1),
+            Some(b'\r') if data.get(2) == Some(&b'\n') => (Token::End, 2),
+            Some(b'\r') => (Token::Ignored, 2),
+            _ => (Token::Ignored, 1),
+        },
+        b';' => (Token::LineComment, 1),
+        b'0' => {
+            let rest = &data[1..];
+            if rest.starts_with(b"-1") {
+                (
+                    Token::Outcome(Some(Outcome::Decisive {
+                        winner: Color::Black,
+                    })),
+                    3,
+                )
+            } else if rest.starts_with(b"-0") {
+                // Castling notation with zeros.
+                let (side, consumed) = if rest[2..].starts_with(b"-0") {
+                    (CastlingSide::QueenSide, 5)
+                } else {
+                    (CastlingSide::KingSide, 3)
+                };
+                let suffix = match data.get(consumed) {
+                    Some(b'+') => Some(Suffix::Check),
+                    Some(b'#') => Some(Suffix::Checkmate),
+                    _ => None,
+                };
+                (
+                    Token::San(SanPlus {
+                        san: San::Castle(side),
+                        suffix,
+                    }),
+                    consumed,
+                )
+            } else {
+                (
+                    Token::Invalid(InvalidTokenKind::Unknown),
+                    1 + find_token_end(rest, 0),
+                )
+            }
+        }
+        b'1' => {
+            let rest = &data[1..];
+            if rest.starts_with(b"-0") {
+                (
+                    Token::Outcome(Some(Outcome::Decisive {
+                        winner: Color::White,
+                    })),
+                    3,
+                )
+            } else if rest.starts_with(b"/2-1/2") {
+                (Token::Outcome(Some(Outcome::Draw)), 7)
+            } else {
+                move_number(data)
+            }
+        }
+        b'2'..=b'9' => move_number(data),
+        b'(' => (Token::BeginVariation, 1),
+        b')' => (Token::EndVariation, 1),
+        b'$' => {
+            let token_end = find_token_end(data, 1);
+            match btoi::btou(&data[1..token_end]) {
+                Ok(nag) => (Token::Nag(Nag(nag)), token_end),
+                Err(_) => (Token::Invalid(InvalidTokenKind::Nag), token_end),
+            }
+        }
+        b'!' => match data.get(1) {
+            Some(b'!') => (Token::Nag(Nag::BRILLIANT_MOVE), 2),
+            Some(b'?') => (Token::Nag(Nag::SPECULATIVE_MOVE), 2),
+            _ => (Token::Nag(Nag::GOOD_MOVE), 1),
+        },
+        b'?' => match data.get(1) {
+            Some(b'!') => (Token::Nag(Nag::DUBIOUS_MOVE), 2),
+            Some(b'?') => (Token::Nag(Nag::BLUNDER), 2),
+            _ => (Token::Nag(Nag::MISTAKE), 1),
+        },
+        b'*' => (Token::Outcome(None), 1),
+        b'a' | b'b' | b'c' | b'd' | b'e' | b'f' | b'g' | b'h' | b'N' | b'B' | b'R' | b'Q'
+        | b'K' | b'@' | b'-' | b'O' => {
+            let token_end = find_token_end(data, 1);
+            match SanPlus::from_ascii(&data[..token_end]) {
+                Ok(san) => (Token::San(san), token_end),
+                Err(_) => (Token::Invalid(InvalidTokenKind::San), token_end),
+            }
+        }
+        // A leading `P`, like in `P@e4`, is ignored.
+        b' ' | b'\t' | b'\r' | b'.' | b'P' => (Token::Ignored, 1),
+        _ => {
+            // Do not split multi-byte characters.
+            let len = if data[0] < 0x80 {
+                1
+            } else {
+                data[1..]
+                    .iter()
+                    .position(|&ch| ch < 0x80)
+                    .map_or(data.len(), |end| 1 + end)
+            };
+            (Token::Invalid(InvalidTokenKind::Unknown), len)
+        }
+    }
+}
+
+/// A step when skipping over movetext or a variation.
+pub(crate) enum SkipToken {
+    /// A `{` comment starts. The rest up to and including `}` should be
+    /// skipped.
+    Comment,
+    /// A `;` comment or `%` escape. The rest of the line should be skipped,
+    /// leaving the line break.
+    LineComment,
+    BeginVariation,
+    EndVariation,
+    /// A blank line or new tag section. Nothing is consumed.
+    End,
+    Ignored,
+}
+
+/// Reads the next token when skipping. `data` must not be empty. Unlike
+/// [`token()`], line breaks that end the movetext are not consumed.
+///
+/// Returns the token and the number of bytes consumed.
+#[inline]
+pub(crate) fn skip_token(data: &[u8]) -> (SkipToken, usize) {
+    match data[0] {
+        b'{' => (SkipToken::Comment, 1),
+        b';' => (SkipToken::LineComment, 1),
+        b'(' => (SkipToken::BeginVariation, 1),
+        b')' => (SkipToken::EndVariation, 1),
+        b'\n' => match data.get(1) {
+            Some(b'%') => (SkipToken::LineComment, 2),
+            Some(b'[') | Some(b'\n') => (SkipToken::End, 0),
+            Some(b'\r') if data.get(2) == Some(&b'\n') => (SkipToken::End, 0),
+            _ => (SkipToken::Ignored, 1),
+        },
+        _ => {
+            let end = memchr::memchr3(b'\n', b'{', b';', data).unwrap_or(data.len());
+            let end = memchr::memchr2(b'(', b')', &data[..end]).unwrap_or(end);
+            (SkipToken::Ignored, end)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_number() {
+        let number = |data| match token(data, 0) {
+            (Token::MoveNumber(MoveNumber { number, dots }), consumed) => {
+                Some((number, dots, consumed))
+            }
+            (Token::Ignored, _) => None,
+            _ => panic!("expected move number"),
+        };
+        assert_eq!(number(b"12... e5"), Some((12, 3, 5)));
+        assert_eq!(number(b"1"), Some((1, 0, 1)));
+        assert_eq!(number(b"4294967295."), Some((u32::MAX, 1, 11)));
+        assert_eq!(number(b"4294967296."), None);
+        assert_eq!(token(b"99999999999999999999 e4", 0).1, 20);
+    }
+}
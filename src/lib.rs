@@ -10,6 +10,13 @@
 //! * The visitor can signal to the reader that it does not care about a game
 //!   or variation.
 //!
+//! # Features
+//!
+//! * `async`: `AsyncBufferedReader`, an asynchronous reader for
+//!   `futures::io::AsyncRead` that can be converted into a `Stream`.
+//! * `tokio`: Support for `tokio::io::AsyncRead` in `AsyncBufferedReader`.
+//! * `regex`: Regular expression matches in [`TagQuery`].
+//!
 //! # Flow
 //!
 //! Visitor methods are called in this order:
@@ -140,15 +147,61 @@
 #![warn(missing_debug_implementations)]
 #![warn(missing_copy_implementations)]
 
+#[cfg(feature = "async")]
+mod async_reader;
 mod buffer;
+mod comment;
+mod error;
+mod event;
+mod game;
+mod header;
+mod index;
+mod lexer;
+mod machine;
+mod parallel;
+mod pattern;
+mod position;
+mod push;
+mod query;
 mod reader;
+mod search;
+mod slice;
+mod tags;
+mod tokenizer;
+mod try_visitor;
 mod types;
+mod validate;
+mod variant;
 mod visitor;
+mod writer;
 
-pub use reader::{BufferedReader, IntoIter};
+#[cfg(feature = "async")]
+pub use async_reader::AsyncBufferedReader;
+#[cfg(feature = "tokio")]
+pub use async_reader::TokioReader;
+pub use comment::{CommentPart, CommentParts, Score, Shape, ShapeColor};
+pub use error::{Error, SyntaxError, SyntaxErrorKind};
+pub use event::{Event, EventReader};
+pub use game::{ChildNode, Game, GameBuilder, Mainline, Node};
+pub use header::{Header, HeaderFilter, HeaderVisitor};
+pub use index::{GameIndex, IndexedReader};
+pub use parallel::ParallelReader;
+pub use pattern::{Pattern, PatternHit, PatternSearch};
+pub use position::{PositionAdapter, PositionVisitor, SetupError};
+pub use push::PushParser;
+pub use query::{InvalidQuery, TagQuery};
+pub use reader::{BufferedReader, IntoIter, ReaderBuilder};
+pub use search::{PositionHit, PositionSearch};
 pub use shakmaty::{
     san::{San, SanPlus},
     CastlingSide, Color, File, Outcome, Rank, Role, Square,
 };
-pub use types::{Nag, RawComment, RawTag, Skip};
-pub use visitor::Visitor;
+pub use slice::SliceReader;
+pub use tags::{Date, Eco, InvalidTag, Round, Termination, Time, TimeControl, TimePeriod};
+pub use tokenizer::{SyntaxToken, TokenKind, Tokenizer};
+pub use try_visitor::{Break, TryError, TryIntoIter, TryVisitor};
+pub use types::{InvalidTokenKind, MoveNumber, Nag, RawComment, RawTag, Skip, TokenAction};
+pub use validate::{IllegalMove, InvalidToken, ValidationError, Validator, WrongMoveNumber};
+pub use variant::VariantTag;
+pub use visitor::{SliceVisitor, Visitor};
+pub use writer::Writer;
@@ -0,0 +1,671 @@
+//! Resumable state machine that turns buffered input into events.
+//!
+//! Unlike the [`BufferedReader`](crate::BufferedReader), the machine never
+//! reads from the underlying input itself. When it needs more data, it
+//! returns [`Status::NeedMore`], and the caller can fill the buffer and try
+//! again. It parses exactly like the reader, given the same settings.
+//!
+//! # Why there are three parsers
+//!
+//! The tag and movetext loops exist three times: here, in
+//! [`BufferedReader`](crate::BufferedReader) and in
+//! [`SliceReader`](crate::SliceReader). The readers call the visitor directly
+//! from tight loops that the compiler monomorphizes per visitor, and refill
+//! (or, for slices, never need to refill) in the middle of a token. The
+//! machine instead has to suspend at any byte and hand out one event at a
+//! time, so each step goes through [`State`] and [`RawEvent`]. Driving the
+//! [`BufferedReader`](crate::BufferedReader) through the machine would put
+//! that bookkeeping on its hot path, so the loops are kept separate.
+//!
+//! All three must agree on the following, so a change to one of them
+//! usually needs the same change in the others:
+//!
+//! * Tokens are recognized only by [`lexer`], including where a game ends:
+//!   at a `[` or a blank line (`\n\n` or `\n\r\n`) in the movetext.
+//! * A UTF-8 byte order mark is skipped before each game.
+//! * `%` escape lines are recognized in the tag section, in the whitespace
+//!   before the movetext, and at the start of a line in the movetext.
+//! * After the closing `]` of a tag, the rest of the line up to the line
+//!   break is skipped (`skip_ket`).
+//! * Syntax errors have the same [`SyntaxErrorKind`], position and game
+//!   number, and the rest of the game is skipped to recover.
+//! * Comments longer than the limit are rejected or streamed according to
+//!   the [`ReaderBuilder`] settings. The `SliceReader` has no limits.
+//! * [`Skip`] from the visitor skips the movetext or a variation the same
+//!   way, and `end_game` is still called.
+//!
+//! The `test_same_as_buffered_reader` tests in the event, push, async and
+//! slice modules compare the events of each parser against the
+//! [`BufferedReader`](crate::BufferedReader) on the fuzz corpus.
+
+use std::{
+    cmp::{max, min},
+    mem,
+    ops::Range,
+};
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    buffer::Buffer,
+    error::{SyntaxError, SyntaxErrorKind},
+    event::Event,
+    lexer::{self, SkipToken, TagToken, Token},
+    reader::ReaderBuilder,
+    types::{InvalidTokenKind, MoveNumber, Nag, RawComment, RawTag, Skip, TokenAction},
+    visitor::Visitor,
+};
+
+/// The result of [`Machine::advance()`].
+pub(crate) enum Status {
+    /// An event is available with [`Machine::event()`].
+    Event,
+    /// A game ended with a syntax error.
+    Error(SyntaxError),
+    /// More input is needed (and the end of the input has not been
+    /// reached).
+    NeedMore,
+    /// There are no more games.
+    Done,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum State {
+    Bom,
+    LeadingWhitespace,
+    Tags,
+    Ket,
+    SkipTagLines,
+    Movetext,
+    LongComment(SyntaxError),
+    EndLongComment(Option<SyntaxError>),
+    SkipVariation(usize),
+    SkipMovetext,
+    TrailingWhitespace,
+}
+
+/// An event, with payloads as ranges of the buffered data.
+#[derive(Debug, Clone)]
+enum RawEvent {
+    BeginTags,
+    Tag {
+        name: Range<usize>,
+        value: Range<usize>,
+    },
+    BeginMovetext,
+    MoveNumber(MoveNumber),
+    San(SanPlus),
+    InvalidToken {
+        token: Range<usize>,
+        kind: InvalidTokenKind,
+    },
+    Nag(Nag),
+    Comment(Range<usize>),
+    LineComment(Range<usize>),
+    EscapeLine(Range<usize>),
+    BeginLongComment,
+    PartialComment(Range<usize>),
+    EndLongComment,
+    BeginVariation,
+    EndVariation,
+    Outcome(Option<Outcome>),
+    EndGame,
+}
+
+enum Step {
+    Continue,
+    Status(Status),
+}
+
+/// Offset of `part` in `data`.
+fn range_in(data: &[u8], part: &[u8]) -> Range<usize> {
+    let start = part.as_ptr() as usize - data.as_ptr() as usize;
+    start..start + part.len()
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Machine {
+    buffer: Buffer,
+    max_tag_line_length: usize,
+    max_comment_length: usize,
+    stream_long_comments: bool,
+    games: u64,
+    game_start: u64,
+    last_game: Option<Range<u64>>,
+    state: State,
+    // Skip until the given byte, and then optionally over it.
+    skip_until: Option<(u8, bool)>,
+    // Reported after skipping the rest of the game.
+    error: Option<SyntaxError>,
+    // The current event, and the number of bytes to consume after it.
+    event: Option<RawEvent>,
+    pending: usize,
+}
+
+impl Machine {
+    pub fn new(builder: &ReaderBuilder) -> Machine {
+        Machine {
+            buffer: builder.new_buffer(),
+            max_tag_line_length: builder.effective_max_tag_line_length(),
+            max_comment_length: builder.max_comment_length,
+            stream_long_comments: builder.stream_long_comments,
+            games: 0,
+            game_start: 0,
+            last_game: None,
+            state: State::Bom,
+            skip_until: None,
+            error: None,
+            event: None,
+            pending: 0,
+        }
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffer
+    }
+
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn into_buffer(mut self) -> Buffer {
+        self.buffer.consume(self.pending);
+        self.buffer
+    }
+
+    pub fn position(&self) -> u64 {
+        self.buffer.position()
+    }
+
+    pub fn last_game_span(&self) -> Option<Range<u64>> {
+        self.last_game.clone()
+    }
+
+    /// The current event. Must only be called after [`Machine::advance()`]
+    /// returned [`Status::Event`].
+    pub fn event(&self) -> Event<'_> {
+        let data = self.buffer.data();
+        match self.event.clone().expect("current event") {
+            RawEvent::BeginTags => Event::BeginTags,
+            RawEvent::Tag { name, value } => Event::Tag {
+                name: &data[name],
+                value: RawTag(&data[value]),
+            },
+            RawEvent::BeginMovetext => Event::BeginMovetext,
+            RawEvent::MoveNumber(move_number) => Event::MoveNumber(move_number),
+            RawEvent::San(san_plus) => Event::San(san_plus),
+            RawEvent::InvalidToken { token, kind } => Event::InvalidToken {
+                token: &data[token],
+                kind,
+            },
+            RawEvent::Nag(nag) => Event::Nag(nag),
+            RawEvent::Comment(comment) => Event::Comment(RawComment(&data[comment])),
+            RawEvent::LineComment(comment) => Event::LineComment(RawComment(&data[comment])),
+            RawEvent::EscapeLine(line) => Event::EscapeLine(&data[line]),
+            RawEvent::BeginLongComment => Event::BeginLongComment,
+            RawEvent::PartialComment(chunk) => Event::PartialComment(RawComment(&data[chunk])),
+            RawEvent::EndLongComment => Event::EndLongComment,
+            RawEvent::BeginVariation => Event::BeginVariation,
+            RawEvent::EndVariation => Event::EndVariation,
+            RawEvent::Outcome(outcome) => Event::Outcome(outcome),
+            RawEvent::EndGame => Event::EndGame,
+        }
+    }
+
+    /// Skips the rest of the movetext of the current game, for example after
+    /// [`Event::BeginMovetext`].
+    pub fn skip_movetext(&mut self) {
+        if let State::Movetext = self.state {
+            self.state = State::SkipMovetext;
+        }
+    }
+
+    /// Skips the rest of the current variation, for example after
+    /// [`Event::BeginVariation`].
+    pub fn skip_variation(&mut self) {
+        if let State::Movetext = self.state {
+            self.state = State::SkipVariation(0);
+        }
+    }
+
+    /// Aborts the current game with [`SyntaxErrorKind::InvalidToken`], like
+    /// [`TokenAction::Abort`], if the current event is
+    /// [`Event::InvalidToken`].
+    pub fn abort(&mut self) {
+        if let Some(RawEvent::InvalidToken { .. }) = self.event {
+            self.error = Some(self.syntax_error(SyntaxErrorKind::InvalidToken, 0));
+            self.state = State::SkipMovetext;
+        }
+    }
+
+    /// Passes the current event to a visitor, and follows its decisions to
+    /// skip. Returns the result of the visitor at the end of a game.
+    pub fn visit<V: Visitor>(&mut self, visitor: &mut V) -> Option<V::Result> {
+        match self.event() {
+            Event::BeginTags => visitor.begin_tags(),
+            Event::Tag { name, value } => visitor.tag(name, value),
+            Event::BeginMovetext => {
+                if let Skip(true) = visitor.begin_movetext() {
+                    self.skip_movetext();
+                }
+            }
+            Event::MoveNumber(move_number) => visitor.move_number(move_number),
+            Event::San(san_plus) => visitor.san(san_plus),
+            Event::InvalidToken { token, kind } => match visitor.invalid_token(token, kind) {
+                TokenAction::Skip => (),
+                TokenAction::Abort => self.abort(),
+                TokenAction::Replace(san_plus) => visitor.san(san_plus),
+            },
+            Event::Nag(nag) => visitor.nag(nag),
+            Event::Comment(comment) => visitor.comment(comment),
+            Event::LineComment(comment) => visitor.line_comment(comment),
+            Event::EscapeLine(line) => visitor.escape_line(line),
+            Event::BeginLongComment => visitor.begin_long_comment(),
+            Event::PartialComment(chunk) => visitor.partial_comment(chunk),
+            Event::EndLongComment => visitor.end_long_comment(),
+            Event::BeginVariation => {
+                if let Skip(true) = visitor.begin_variation() {
+                    self.skip_variation();
+                }
+            }
+            Event::EndVariation => visitor.end_variation(),
+            Event::Outcome(outcome) => visitor.outcome(outcome),
+            Event::EndGame => return Some(visitor.end_game()),
+        }
+        None
+    }
+
+    /// Makes progress until the next event, or until more input is needed.
+    /// `finished` signals that the end of the input has been reached.
+    pub fn advance(&mut self, finished: bool) -> Status {
+        self.buffer.consume(mem::take(&mut self.pending));
+        self.event = None;
+
+        loop {
+            if !self.skip(finished) {
+                return Status::NeedMore;
+            }
+            if let Step::Status(status) = self.step(finished) {
+                return status;
+            }
+        }
+    }
+
+    /// Skips to the start of the next game, without starting it. Escape
+    /// lines before the game are skipped. Returns whether there is another
+    /// game, or `None` if more input is needed.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn has_more(&mut self, finished: bool) -> Option<bool> {
+        self.buffer.consume(mem::take(&mut self.pending));
+        self.event = None;
+
+        loop {
+            if !self.skip(finished) {
+                return None;
+            }
+            match self.state {
+                State::Bom => {
+                    if let Step::Status(_) = self.step(finished) {
+                        return None;
+                    }
+                }
+                State::LeadingWhitespace => match self.buffer.data().first() {
+                    Some(b' ' | b'\t' | b'\r' | b'\n') => self.buffer.bump(),
+                    Some(b'%') => self.skip_until = Some((b'\n', true)),
+                    Some(_) => return Some(true),
+                    None if finished => return Some(false),
+                    None => return None,
+                },
+                _ => return Some(true),
+            }
+        }
+    }
+
+    fn emit(&mut self, event: RawEvent, consumed: usize) -> Step {
+        self.event = Some(event);
+        self.pending = consumed;
+        Step::Status(Status::Event)
+    }
+
+    fn syntax_error(&self, kind: SyntaxErrorKind, n: usize) -> SyntaxError {
+        let location = self.buffer.location_before(n);
+        SyntaxError::new(
+            kind,
+            location.offset,
+            location.line,
+            location.column,
+            self.games.saturating_sub(1),
+        )
+    }
+
+    fn tag_error(&self) -> SyntaxError {
+        // The buffer holds the complete line up to the limit, unless the end
+        // of the input was reached.
+        let kind = if self.buffer.data().len() + 1 >= self.max_tag_line_length {
+            SyntaxErrorKind::TagTooLong
+        } else {
+            SyntaxErrorKind::UnterminatedTag
+        };
+        self.syntax_error(kind, 1)
+    }
+
+    /// Skips to a pending needle. Returns `false` if more input is needed.
+    fn skip(&mut self, finished: bool) -> bool {
+        let Some((needle, bump)) = self.skip_until else {
+            return true;
+        };
+        match memchr::memchr(needle, self.buffer.data()) {
+            Some(pos) => {
+                self.buffer.consume(pos);
+                if bump {
+                    self.buffer.bump();
+                }
+            }
+            None => {
+                self.buffer.discard_data();
+                if !finished {
+                    return false;
+                }
+            }
+        }
+        self.skip_until = None;
+        true
+    }
+
+    fn step(&mut self, finished: bool) -> Step {
+        let data = self.buffer.data();
+        // A full buffer holds at least the maximum window of the reader.
+        let full = data.len() == self.buffer.capacity();
+        let need_more = Step::Status(Status::NeedMore);
+
+        match self.state {
+            State::Bom => {
+                if data.len() < 3 && !finished && b"\xef\xbb\xbf".starts_with(data) {
+                    return need_more;
+                }
+                if data.starts_with(b"\xef\xbb\xbf") {
+                    self.buffer.consume(3);
+                }
+                self.state = State::LeadingWhitespace;
+            }
+            State::LeadingWhitespace | State::TrailingWhitespace => match data.first() {
+                Some(b' ' | b'\t' | b'\r' | b'\n') => self.buffer.bump(),
+                Some(b'%') => return self.emit_line(1, true, finished),
+                None if !finished => return need_more,
+                None if matches!(self.state, State::LeadingWhitespace) => {
+                    return Step::Status(Status::Done);
+                }
+                Some(_) if matches!(self.state, State::LeadingWhitespace) => {
+                    self.games += 1;
+                    self.game_start = self.buffer.position();
+                    self.state = State::Tags;
+                    return self.emit(RawEvent::BeginTags, 0);
+                }
+                _ => {
+                    self.state = State::Bom;
+                    return self.emit(RawEvent::EndGame, 0);
+                }
+            },
+            State::Tags => {
+                if data.is_empty() {
+                    if !finished {
+                        return need_more;
+                    }
+                    self.state = State::Movetext;
+                    return self.emit(RawEvent::BeginMovetext, 0);
+                }
+
+                let (token, consumed) = lexer::tag_token(data, self.max_tag_line_length);
+                let complete = finished
+                    || full
+                    || match token {
+                        TagToken::Tag { .. } | TagToken::Malformed => data.len() > consumed,
+                        TagToken::Unclosed => false,
+                        TagToken::Escape | TagToken::End => true,
+                    };
+                if !complete {
+                    return need_more;
+                }
+
+                match token {
+                    TagToken::Tag { name, value } => {
+                        let event = RawEvent::Tag {
+                            name: range_in(data, name),
+                            value: range_in(data, value),
+                        };
+                        self.state = State::Ket;
+                        return self.emit(event, consumed);
+                    }
+                    TagToken::Malformed => {
+                        self.buffer.consume(consumed);
+                        self.state = State::Ket;
+                    }
+                    TagToken::Unclosed => {
+                        self.buffer.consume(consumed);
+                        let err = self.tag_error();
+                        let window = min(self.buffer.data().len(), self.max_tag_line_length - 1);
+                        self.buffer.consume(window);
+                        self.skip_until = Some((b'\n', true));
+                        self.error = Some(err);
+                        self.state = State::SkipTagLines;
+                    }
+                    TagToken::Escape => return self.emit_line(1, true, finished),
+                    TagToken::End => {
+                        self.state = State::Movetext;
+                        return self.emit(RawEvent::BeginMovetext, 0);
+                    }
+                }
+            }
+            State::Ket => match data.first() {
+                Some(b' ' | b'\t' | b'\r' | b']') => self.buffer.bump(),
+                Some(b'%') => {
+                    self.state = State::Tags;
+                    return self.emit_line(1, true, finished);
+                }
+                Some(b'\n') => {
+                    self.buffer.bump();
+                    self.state = State::Tags;
+                }
+                None if !finished => return need_more,
+                _ => self.state = State::Tags,
+            },
+            State::SkipTagLines => match data.first() {
+                Some(b'[' | b'%') => self.skip_until = Some((b'\n', true)),
+                None if !finished => return need_more,
+                _ => self.state = State::SkipMovetext,
+            },
+            State::Movetext => {
+                if data.is_empty() {
+                    if !finished {
+                        return need_more;
+                    }
+                    return self.end_movetext();
+                }
+
+                let (token, consumed) = lexer::token(data, self.max_comment_length);
+                // Some tokens look further ahead than what they consume,
+                // like `0-0` (`0-0-0`), `1` (`1/2-1/2`), or `\n` (`\n\r\n`).
+                let lookahead = match data[0] {
+                    b'0' => 4,
+                    b'1' => 6,
+                    b'\n' => 2,
+                    _ => 0,
+                };
+                let complete = finished
+                    || full
+                    || match token {
+                        Token::Comment(_) => true,
+                        Token::UnclosedComment => false,
+                        _ => data.len() > max(consumed, lookahead),
+                    };
+                if !complete {
+                    return need_more;
+                }
+
+                let event = match token {
+                    Token::MoveNumber(move_number) => RawEvent::MoveNumber(move_number),
+                    Token::San(san_plus) => RawEvent::San(san_plus),
+                    Token::Invalid(kind) => RawEvent::InvalidToken {
+                        token: 0..consumed,
+                        kind,
+                    },
+                    Token::Nag(nag) => RawEvent::Nag(nag),
+                    Token::Comment(comment) => RawEvent::Comment(range_in(data, comment)),
+                    Token::UnclosedComment => {
+                        self.buffer.consume(consumed);
+
+                        // The buffer holds the complete comment up to the
+                        // limit, unless the end of the input was reached.
+                        let window = min(self.buffer.data().len(), self.max_comment_length + 1);
+                        if window > self.max_comment_length && self.stream_long_comments {
+                            let err = self.syntax_error(SyntaxErrorKind::UnterminatedComment, 1);
+                            self.state = State::LongComment(err);
+                            return self.emit(RawEvent::BeginLongComment, 0);
+                        }
+
+                        let kind = if window > self.max_comment_length {
+                            SyntaxErrorKind::CommentTooLong
+                        } else {
+                            SyntaxErrorKind::UnterminatedComment
+                        };
+                        self.error = Some(self.syntax_error(kind, 1));
+                        self.buffer.consume(window);
+                        self.skip_until = Some((b'}', true));
+                        self.state = State::SkipMovetext;
+                        return Step::Continue;
+                    }
+                    Token::BeginVariation => RawEvent::BeginVariation,
+                    Token::EndVariation => RawEvent::EndVariation,
+                    Token::Outcome(outcome) => RawEvent::Outcome(outcome),
+                    Token::LineComment => return self.emit_line(consumed, false, finished),
+                    Token::Escape => return self.emit_line(consumed, true, finished),
+                    Token::End => {
+                        self.buffer.consume(consumed);
+                        return self.end_movetext();
+                    }
+                    Token::Ignored => {
+                        self.buffer.consume(consumed);
+                        return Step::Continue;
+                    }
+                };
+                return self.emit(event, consumed);
+            }
+            State::LongComment(err) => {
+                if data.is_empty() {
+                    if !finished {
+                        return need_more;
+                    }
+                    self.state = State::EndLongComment(Some(err));
+                    return self.emit(RawEvent::EndLongComment, 0);
+                }
+
+                return match memchr::memchr(b'}', data) {
+                    Some(right_brace) => {
+                        self.state = State::EndLongComment(None);
+                        self.emit(RawEvent::PartialComment(0..right_brace), right_brace + 1)
+                    }
+                    None => self.emit(RawEvent::PartialComment(0..data.len()), data.len()),
+                };
+            }
+            State::EndLongComment(None) => {
+                self.state = State::Movetext;
+                return self.emit(RawEvent::EndLongComment, 0);
+            }
+            State::EndLongComment(Some(err)) => {
+                self.last_game = Some(self.game_start..self.buffer.position());
+                self.state = State::Bom;
+                return Step::Status(Status::Error(err));
+            }
+            State::SkipVariation(depth) => {
+                if data.is_empty() || (data[0] == b'\n' && data.len() < 3) {
+                    if !finished {
+                        return need_more;
+                    }
+                    if data.is_empty() {
+                        self.state = State::Movetext;
+                        return Step::Continue;
+                    }
+                }
+
+                let (token, consumed) = lexer::skip_token(data);
+                match token {
+                    SkipToken::BeginVariation => {
+                        self.buffer.consume(consumed);
+                        self.state = State::SkipVariation(depth + 1);
+                    }
+                    SkipToken::EndVariation => match depth.checked_sub(1) {
+                        Some(depth) => {
+                            self.buffer.consume(consumed);
+                            self.state = State::SkipVariation(depth);
+                        }
+                        None => self.state = State::Movetext,
+                    },
+                    SkipToken::Comment => {
+                        self.buffer.consume(consumed);
+                        self.skip_until = Some((b'}', true));
+                    }
+                    SkipToken::LineComment => {
+                        self.buffer.consume(consumed);
+                        self.skip_until = Some((b'\n', false));
+                    }
+                    SkipToken::End => self.state = State::Movetext,
+                    SkipToken::Ignored => self.buffer.consume(consumed),
+                }
+            }
+            State::SkipMovetext => {
+                if data.is_empty() || (data[0] == b'\n' && data.len() < 3) {
+                    if !finished {
+                        return need_more;
+                    }
+                    if data.is_empty() {
+                        return self.end_movetext();
+                    }
+                }
+
+                let (token, consumed) = lexer::skip_token(data);
+                self.buffer.consume(consumed);
+                match token {
+                    SkipToken::Comment => self.skip_until = Some((b'}', true)),
+                    SkipToken::LineComment => self.skip_until = Some((b'\n', false)),
+                    SkipToken::End => return self.end_movetext(),
+                    SkipToken::BeginVariation | SkipToken::EndVariation | SkipToken::Ignored => (),
+                }
+            }
+        }
+
+        Step::Continue
+    }
+
+    /// Emits the rest of the line from `start`, up to the maximum comment
+    /// length, and skips to the line break (and over it, for escapes).
+    fn emit_line(&mut self, start: usize, escape: bool, finished: bool) -> Step {
+        let line = &self.buffer.data()[start..];
+        let window = min(line.len(), self.max_comment_length + 1);
+        let len = match memchr::memchr(b'\n', &line[..window]) {
+            Some(eol) => eol,
+            None if finished || window > self.max_comment_length => {
+                min(window, self.max_comment_length)
+            }
+            None => return Step::Status(Status::NeedMore),
+        };
+        self.skip_until = Some((b'\n', escape));
+        let range = start..start + len;
+        let event = if escape {
+            RawEvent::EscapeLine(range)
+        } else {
+            RawEvent::LineComment(range)
+        };
+        self.emit(event, start + len)
+    }
+
+    fn end_movetext(&mut self) -> Step {
+        self.last_game = Some(self.game_start..self.buffer.position());
+        match self.error.take() {
+            Some(err) => {
+                self.state = State::Bom;
+                Step::Status(Status::Error(err))
+            }
+            None => {
+                self.state = State::TrailingWhitespace;
+                Step::Continue
+            }
+        }
+    }
+}
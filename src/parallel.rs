@@ -0,0 +1,676 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Seek, SeekFrom},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Condvar, Mutex, PoisonError,
+    },
+    thread,
+};
+
+use crate::{
+    error::{Error, SyntaxError},
+    reader::ReaderBuilder,
+    slice::SliceReader,
+    visitor::{SliceVisitor, Visitor},
+};
+
+const DEFAULT_CHUNK_SIZE: u64 = 16 << 20;
+
+/// Number of chunks per thread that may be parsed ahead of the oldest chunk
+/// whose results have not been passed on yet.
+const WINDOW_PER_THREAD: usize = 4;
+
+/// Reads games from a large seekable file or in-memory slice on multiple
+/// threads.
+///
+/// The input is split into chunks of roughly equal size. Chunks begin and
+/// end at game boundaries, meaning a `[` that follows a blank line. Each
+/// chunk is parsed on a worker thread, with one visitor instance per
+/// worker. The results are passed to a callback on the calling thread,
+/// either in the original order of the games or in the order in which the
+/// chunks are completed.
+///
+/// A game boundary inside a `{ comment }` would split the game. Games that
+/// do not start with tags are never split from the previous game. Syntax
+/// errors report absolute byte offsets, line numbers and game indices. When
+/// results are not ordered, errors are therefore held back until all
+/// previous chunks have been completed. After an I/O error, the remaining
+/// games of the chunk are lost, and later game indices may be too low.
+///
+/// Workers parse at most a few chunks per thread ahead of the oldest chunk
+/// that has not been completed, so that memory usage stays bounded even if
+/// a single chunk is slow.
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::{ParallelReader, SanPlus, Visitor};
+///
+/// struct MoveCounter {
+///     moves: usize,
+/// }
+///
+/// impl Visitor for MoveCounter {
+///     type Result = usize;
+///
+///     fn san(&mut self, _san_plus: SanPlus) {
+///         self.moves += 1;
+///     }
+///
+///     fn end_game(&mut self) -> usize {
+///         std::mem::take(&mut self.moves)
+///     }
+/// }
+///
+/// let pgn = "[Event \"?\"]\n\n1. e4 e5 2. Nf3 *\n\n".repeat(1000);
+///
+/// let mut moves = Vec::new();
+/// ParallelReader::new()
+///     .threads(4)
+///     .chunk_size(1 << 10)
+///     .read_slice(
+///         pgn.as_bytes(),
+///         || MoveCounter { moves: 0 },
+///         |result| moves.push(result.expect("valid pgn")),
+///     );
+///
+/// assert_eq!(moves, vec![3; 1000]);
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct ParallelReader {
+    threads: usize,
+    chunk_size: u64,
+    ordered: bool,
+    builder: ReaderBuilder,
+}
+
+impl Default for ParallelReader {
+    fn default() -> ParallelReader {
+        ParallelReader::new()
+    }
+}
+
+impl ParallelReader {
+    /// Creates a parallel reader with the default settings: one thread per
+    /// available CPU, chunks of 16 MiB, and results in the original order.
+    pub fn new() -> ParallelReader {
+        ParallelReader {
+            threads: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            ordered: true,
+            builder: ReaderBuilder::new(),
+        }
+    }
+
+    /// Sets the number of worker threads. `0` selects the available
+    /// parallelism.
+    pub fn threads(&mut self, threads: usize) -> &mut ParallelReader {
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the approximate size of the chunks in bytes.
+    pub fn chunk_size(&mut self, chunk_size: u64) -> &mut ParallelReader {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets whether results are produced in the original order of the games
+    /// (the default), or in the order in which chunks are completed.
+    pub fn ordered(&mut self, ordered: bool) -> &mut ParallelReader {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Sets the configuration of the [`BufferedReader`](crate::BufferedReader)
+    /// used for each chunk of a file.
+    pub fn reader_builder(&mut self, builder: ReaderBuilder) -> &mut ParallelReader {
+        self.builder = builder;
+        self
+    }
+
+    /// Reads all games from an in-memory slice, using a
+    /// [`SliceReader`] for each chunk. `visitor` is
+    /// called once on each worker thread to create its visitor. `sink` is
+    /// called with the result of each game.
+    pub fn read_slice<'a, V, F, S>(&self, data: &'a [u8], visitor: F, sink: S)
+    where
+        V: SliceVisitor<'a>,
+        V::Result: Send,
+        F: Fn() -> V + Sync,
+        S: FnMut(Result<V::Result, SyntaxError>),
+    {
+        let chunk_size = self.chunk_size_for(data.len() as u64) as usize;
+        let chunks = data.len().div_ceil(chunk_size);
+
+        self.run(chunks, visitor, sink, |visitor, index| {
+            let start = match index {
+                0 => 0,
+                _ => next_boundary(data, index * chunk_size).unwrap_or(data.len()),
+            };
+            let end = next_boundary(data, (index + 1) * chunk_size).unwrap_or(data.len());
+
+            let mut chunk = Chunk::default();
+            if start < end {
+                let data = &data[start..end];
+                chunk.lines = memchr::memchr_iter(b'\n', data).count() as u64;
+                let mut reader = SliceReader::new(data);
+                loop {
+                    match reader.read_game(visitor) {
+                        Ok(Some(result)) => chunk.results.push(Ok(result)),
+                        Ok(None) => break,
+                        Err(err) => chunk.results.push(Err(err.rebase(start as u64))),
+                    }
+                }
+                chunk.games = chunk.results.len() as u64;
+            }
+            chunk
+        });
+    }
+
+    /// Reads all games from a seekable file. `open` is called on each worker
+    /// thread to open an independent handle to the file, for example
+    /// `|| File::open(path)`. `visitor` is called once on each worker thread
+    /// to create its visitor. `sink` is called with the result of each game.
+    ///
+    /// # Errors
+    ///
+    /// I/O error when opening the file or determining its length. Later
+    /// errors are passed to `sink`.
+    pub fn read_file<R, O, V, F, S>(&self, open: O, visitor: F, sink: S) -> io::Result<()>
+    where
+        R: Read + Seek,
+        O: Fn() -> io::Result<R> + Sync,
+        V: Visitor,
+        V::Result: Send,
+        F: Fn() -> V + Sync,
+        S: FnMut(Result<V::Result, Error>),
+    {
+        let len = open()?.seek(SeekFrom::End(0))?;
+        let chunk_size = self.chunk_size_for(len);
+        let chunks = len.div_ceil(chunk_size) as usize;
+
+        self.run(
+            chunks,
+            || (visitor(), None),
+            sink,
+            |(visitor, file), index| {
+                let file = match file {
+                    Some(file) => file,
+                    None => match open() {
+                        Ok(opened) => file.insert(opened),
+                        Err(err) => return Chunk::io_error(err),
+                    },
+                };
+                self.read_file_chunk(file, len, chunk_size, index, visitor)
+                    .unwrap_or_else(Chunk::io_error)
+            },
+        );
+
+        Ok(())
+    }
+
+    fn read_file_chunk<R: Read + Seek, V: Visitor>(
+        &self,
+        file: &mut R,
+        len: u64,
+        chunk_size: u64,
+        index: usize,
+        visitor: &mut V,
+    ) -> io::Result<Chunk<V::Result, Error>> {
+        let index = index as u64;
+        let start = match index {
+            0 => 0,
+            _ => find_boundary(file, index * chunk_size)?.unwrap_or(len),
+        };
+        let end = find_boundary(file, (index + 1) * chunk_size)?.unwrap_or(len);
+
+        let mut chunk = Chunk::default();
+        if start < end {
+            file.seek(SeekFrom::Start(start))?;
+            let mut reader = self.builder.build(CountLines {
+                inner: file.take(end - start),
+                lines: 0,
+            });
+            loop {
+                match reader.read_game(visitor) {
+                    Ok(Some(result)) => chunk.results.push(Ok(result)),
+                    Ok(None) => break,
+                    Err(Error::Syntax(err)) => {
+                        chunk.results.push(Err(Error::Syntax(err.rebase(start))))
+                    }
+                    Err(err) => {
+                        chunk.results.push(Err(err));
+                        break;
+                    }
+                }
+            }
+            // Count the line breaks of the rest of the chunk, if reading
+            // was aborted.
+            let (_, mut counter) = reader.into_inner().into_inner();
+            let _ = io::copy(&mut counter, &mut io::sink());
+            chunk.lines = counter.lines;
+            chunk.games = chunk.results.len() as u64;
+        }
+        Ok(chunk)
+    }
+
+    fn chunk_size_for(&self, len: u64) -> u64 {
+        self.chunk_size.clamp(1, len.max(1))
+    }
+
+    fn run<W, T, E, I, S, P>(&self, chunks: usize, init: I, mut sink: S, parse: P)
+    where
+        T: Send,
+        E: Relocate + Send,
+        I: Fn() -> W + Sync,
+        S: FnMut(Result<T, E>),
+        P: Fn(&mut W, usize) -> Chunk<T, E> + Sync,
+    {
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            threads => threads,
+        }
+        .min(chunks);
+
+        let next_chunk = AtomicUsize::new(0);
+        let window = Window {
+            done: Mutex::new(0),
+            advanced: Condvar::new(),
+            size: threads.saturating_mul(WINDOW_PER_THREAD),
+        };
+
+        thread::scope(|scope| {
+            let (send, recv) = mpsc::sync_channel(threads);
+
+            for _ in 0..threads {
+                let send = send.clone();
+                let (init, parse, next_chunk, window) = (&init, &parse, &next_chunk, &window);
+                scope.spawn(move || {
+                    let _release = Release(window);
+                    let mut worker = init();
+                    loop {
+                        let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                        if index >= chunks {
+                            break;
+                        }
+                        window.wait(index);
+                        if send.send((index, parse(&mut worker, index))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(send);
+
+            // If the sink panics, workers must not wait for chunks that will
+            // never be passed on. They stop once the channel is closed.
+            let _release = Release(&window);
+
+            // Chunks that have been completed, but not all of their results
+            // could be passed on yet.
+            let mut pending = BTreeMap::new();
+            let (mut next, mut lines, mut games) = (0, 0, 0);
+            for (index, mut chunk) in recv {
+                if !self.ordered {
+                    // Results can be passed on right away, but errors need
+                    // the line breaks and games of all previous chunks.
+                    let (errors, results) = chunk.results.into_iter().partition(Result::is_err);
+                    chunk.results = errors;
+                    results.into_iter().for_each(&mut sink);
+                }
+                pending.insert(index, chunk);
+                while let Some(chunk) = pending.remove(&next) {
+                    for result in chunk.results {
+                        sink(result.map_err(|err| err.relocate(lines, games)));
+                    }
+                    lines += chunk.lines;
+                    games += chunk.games;
+                    next += 1;
+                }
+                window.advance(next);
+            }
+        });
+    }
+}
+
+/// The results of a chunk, along with the number of line breaks and games
+/// in it.
+struct Chunk<T, E> {
+    results: Vec<Result<T, E>>,
+    lines: u64,
+    games: u64,
+}
+
+impl<T, E> Default for Chunk<T, E> {
+    fn default() -> Chunk<T, E> {
+        Chunk {
+            results: Vec::new(),
+            lines: 0,
+            games: 0,
+        }
+    }
+}
+
+impl<T> Chunk<T, Error> {
+    fn io_error(err: io::Error) -> Chunk<T, Error> {
+        Chunk {
+            results: vec![Err(Error::Io(err))],
+            ..Chunk::default()
+        }
+    }
+}
+
+/// Errors that can be moved from chunk-relative to absolute line numbers
+/// and game indices.
+trait Relocate {
+    fn relocate(self, lines: u64, games: u64) -> Self;
+}
+
+impl Relocate for SyntaxError {
+    fn relocate(self, lines: u64, games: u64) -> SyntaxError {
+        SyntaxError::relocate(self, lines, games)
+    }
+}
+
+impl Relocate for Error {
+    fn relocate(self, lines: u64, games: u64) -> Error {
+        match self {
+            Error::Syntax(err) => Error::Syntax(err.relocate(lines, games)),
+            err => err,
+        }
+    }
+}
+
+/// Counts the line breaks read from the inner reader.
+struct CountLines<R> {
+    inner: R,
+    lines: u64,
+}
+
+impl<R: Read> Read for CountLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.lines += memchr::memchr_iter(b'\n', &buf[..len]).count() as u64;
+        Ok(len)
+    }
+}
+
+/// Limits how far workers may run ahead of the oldest chunk whose results
+/// have not been passed on, so that pending results stay bounded.
+struct Window {
+    done: Mutex<usize>,
+    advanced: Condvar,
+    size: usize,
+}
+
+impl Window {
+    /// Blocks until chunk `index` is within the window.
+    fn wait(&self, index: usize) {
+        let mut done = self.done.lock().unwrap_or_else(PoisonError::into_inner);
+        while index >= done.saturating_add(self.size) {
+            done = self
+                .advanced
+                .wait(done)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Records that all chunks before `done` have been passed on.
+    fn advance(&self, done: usize) {
+        let mut guard = self.done.lock().unwrap_or_else(PoisonError::into_inner);
+        *guard = done.max(*guard);
+        self.advanced.notify_all();
+    }
+}
+
+/// Opens the window completely if a worker or the sink panics, so that
+/// workers waiting for a chunk that will never be passed on do not block
+/// forever.
+struct Release<'a>(&'a Window);
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.advance(usize::MAX);
+        }
+    }
+}
+
+/// Finds the first game boundary at or after `from`: the position of a `[`
+/// that follows a blank line.
+fn next_boundary(data: &[u8], from: usize) -> Option<usize> {
+    let offset = from.checked_sub(1)?;
+    memchr::memmem::find_iter(data.get(offset..)?, b"\n[")
+        .map(|newline| offset + newline + 1)
+        .find(|&bracket| matches!(data[..bracket - 1], [.., b'\n'] | [.., b'\n', b'\r']))
+}
+
+/// Like [`next_boundary()`], but reads from a file in blocks.
+fn find_boundary<R: Read + Seek>(file: &mut R, from: u64) -> io::Result<Option<u64>> {
+    // Include enough context to recognize a blank line before `from`.
+    let mut base = from.saturating_sub(3);
+    file.seek(SeekFrom::Start(base))?;
+
+    let mut buf = Vec::new();
+    let mut block = vec![0; 1 << 16];
+    loop {
+        let len = match file.read(&mut block) {
+            Ok(0) => return Ok(None),
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        buf.extend_from_slice(&block[..len]);
+
+        let relative_from = from.saturating_sub(base).max(1) as usize;
+        if let Some(bracket) = next_boundary(&buf, relative_from) {
+            return Ok(Some(base + bracket as u64));
+        }
+
+        // Keep enough context for the next block.
+        let keep = buf.len().min(3);
+        base += (buf.len() - keep) as u64;
+        buf.drain(..buf.len() - keep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io, mem,
+        panic::{self, AssertUnwindSafe},
+        time::Duration,
+    };
+
+    use shakmaty::san::SanPlus;
+
+    use super::*;
+    use crate::types::RawTag;
+
+    #[derive(Default)]
+    struct Summary {
+        event: Vec<u8>,
+        moves: usize,
+    }
+
+    impl Visitor for Summary {
+        type Result = (Vec<u8>, usize);
+
+        fn begin_tags(&mut self) {
+            *self = Summary::default();
+        }
+
+        fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+            if name == b"Event" {
+                self.event = value.as_bytes().to_owned();
+            }
+        }
+
+        fn san(&mut self, _san_plus: SanPlus) {
+            self.moves += 1;
+        }
+
+        fn end_game(&mut self) -> Self::Result {
+            (mem::take(&mut self.event), mem::take(&mut self.moves))
+        }
+    }
+
+    fn pgn() -> Vec<u8> {
+        let mut pgn = Vec::new();
+        for i in 0..500 {
+            let newline = if i % 7 == 0 { "\r\n" } else { "\n" };
+            pgn.extend_from_slice(format!("[Event \"{i}\"]{newline}{newline}").as_bytes());
+            for _ in 0..(i % 13) {
+                pgn.extend_from_slice(b"Nf3 Nf6 Ng1 Ng8 ");
+            }
+            pgn.extend_from_slice(format!("*{newline}{newline}").as_bytes());
+        }
+        pgn
+    }
+
+    fn sequential(pgn: &[u8]) -> Vec<(Vec<u8>, usize)> {
+        let mut reader = SliceReader::new(pgn);
+        let mut results = Vec::new();
+        while let Some(result) = reader.read_game(&mut Summary::default()).unwrap() {
+            results.push(result);
+        }
+        results
+    }
+
+    #[test]
+    fn test_next_boundary() {
+        let data = b"[A \"1\"]\n\n1. e4\n\n[B \"2\"]\r\n\r\n1. d4\n[C \"3\"]\n\n[D \"4\"]";
+        assert_eq!(next_boundary(data, 0), None);
+        assert_eq!(next_boundary(data, 1), Some(16));
+        assert_eq!(next_boundary(data, 16), Some(16));
+        assert_eq!(next_boundary(data, 17), Some(42));
+        assert_eq!(next_boundary(data, 43), None);
+
+        for from in 1..data.len() + 3 {
+            let mut cursor = io::Cursor::new(&data[..]);
+            assert_eq!(
+                find_boundary(&mut cursor, from as u64).unwrap(),
+                next_boundary(data, from).map(|b| b as u64)
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_slice() {
+        let pgn = pgn();
+        let expected = sequential(&pgn);
+        assert_eq!(expected.len(), 500);
+
+        for chunk_size in [1, 100, 1000, 1 << 20] {
+            let mut results = Vec::new();
+            ParallelReader::new()
+                .threads(4)
+                .chunk_size(chunk_size)
+                .read_slice(&pgn, Summary::default, |result| {
+                    results.push(result.unwrap())
+                });
+            assert_eq!(results, expected);
+
+            let mut results = Vec::new();
+            ParallelReader::new()
+                .threads(3)
+                .chunk_size(chunk_size)
+                .ordered(false)
+                .read_slice(&pgn, Summary::default, |result| {
+                    results.push(result.unwrap())
+                });
+            results.sort_by_key(|(event, _)| btoi::btou::<usize>(event).unwrap());
+            assert_eq!(results, expected);
+        }
+    }
+
+    #[test]
+    fn test_read_file() {
+        let pgn = pgn();
+        let expected = sequential(&pgn);
+
+        for chunk_size in [1, 100, 1000, 1 << 20] {
+            let mut results = Vec::new();
+            ParallelReader::new()
+                .threads(4)
+                .chunk_size(chunk_size)
+                .read_file(
+                    || Ok(io::Cursor::new(&pgn)),
+                    Summary::default,
+                    |result| results.push(result.unwrap()),
+                )
+                .unwrap();
+            assert_eq!(results, expected);
+        }
+    }
+
+    /// Takes a while to parse the first game, so that other workers run
+    /// ahead and wait for its chunk to be passed on.
+    struct SlowStart(Summary);
+
+    impl Visitor for SlowStart {
+        type Result = (Vec<u8>, usize);
+
+        fn begin_tags(&mut self) {
+            Visitor::begin_tags(&mut self.0);
+        }
+
+        fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+            Visitor::tag(&mut self.0, name, value);
+        }
+
+        fn end_game(&mut self) -> Self::Result {
+            if self.0.event == b"0" {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Visitor::end_game(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn test_panicking_sink() {
+        let pgn = pgn();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            ParallelReader::new().threads(2).chunk_size(64).read_slice(
+                &pgn,
+                || SlowStart(Summary::default()),
+                |_| panic!("sink"),
+            );
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_syntax_error_offset() {
+        let pgn = b"[Event \"1\"]\n\n1. e4 *\n\n[Event \"2\"]\n\n1. e4 { unterminated\n\n[Event \"3\"]\n\n1. d4 *";
+        let mut results = Vec::new();
+        ParallelReader::new()
+            .chunk_size(20)
+            .read_slice(pgn, Summary::default, |result| results.push(result));
+
+        assert_eq!(results.len(), 3);
+        let err = results[1].as_ref().expect_err("unterminated comment");
+        assert_eq!(err.offset(), 41);
+        assert_eq!(err.line(), 7);
+        assert_eq!(err.game(), 1);
+        assert_eq!(results[2].as_ref().unwrap(), &(b"3".to_vec(), 1));
+
+        let mut errors = Vec::new();
+        ParallelReader::new()
+            .chunk_size(20)
+            .ordered(false)
+            .read_file(
+                || Ok(io::Cursor::new(&pgn[..])),
+                Summary::default,
+                |result| errors.extend(result.err()),
+            )
+            .unwrap();
+        match &errors[..] {
+            [Error::Syntax(err)] => assert_eq!((err.offset(), err.line(), err.game()), (41, 7, 1)),
+            _ => panic!("expected syntax error, got {errors:?}"),
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use std::{mem, ops::Range};
+
+use shakmaty::{
+    san::SanPlus,
+    variant::{Variant, VariantPosition},
+    Bitboard, ByColor, ByRole, Color, Move, Piece, Position, Role,
+};
+
+use crate::{
+    position::{forward_visitor, ply, PositionAdapter, PositionVisitor},
+    search::material_reachable,
+    types::Skip,
+};
+
+/// A condition on a single position, to be searched for with a
+/// [`PatternSearch`].
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::Pattern;
+/// use shakmaty::{Bitboard, Color, Piece, Rank};
+///
+/// // Rook and pawn versus rook, with White to move, and the white pawn on
+/// // the seventh rank.
+/// let pawn = Piece::from_char('P').expect("piece");
+/// let pattern = Pattern::All(vec![
+///     Pattern::material("KRPvKR").expect("signature"),
+///     Pattern::Turn(Color::White),
+///     Pattern::on(pawn, Bitboard::from_rank(Rank::Seventh)),
+/// ]);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Pattern {
+    /// Exactly the given material on the board, counting kings.
+    Material(ByColor<ByRole<u8>>),
+    /// The number of the given piece on the given squares is in the
+    /// inclusive range from `min` to `max`.
+    Count {
+        piece: Piece,
+        squares: Bitboard,
+        min: u32,
+        max: u32,
+    },
+    /// The given side is to move.
+    Turn(Color),
+    /// The side to move is in check.
+    Check,
+    /// All of the patterns match. Matches if there are none.
+    All(Vec<Pattern>),
+    /// Any of the patterns matches. Does not match if there are none.
+    Any(Vec<Pattern>),
+    /// The pattern does not match.
+    Not(Box<Pattern>),
+}
+
+impl Pattern {
+    /// Parses a material signature like `KRPvKR` or `KRPKR`, with the white
+    /// pieces first. Without `v`, the black pieces start at the second king.
+    ///
+    /// Returns `None` if the signature contains anything but piece letters,
+    /// or if a side has no pieces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::Pattern;
+    ///
+    /// assert_eq!(Pattern::material("KRPvKR"), Pattern::material("KRPKR"));
+    /// assert_eq!(Pattern::material("KRP"), None);
+    /// assert_eq!(Pattern::material("KRvKX"), None);
+    /// ```
+    pub fn material(signature: &str) -> Option<Pattern> {
+        let (white, black) = match signature.split_once(['v', 'V']) {
+            Some(sides) => sides,
+            None => {
+                let split = signature
+                    .char_indices()
+                    .filter(|&(_, ch)| ch == 'K')
+                    .nth(1)
+                    .map_or(signature.len(), |(i, _)| i);
+                signature.split_at(split)
+            }
+        };
+
+        let side = |pieces: &str| {
+            let mut material = ByRole::<u8>::default();
+            for ch in pieces.chars() {
+                let count = material.get_mut(Role::from_char(ch)?);
+                *count = count.checked_add(1)?;
+            }
+            (!pieces.is_empty()).then_some(material)
+        };
+
+        Some(Pattern::Material(ByColor {
+            white: side(white)?,
+            black: side(black)?,
+        }))
+    }
+
+    /// At least one of the given piece on the given squares.
+    pub fn on(piece: Piece, squares: Bitboard) -> Pattern {
+        Pattern::Count {
+            piece,
+            squares,
+            min: 1,
+            max: u32::MAX,
+        }
+    }
+
+    /// Each side has exactly one bishop, and the bishops are on squares of
+    /// different colors.
+    pub fn opposite_colored_bishops() -> Pattern {
+        let bishops = |color, squares| Pattern::Count {
+            piece: Piece {
+                color,
+                role: Role::Bishop,
+            },
+            squares,
+            min: 1,
+            max: 1,
+        };
+        let exactly_one = |color| bishops(color, Bitboard::FULL);
+        Pattern::All(vec![
+            exactly_one(Color::White),
+            exactly_one(Color::Black),
+            Pattern::Any(vec![
+                Pattern::All(vec![
+                    bishops(Color::White, Bitboard::LIGHT_SQUARES),
+                    bishops(Color::Black, Bitboard::DARK_SQUARES),
+                ]),
+                Pattern::All(vec![
+                    bishops(Color::White, Bitboard::DARK_SQUARES),
+                    bishops(Color::Black, Bitboard::LIGHT_SQUARES),
+                ]),
+            ]),
+        ])
+    }
+
+    /// Tests if the pattern matches the position.
+    pub fn matches(&self, pos: &VariantPosition) -> bool {
+        match self {
+            Pattern::Material(material) => pos.board().material() == *material,
+            Pattern::Count {
+                piece,
+                squares,
+                min,
+                max,
+            } => {
+                let count = (pos.board().by_piece(*piece) & *squares).count() as u32;
+                (*min..=*max).contains(&count)
+            }
+            Pattern::Turn(color) => pos.turn() == *color,
+            Pattern::Check => pos.is_check(),
+            Pattern::All(patterns) => patterns.iter().all(|pattern| pattern.matches(pos)),
+            Pattern::Any(patterns) => patterns.iter().any(|pattern| pattern.matches(pos)),
+            Pattern::Not(pattern) => !pattern.matches(pos),
+        }
+    }
+
+    /// Tests if the pattern can not match any position reachable from
+    /// `pos`. This is conservative: `false` does not mean that a matching
+    /// position is actually reachable.
+    fn ruled_out(&self, pos: &VariantPosition) -> bool {
+        match self {
+            Pattern::Material(material) => {
+                pos.variant() != Variant::Crazyhouse
+                    && !material_reachable(&pos.board().material(), material)
+            }
+            Pattern::All(patterns) => patterns.iter().any(|pattern| pattern.ruled_out(pos)),
+            Pattern::Any(patterns) => patterns.iter().all(|pattern| pattern.ruled_out(pos)),
+            Pattern::Count { .. } | Pattern::Turn(_) | Pattern::Check | Pattern::Not(_) => false,
+        }
+    }
+}
+
+/// A run of consecutive positions matching a [`PatternSearch`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PatternHit {
+    /// Number of the game, counting from 0 for the first game visited by
+    /// the search. Games are counted over all readers that the search is
+    /// used with, until [`PatternSearch::reset_games()`].
+    pub game: usize,
+    /// Plies of the matching positions, counted like
+    /// [`PositionHit::ply`](crate::PositionHit::ply). The end is exclusive,
+    /// and saturates at `u32::MAX`.
+    pub plies: Range<u32>,
+}
+
+#[derive(Debug)]
+struct Matcher {
+    pattern: Pattern,
+    min_plies: u32,
+    games: usize,
+    // Ply of the first matching position of the current run, and the
+    // number of matching positions so far. Counted separately, because the
+    // ply saturates.
+    run: Option<(u32, u32)>,
+    hits: Vec<PatternHit>,
+}
+
+impl Matcher {
+    fn check(&mut self, pos: &VariantPosition) {
+        if self.pattern.matches(pos) {
+            match self.run {
+                Some((_, ref mut len)) => *len = len.saturating_add(1),
+                None => self.run = Some((ply(pos), 1)),
+            }
+        } else {
+            self.close();
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some((start, len)) = self.run.take() {
+            if len >= self.min_plies {
+                self.hits.push(PatternHit {
+                    game: self.games - 1,
+                    plies: start..start.saturating_add(len),
+                });
+            }
+        }
+    }
+}
+
+impl PositionVisitor for Matcher {
+    type Result = Vec<PatternHit>;
+
+    fn begin_tags(&mut self) {
+        self.games += 1;
+        self.run = None;
+    }
+
+    fn begin_movetext(&mut self, pos: &VariantPosition) -> Skip {
+        if self.pattern.ruled_out(pos) {
+            return Skip(true);
+        }
+        self.check(pos);
+        Skip(false)
+    }
+
+    fn play(
+        &mut self,
+        _before: &VariantPosition,
+        _san_plus: SanPlus,
+        _m: Move,
+        after: &VariantPosition,
+    ) {
+        self.check(after);
+    }
+
+    fn begin_variation(&mut self, _pos: &VariantPosition) -> Skip {
+        // Runs are consecutive positions of one line, and can not be
+        // continued through a variation.
+        Skip(true)
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        self.close();
+        mem::take(&mut self.hits)
+    }
+}
+
+/// A [`Visitor`](crate::Visitor) that finds runs of consecutive mainline positions matching
+/// a [`Pattern`].
+///
+/// Positions are tracked by a [`PositionAdapter`], so games start from the
+/// `Variant` and `FEN` tags. The starting position and the position after
+/// each mainline move are tested. Variations are always skipped, unlike
+/// with [`PositionSearch::search_variations()`](crate::PositionSearch::search_variations),
+/// because a run of consecutive positions follows a single line. The
+/// search stops at the first illegal move.
+///
+/// Games are skipped before reading the movetext if the material of the
+/// starting position rules out the pattern.
+///
+/// The result for each game is the list of runs of at least
+/// [`min_plies()`](PatternSearch::min_plies) matching positions, in the
+/// order in which they occur.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, Pattern, PatternSearch};
+///
+/// let pgn = b"[FEN \"8/8/4k3/8/8/2K5/1P2r3/R7 w - - 0 50\"]\n\n50. Ra6+ Kd5 51. Kb3 Re3+ 52. Ka4 Re4+ 53. Kb5 Rb4+ 54. Kxb4 *";
+///
+/// let mut search = PatternSearch::new(Pattern::material("KRPvKR").expect("signature"));
+/// search.min_plies(4);
+///
+/// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let hits = reader.read_game(&mut search)?.expect("game");
+///
+/// assert_eq!(hits.len(), 1);
+/// assert_eq!(hits[0].plies, 98..107);
+/// # Ok::<_, io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct PatternSearch {
+    inner: PositionAdapter<Matcher>,
+}
+
+impl PatternSearch {
+    /// Creates a new search for positions matching `pattern`.
+    pub fn new(pattern: Pattern) -> PatternSearch {
+        PatternSearch {
+            inner: PositionAdapter::new(Matcher {
+                pattern,
+                min_plies: 1,
+                games: 0,
+                run: None,
+                hits: Vec::new(),
+            }),
+        }
+    }
+
+    /// Starts counting games from 0 again, for example before searching
+    /// another file.
+    pub fn reset_games(&mut self) -> &mut PatternSearch {
+        self.inner.get_mut().games = 0;
+        self
+    }
+
+    /// Only report runs of at least `min_plies` consecutive matching
+    /// positions. Defaults to 1.
+    pub fn min_plies(&mut self, min_plies: u32) -> &mut PatternSearch {
+        self.inner.get_mut().min_plies = min_plies;
+        self
+    }
+}
+
+forward_visitor!(PatternSearch, Vec<PatternHit>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::collect_hits as search;
+
+    #[test]
+    fn test_material() {
+        let Some(Pattern::Material(material)) = Pattern::material("KQRRBBNNPPvK") else {
+            panic!("expected material");
+        };
+        assert_eq!(material.white.rook, 2);
+        assert_eq!(material.white.pawn, 2);
+        assert_eq!(material.black.king, 1);
+        assert_eq!(material.black.queen, 0);
+
+        assert_eq!(Pattern::material("KvK"), Pattern::material("KK"));
+        assert_eq!(Pattern::material("K"), None);
+        assert_eq!(Pattern::material(""), None);
+    }
+
+    #[test]
+    fn test_opposite_colored_bishops() {
+        let pgn = b"[FEN \"4k3/8/2b5/8/8/8/8/2B1K3 w - - 0 1\"]\n\n1. Bf4 Bd5 2. Be3 *\n\n[FEN \"4k3/8/3b4/8/8/8/8/2B1K3 w - - 0 1\"]\n\n1. Bf4 *\n\n1. e4 *";
+        let mut s = PatternSearch::new(Pattern::opposite_colored_bishops());
+        let hits = search(&mut s, pgn);
+        assert_eq!(
+            hits,
+            [PatternHit {
+                game: 0,
+                plies: 0..4,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_runs() {
+        // Black is in check after 2. Bb5+, White after 3... Qa5+.
+        let pgn = b"1. e4 d5 2. Bb5+ c6 3. d3 Qa5+ 4. Nc3 Qa6 5. Qe2 Qd6 6. Qf3 *";
+        let check =
+            |turn| PatternSearch::new(Pattern::All(vec![Pattern::Check, Pattern::Turn(turn)]));
+
+        let hits = search(&mut check(Color::Black), pgn);
+        assert_eq!(
+            hits.iter()
+                .map(|hit| (hit.plies.start, hit.plies.end))
+                .collect::<Vec<_>>(),
+            [(3, 4)]
+        );
+        let hits = search(&mut check(Color::White), pgn);
+        assert_eq!(
+            hits.iter()
+                .map(|hit| (hit.plies.start, hit.plies.end))
+                .collect::<Vec<_>>(),
+            [(6, 7)]
+        );
+
+        // Black queen on the a-file for at least 2 plies.
+        let queen = Piece {
+            color: Color::Black,
+            role: Role::Queen,
+        };
+        let mut s = PatternSearch::new(Pattern::on(queen, Bitboard::from_file(shakmaty::File::A)));
+        s.min_plies(2);
+        let hits = search(&mut s, pgn);
+        assert_eq!(
+            hits.iter()
+                .map(|hit| (hit.plies.start, hit.plies.end))
+                .collect::<Vec<_>>(),
+            [(6, 10)]
+        );
+    }
+
+    #[test]
+    fn test_saturated_ply() {
+        let pgn = b"[FEN \"4k3/8/8/8/8/8/8/4K3 b - - 0 4294967295\"]\n\n4294967295... Kd7 *";
+        let mut s = PatternSearch::new(Pattern::material("KvK").expect("signature"));
+        s.min_plies(2);
+        assert_eq!(
+            search(&mut s, pgn),
+            [PatternHit {
+                game: 0,
+                plies: u32::MAX..u32::MAX,
+            }]
+        );
+
+        assert_eq!(search(&mut s, pgn)[0].game, 1);
+        s.reset_games();
+        assert_eq!(search(&mut s, pgn)[0].game, 0);
+    }
+}
@@ -0,0 +1,657 @@
+use std::{error, fmt, mem};
+
+use shakmaty::{
+    fen::{Fen, ParseFenError},
+    san::{SanError, SanPlus},
+    variant::VariantPosition,
+    CastlingMode, EnPassantMode, Move, Outcome, Position, PositionError,
+};
+
+use crate::{
+    types::{InvalidTokenKind, MoveNumber, Nag, RawComment, RawTag, Skip, TokenAction},
+    variant::VariantTag,
+    visitor::Visitor,
+};
+
+/// Error when setting up the starting position of a game.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SetupError {
+    /// The `Variant` tag does not name a supported variant.
+    UnknownVariant,
+    /// The `FEN` tag is not syntactically valid.
+    InvalidFen(ParseFenError),
+    /// The `FEN` tag describes an illegal position.
+    IllegalFen(Box<PositionError<VariantPosition>>),
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::UnknownVariant => f.write_str("unknown variant tag"),
+            SetupError::InvalidFen(err) => write!(f, "invalid fen tag: {err}"),
+            SetupError::IllegalFen(err) => write!(f, "illegal fen tag: {err}"),
+        }
+    }
+}
+
+impl error::Error for SetupError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SetupError::UnknownVariant => None,
+            SetupError::InvalidFen(err) => Some(err),
+            SetupError::IllegalFen(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+/// Consumes games with the current position, as tracked by a
+/// [`PositionAdapter`].
+pub trait PositionVisitor {
+    /// Value produced by the visitor after reading a game.
+    type Result;
+
+    /// Called at the start of the game; directly before reading game tags.
+    fn begin_tags(&mut self) {}
+    /// Called when parsing a game tag pair like `[White "Deep Blue"]`.
+    fn tag(&mut self, _name: &[u8], _value: RawTag<'_>) {}
+    /// Called after reading the tags of a game, with the starting position.
+    /// May skip over the following movetext directly to
+    /// [`end_game()`](trait.PositionVisitor.html#tymethod.end_game).
+    fn begin_movetext(&mut self, _pos: &VariantPosition) -> Skip {
+        Skip(false)
+    }
+    /// Called instead of
+    /// [`begin_movetext()`](trait.PositionVisitor.html#method.begin_movetext)
+    /// if the starting position could not be set up. The movetext is
+    /// skipped.
+    fn setup_error(&mut self, _error: SetupError) {}
+
+    /// Called for each move number like `12.` or `12...`, with the position
+    /// before the following move. Not reported after an illegal move in the
+    /// same line.
+    fn move_number(&mut self, _pos: &VariantPosition, _move_number: MoveNumber) {}
+    /// Called for each legal move, with the position before the move, the
+    /// move, and the position after the move.
+    fn play(
+        &mut self,
+        _before: &VariantPosition,
+        _san_plus: SanPlus,
+        _m: Move,
+        _after: &VariantPosition,
+    ) {
+    }
+    /// Called for an illegal or ambiguous move, with the position in which
+    /// it was played. The remaining moves of the same line can not be
+    /// followed and are not reported, but variations that replace the
+    /// illegal move are.
+    fn illegal_move(&mut self, _pos: &VariantPosition, _san_plus: SanPlus, _error: SanError) {}
+    /// Called for each movetext token that is not recognized, with the
+    /// position in which it was found. See [`Visitor::invalid_token()`].
+    /// A replacement move is played like any other move.
+    fn invalid_token(
+        &mut self,
+        _pos: &VariantPosition,
+        _token: &[u8],
+        _kind: InvalidTokenKind,
+    ) -> TokenAction {
+        TokenAction::Skip
+    }
+    /// Called for each numeric annotation glyph like `!?` or `$7`.
+    fn nag(&mut self, _nag: Nag) {}
+    /// Called for each `{ comment }`.
+    fn comment(&mut self, _comment: RawComment<'_>) {}
+    /// See [`Visitor::begin_long_comment()`].
+    fn begin_long_comment(&mut self) {}
+    /// See [`Visitor::partial_comment()`].
+    fn partial_comment(&mut self, _chunk: RawComment<'_>) {}
+    /// See [`Visitor::end_long_comment()`].
+    fn end_long_comment(&mut self) {}
+    /// See [`Visitor::line_comment()`].
+    fn line_comment(&mut self, _comment: RawComment<'_>) {}
+    /// See [`Visitor::escape_line()`].
+    fn escape_line(&mut self, _line: &[u8]) {}
+    /// Called for each `(`, with the position from which the variation
+    /// starts. May skip over the following variation directly to
+    /// [`end_variation()`](trait.PositionVisitor.html#method.end_variation).
+    ///
+    /// Variations that do not follow a move are skipped by the adapter and
+    /// not reported at all.
+    fn begin_variation(&mut self, _pos: &VariantPosition) -> Skip {
+        Skip(false)
+    }
+    /// Called for each `)` that matches a reported `(`. The position is
+    /// restored to where it was before the variation.
+    fn end_variation(&mut self) {}
+    /// Called for each game termination, like `*` or `1-0`.
+    fn outcome(&mut self, _outcome: Option<Outcome>) {}
+
+    /// Called after parsing a game. Can produce a custom result.
+    fn end_game(&mut self) -> Self::Result;
+}
+
+/// Number of half-moves played to reach `pos`, respecting its move number.
+/// 0 for the standard starting position.
+pub(crate) fn ply(pos: &VariantPosition) -> u32 {
+    (pos.fullmoves().get() - 1)
+        .saturating_mul(2)
+        .saturating_add(pos.turn().fold_wb(0, 1))
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    pos: VariantPosition,
+    // Position before the last move in this line, where the next variation
+    // starts.
+    before: Option<VariantPosition>,
+    // Set after an illegal move. The rest of the line can not be followed.
+    broken: bool,
+}
+
+impl Frame {
+    fn new(pos: VariantPosition) -> Frame {
+        Frame {
+            pos,
+            before: None,
+            broken: false,
+        }
+    }
+}
+
+/// A [`Visitor`] that tracks the current position and drives a
+/// [`PositionVisitor`].
+///
+/// Games start from the standard position of the variant selected by the
+/// `Variant` tag (see [`VariantTag::from_ascii()`]), or from the `FEN` tag
+/// (unless `SetUp` is `0`). Each variation starts from the position before
+/// the preceding move, and the position is restored at the end of the
+/// variation.
+///
+/// Positions use [`CastlingMode::Chess960`] for Chess960 games, and for
+/// `FEN` tags with castling rights that are only valid in Chess960. The
+/// mode is available as `pos.castles().mode()`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, PositionAdapter, PositionVisitor, SanPlus};
+/// use shakmaty::{variant::VariantPosition, Move, Position};
+///
+/// struct Captures {
+///     captures: usize,
+/// }
+///
+/// impl PositionVisitor for Captures {
+///     type Result = usize;
+///
+///     fn play(&mut self, _: &VariantPosition, _: SanPlus, m: Move, _: &VariantPosition) {
+///         if m.is_capture() {
+///             self.captures += 1;
+///         }
+///     }
+///
+///     fn end_game(&mut self) -> usize {
+///         std::mem::take(&mut self.captures)
+///     }
+/// }
+///
+/// let pgn = b"1. e4 d5 2. exd5 Qxd5 (2... Nf6 3. d4 Nxd5) 3. Nc3 *";
+///
+/// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let mut adapter = PositionAdapter::new(Captures { captures: 0 });
+/// assert_eq!(reader.read_game(&mut adapter)?, Some(3));
+/// # Ok::<_, io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct PositionAdapter<V> {
+    visitor: V,
+    fen: Option<Vec<u8>>,
+    setup: bool,
+    variant: Option<VariantTag>,
+    stack: Vec<Frame>,
+    skipped_variation: bool,
+}
+
+impl<V: PositionVisitor> PositionAdapter<V> {
+    /// Wraps a [`PositionVisitor`].
+    pub fn new(visitor: V) -> PositionAdapter<V> {
+        PositionAdapter {
+            visitor,
+            fen: None,
+            setup: true,
+            variant: Some(VariantTag::default()),
+            stack: Vec::new(),
+            skipped_variation: false,
+        }
+    }
+
+    /// Gets a reference to the underlying visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.visitor
+    }
+
+    /// Gets a mutable reference to the underlying visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.visitor
+    }
+
+    /// Unwraps the underlying visitor.
+    pub fn into_inner(self) -> V {
+        self.visitor
+    }
+
+    fn start_position(&self) -> Result<VariantPosition, SetupError> {
+        let VariantTag {
+            variant,
+            castling_mode,
+        } = self.variant.ok_or(SetupError::UnknownVariant)?;
+
+        let setup = match self.fen {
+            Some(ref fen) if self.setup => Fen::from_ascii(fen)
+                .map_err(SetupError::InvalidFen)?
+                .into_setup(),
+            _ if castling_mode == CastlingMode::Standard => {
+                return Ok(VariantPosition::new(variant));
+            }
+            _ => VariantPosition::new(variant).to_setup(EnPassantMode::Legal),
+        };
+
+        match VariantPosition::from_setup(variant, setup.clone(), castling_mode) {
+            Err(_) if castling_mode == CastlingMode::Standard => {
+                // Tolerate Chess960 castling rights in games that are not
+                // tagged as Chess960.
+                VariantPosition::from_setup(variant, setup, CastlingMode::Chess960)
+            }
+            res => res,
+        }
+        .map_err(|err| SetupError::IllegalFen(Box::new(err)))
+    }
+}
+
+impl<V: PositionVisitor> Visitor for PositionAdapter<V> {
+    type Result = V::Result;
+
+    fn begin_tags(&mut self) {
+        self.fen = None;
+        self.setup = true;
+        self.variant = Some(VariantTag::default());
+        self.stack.clear();
+        self.skipped_variation = false;
+        self.visitor.begin_tags();
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        if name == b"FEN" {
+            self.fen = Some(value.as_bytes().to_owned());
+        } else if name == b"SetUp" {
+            self.setup = value.as_bytes() != b"0";
+        } else if name == b"Variant" {
+            self.variant = VariantTag::from_ascii(value.as_bytes());
+        }
+        self.visitor.tag(name, value);
+    }
+
+    fn begin_movetext(&mut self) -> Skip {
+        match self.start_position() {
+            Ok(pos) => {
+                let skip = self.visitor.begin_movetext(&pos);
+                self.stack.push(Frame::new(pos));
+                skip
+            }
+            Err(err) => {
+                self.visitor.setup_error(err);
+                Skip(true)
+            }
+        }
+    }
+
+    fn move_number(&mut self, move_number: MoveNumber) {
+        if let Some(frame) = self.stack.last().filter(|frame| !frame.broken) {
+            self.visitor.move_number(&frame.pos, move_number);
+        }
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        let Some(frame) = self.stack.last_mut() else {
+            return;
+        };
+        if frame.broken {
+            return;
+        }
+
+        match san_plus.san.to_move(&frame.pos) {
+            Ok(m) => {
+                let before = frame.pos.clone();
+                frame.pos.play_unchecked(m);
+                self.visitor.play(&before, san_plus, m, &frame.pos);
+                frame.before = Some(before);
+            }
+            Err(error) => {
+                self.visitor.illegal_move(&frame.pos, san_plus, error);
+                frame.before = Some(frame.pos.clone());
+                frame.broken = true;
+            }
+        }
+    }
+
+    fn invalid_token(&mut self, token: &[u8], kind: InvalidTokenKind) -> TokenAction {
+        match self.stack.last() {
+            Some(frame) if !frame.broken => self.visitor.invalid_token(&frame.pos, token, kind),
+            _ => TokenAction::Skip,
+        }
+    }
+
+    fn nag(&mut self, nag: Nag) {
+        self.visitor.nag(nag);
+    }
+
+    fn comment(&mut self, comment: RawComment<'_>) {
+        self.visitor.comment(comment);
+    }
+
+    fn begin_long_comment(&mut self) {
+        self.visitor.begin_long_comment();
+    }
+
+    fn partial_comment(&mut self, chunk: RawComment<'_>) {
+        self.visitor.partial_comment(chunk);
+    }
+
+    fn end_long_comment(&mut self) {
+        self.visitor.end_long_comment();
+    }
+
+    fn line_comment(&mut self, comment: RawComment<'_>) {
+        self.visitor.line_comment(comment);
+    }
+
+    fn escape_line(&mut self, line: &[u8]) {
+        self.visitor.escape_line(line);
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        match self.stack.last().and_then(|frame| frame.before.clone()) {
+            Some(pos) => {
+                let skip = self.visitor.begin_variation(&pos);
+                self.stack.push(Frame::new(pos));
+                skip
+            }
+            None => {
+                // No move to be replaced.
+                self.skipped_variation = true;
+                Skip(true)
+            }
+        }
+    }
+
+    fn end_variation(&mut self) {
+        if mem::take(&mut self.skipped_variation) {
+            return;
+        }
+        if self.stack.len() > 1 {
+            self.stack.pop();
+            self.visitor.end_variation();
+        }
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.visitor.outcome(outcome);
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        self.stack.clear();
+        self.visitor.end_game()
+    }
+}
+
+/// Implements [`Visitor`] for a wrapper with a [`PositionAdapter`] in its
+/// `inner` field, forwarding every callback to the adapter.
+macro_rules! forward_visitor {
+    ($wrapper:ty, $result:ty) => {
+        impl $crate::visitor::Visitor for $wrapper {
+            type Result = $result;
+
+            fn begin_tags(&mut self) {
+                self.inner.begin_tags();
+            }
+
+            fn tag(&mut self, name: &[u8], value: $crate::types::RawTag<'_>) {
+                self.inner.tag(name, value);
+            }
+
+            fn begin_movetext(&mut self) -> $crate::types::Skip {
+                self.inner.begin_movetext()
+            }
+
+            fn move_number(&mut self, move_number: $crate::types::MoveNumber) {
+                self.inner.move_number(move_number);
+            }
+
+            fn san(&mut self, san_plus: ::shakmaty::san::SanPlus) {
+                self.inner.san(san_plus);
+            }
+
+            fn invalid_token(
+                &mut self,
+                token: &[u8],
+                kind: $crate::types::InvalidTokenKind,
+            ) -> $crate::types::TokenAction {
+                self.inner.invalid_token(token, kind)
+            }
+
+            fn nag(&mut self, nag: $crate::types::Nag) {
+                self.inner.nag(nag);
+            }
+
+            fn comment(&mut self, comment: $crate::types::RawComment<'_>) {
+                self.inner.comment(comment);
+            }
+
+            fn begin_long_comment(&mut self) {
+                self.inner.begin_long_comment();
+            }
+
+            fn partial_comment(&mut self, chunk: $crate::types::RawComment<'_>) {
+                self.inner.partial_comment(chunk);
+            }
+
+            fn end_long_comment(&mut self) {
+                self.inner.end_long_comment();
+            }
+
+            fn line_comment(&mut self, comment: $crate::types::RawComment<'_>) {
+                self.inner.line_comment(comment);
+            }
+
+            fn escape_line(&mut self, line: &[u8]) {
+                self.inner.escape_line(line);
+            }
+
+            fn begin_variation(&mut self) -> $crate::types::Skip {
+                self.inner.begin_variation()
+            }
+
+            fn end_variation(&mut self) {
+                self.inner.end_variation();
+            }
+
+            fn outcome(&mut self, outcome: Option<::shakmaty::Outcome>) {
+                self.inner.outcome(outcome);
+            }
+
+            fn end_game(&mut self) -> Self::Result {
+                self.inner.end_game()
+            }
+        }
+    };
+}
+
+pub(crate) use forward_visitor;
+
+/// Reads all games with `visitor` and collects the results.
+#[cfg(test)]
+pub(crate) fn collect_hits<V, T>(visitor: &mut V, pgn: &[u8]) -> Vec<T>
+where
+    V: Visitor<Result = Vec<T>>,
+{
+    crate::BufferedReader::new(std::io::Cursor::new(pgn))
+        .into_iter(visitor)
+        .flat_map(|hits| hits.unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use shakmaty::{uci::UciMove, EnPassantMode};
+
+    use super::*;
+    use crate::BufferedReader;
+
+    #[derive(Default)]
+    struct Events {
+        events: Vec<String>,
+    }
+
+    impl PositionVisitor for Events {
+        type Result = Vec<String>;
+
+        fn begin_movetext(&mut self, pos: &VariantPosition) -> Skip {
+            self.events.push(format!(
+                "start {}",
+                Fen::from_position(pos, EnPassantMode::Legal)
+            ));
+            Skip(false)
+        }
+
+        fn setup_error(&mut self, error: SetupError) {
+            self.events.push(format!("error {error}"));
+        }
+
+        fn play(
+            &mut self,
+            before: &VariantPosition,
+            san_plus: SanPlus,
+            m: Move,
+            after: &VariantPosition,
+        ) {
+            assert_eq!(before.turn(), !after.turn());
+            self.events.push(format!(
+                "{} {}",
+                san_plus,
+                UciMove::from_move(m, CastlingMode::Standard)
+            ));
+        }
+
+        fn illegal_move(&mut self, _pos: &VariantPosition, san_plus: SanPlus, _error: SanError) {
+            self.events.push(format!("illegal {san_plus}"));
+        }
+
+        fn begin_variation(&mut self, _pos: &VariantPosition) -> Skip {
+            self.events.push("(".to_owned());
+            Skip(false)
+        }
+
+        fn end_variation(&mut self) {
+            self.events.push(")".to_owned());
+        }
+
+        fn end_game(&mut self) -> Self::Result {
+            mem::take(&mut self.events)
+        }
+    }
+
+    fn events(pgn: &[u8]) -> Vec<String> {
+        BufferedReader::new(io::Cursor::new(pgn))
+            .read_game(&mut PositionAdapter::new(Events::default()))
+            .expect("read")
+            .expect("game")
+    }
+
+    #[test]
+    fn test_variations() {
+        assert_eq!(
+            events(b"1. e4 e5 (1... c5 2. Nf3 (2. Nc3 Nc6) 2... d6) ( ( ) ) 2. Nf3 Nf3 Nc6 (2... Nc6) *"),
+            [
+                "start rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "e4 e2e4",
+                "e5 e7e5",
+                "(",
+                "c5 c7c5",
+                "Nf3 g1f3",
+                "(",
+                "Nc3 b1c3",
+                "Nc6 b8c6",
+                ")",
+                "d6 d7d6",
+                ")",
+                "(",
+                ")",
+                "Nf3 g1f3",
+                "illegal Nf3",
+                "(",
+                "Nc6 b8c6",
+                ")",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_setup() {
+        assert_eq!(
+            events(b"[Variant \"Crazyhouse\"]\n[FEN \"4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1\"]\n\n1. Q@e2+ *"),
+            [
+                "start 4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1",
+                "Q@e2+ Q@e2",
+            ]
+        );
+        assert_eq!(
+            events(b"[Variant \"Horde\"]\n[SetUp \"0\"]\n[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n1. e5 *")[1],
+            "e5 e4e5"
+        );
+        assert_eq!(
+            events(b"[Variant \"Suicide Bughouse\"]\n\n1. e4 *"),
+            ["error unknown variant tag"]
+        );
+        assert_eq!(
+            events(b"[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n1. e4 *").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_castling_mode() {
+        struct Tags;
+
+        impl PositionVisitor for Tags {
+            type Result = ();
+
+            fn end_game(&mut self) {}
+        }
+
+        fn castling_mode(pgn: &[u8]) -> CastlingMode {
+            let mut adapter = PositionAdapter::new(Tags);
+            let mut reader = BufferedReader::new(io::Cursor::new(pgn));
+            reader.read_game(&mut adapter).expect("read").expect("game");
+            adapter
+                .start_position()
+                .expect("start position")
+                .castles()
+                .mode()
+        }
+
+        assert_eq!(castling_mode(b"1. e4 *"), CastlingMode::Standard);
+        assert_eq!(
+            castling_mode(b"[Variant \"FRC\"]\n\n1. e4 *"),
+            CastlingMode::Chess960
+        );
+        assert_eq!(
+            castling_mode(
+                b"[Variant \"From Position\"]\n[FEN \"rk5r/8/8/8/8/8/8/RK5R w KQkq - 0 1\"]\n\n*"
+            ),
+            CastlingMode::Chess960
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::{cmp::min, fmt, ops::Range, vec};
+
+use crate::{
+    error::SyntaxError,
+    machine::{Machine, Status},
+    reader::ReaderBuilder,
+    visitor::Visitor,
+};
+
+impl ReaderBuilder {
+    /// Creates a push parser with the configured settings.
+    pub fn build_push<V: Visitor>(&self, visitor: V) -> PushParser<V> {
+        PushParser {
+            visitor,
+            machine: Machine::new(self),
+            finished: false,
+            results: Vec::new(),
+        }
+    }
+}
+
+/// A push-based parser for PGNs that arrive in pieces, for example from a
+/// network connection or a decompression callback.
+///
+/// Feed the input with [`PushParser::feed()`] and signal the end with
+/// [`PushParser::finish()`]. The [`Visitor`] is called as soon as tokens
+/// are complete, no matter where the input is split. The results of
+/// completed games are returned from both methods.
+///
+/// Parses exactly like the [`BufferedReader`](crate::BufferedReader) with
+/// the same settings, except that long comments (see
+/// [`ReaderBuilder::stream_long_comments()`]) may be split into different
+/// pieces.
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::{PushParser, SanPlus, Visitor};
+///
+/// struct MoveCounter {
+///     moves: usize,
+/// }
+///
+/// impl Visitor for MoveCounter {
+///     type Result = usize;
+///
+///     fn san(&mut self, _san_plus: SanPlus) {
+///         self.moves += 1;
+///     }
+///
+///     fn end_game(&mut self) -> usize {
+///         std::mem::take(&mut self.moves)
+///     }
+/// }
+///
+/// let mut parser = PushParser::new(MoveCounter { moves: 0 });
+///
+/// assert_eq!(parser.feed(b"[Event \"?\"]\n\n1. e4 e5 2. N").count(), 0);
+/// assert_eq!(parser.get_ref().moves, 2);
+///
+/// let games: Vec<_> = parser.feed(b"f3 *\n\n[Ev").collect();
+/// assert_eq!(games, [Ok(3)]);
+///
+/// let games: Vec<_> = parser.feed(b"ent \"?\"]\n\n1. d4 *").collect();
+/// assert!(games.is_empty());
+///
+/// let games: Vec<_> = parser.finish().collect();
+/// assert_eq!(games, [Ok(1)]);
+/// ```
+pub struct PushParser<V: Visitor> {
+    visitor: V,
+    machine: Machine,
+    finished: bool,
+    results: Vec<Result<V::Result, SyntaxError>>,
+}
+
+impl<V: Visitor + fmt::Debug> fmt::Debug for PushParser<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushParser")
+            .field("visitor", &self.visitor)
+            .field("position", &self.position())
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<V: Visitor> PushParser<V> {
+    pub fn new(visitor: V) -> PushParser<V> {
+        ReaderBuilder::new().build_push(visitor)
+    }
+
+    /// Gets a reference to the visitor.
+    pub fn get_ref(&self) -> &V {
+        &self.visitor
+    }
+
+    /// Gets a mutable reference to the visitor.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.visitor
+    }
+
+    /// Unwraps the visitor.
+    pub fn into_inner(self) -> V {
+        self.visitor
+    }
+
+    /// Absolute byte offset of the next byte that has not been processed,
+    /// counted from the start of the input.
+    pub fn position(&self) -> u64 {
+        self.machine.position()
+    }
+
+    /// The byte range of the last game that was completed. See
+    /// [`BufferedReader::last_game_span()`](crate::BufferedReader::last_game_span).
+    pub fn last_game_span(&self) -> Option<Range<u64>> {
+        self.machine.last_game_span()
+    }
+
+    /// Parses the next piece of the input, and returns the results of the
+    /// games that were completed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called after [`PushParser::finish()`].
+    pub fn feed(&mut self, mut data: &[u8]) -> vec::Drain<'_, Result<V::Result, SyntaxError>> {
+        assert!(!self.finished, "feed after finish");
+
+        while !data.is_empty() {
+            let spare = self.machine.buffer_mut().spare();
+            let n = min(spare.len(), data.len());
+            spare[..n].copy_from_slice(&data[..n]);
+            self.machine.buffer_mut().fill(n);
+            data = &data[n..];
+            self.run();
+        }
+
+        self.results.drain(..)
+    }
+
+    /// Signals the end of the input, and returns the results of the
+    /// remaining games.
+    pub fn finish(&mut self) -> vec::Drain<'_, Result<V::Result, SyntaxError>> {
+        self.finished = true;
+        self.run();
+        self.results.drain(..)
+    }
+
+    /// Makes progress until more input is needed.
+    fn run(&mut self) {
+        loop {
+            match self.machine.advance(self.finished) {
+                Status::Event => {
+                    if let Some(result) = self.machine.visit(&mut self.visitor) {
+                        self.results.push(Ok(result));
+                    }
+                }
+                Status::Error(err) => self.results.push(Err(err)),
+                Status::NeedMore | Status::Done => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{game::GameBuilder, reader::BufferedReader};
+
+    fn sync_results(builder: &ReaderBuilder, data: &[u8]) -> Vec<String> {
+        let mut reader = builder.build(data);
+        let mut game_builder = GameBuilder::new();
+        let mut results = Vec::new();
+        loop {
+            match reader.read_game(&mut game_builder) {
+                Ok(None) => break results,
+                Ok(Some(game)) => results.push(format!("{game:?}")),
+                Err(err) => results.push(format!("{:?}", err)),
+            }
+        }
+    }
+
+    fn push_results(builder: &ReaderBuilder, data: &[u8], piece: usize) -> Vec<String> {
+        let mut parser = builder.build_push(GameBuilder::new());
+        let mut results = Vec::new();
+        let mut collect = |result: Result<_, SyntaxError>| match result {
+            Ok(game) => results.push(format!("{game:?}")),
+            Err(err) => results.push(format!("{:?}", crate::Error::Syntax(err))),
+        };
+        for chunk in data.chunks(piece) {
+            parser.feed(chunk).for_each(&mut collect);
+        }
+        parser.finish().for_each(&mut collect);
+        results
+    }
+
+    #[test]
+    fn test_same_as_buffered_reader() {
+        let mut small = BufferedReader::builder();
+        small
+            .buffer_capacity(64)
+            .max_tag_line_length(40)
+            .max_comment_length(30);
+        let mut streaming = small;
+        streaming.stream_long_comments(true);
+
+        let mut inputs: Vec<Vec<u8>> = fs::read_dir("fuzz/corpus/pgn")
+            .unwrap()
+            .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+            .collect();
+        for edge in [
+            &b""[..],
+            b"\xef\xbb",
+            b"\xef\xbb\xbf1. e4",
+            b"[Event \"?\"]\n[Site \"unterminated",
+            b"[Event \"?\"]\n\n1. e4 { unterminated",
+            b"1. e4 (1. d4 { (} ; )\n% )\n e5) (\n\r\n[Event",
+            b"1. 0-0-0+ 0-0 1/2-1/2\n\r\n\r\n1. e4 1-0 0-1 *",
+            b"% escape\n[A \"1\"] % x\n[B \"2\"]]]\n[C\n\n1. $12 !! ?! e4!? e5",
+            b"1. Nf3 ; line comment\n\n[Event \"?\"]\n\n1. e4",
+        ] {
+            inputs.push(edge.to_vec());
+        }
+
+        for data in &inputs {
+            for builder in [&ReaderBuilder::new(), &small, &streaming] {
+                let expected = sync_results(builder, data);
+                for piece in [1, 2, 3, 5, 64, 1000, usize::MAX] {
+                    assert_eq!(
+                        push_results(builder, data, piece),
+                        expected,
+                        "piece size {piece}, input {:?}",
+                        String::from_utf8_lossy(&data[..min(data.len(), 100)])
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use std::{cmp::Ordering, error, fmt, str::FromStr};
+
+use crate::{
+    header::{Header, HeaderFilter},
+    tags::Date,
+    types::RawTag,
+    visitor::Visitor,
+};
+
+/// Error when parsing a [`TagQuery`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct InvalidQuery {
+    position: usize,
+    reason: String,
+}
+
+impl InvalidQuery {
+    fn new(position: usize, reason: impl Into<String>) -> InvalidQuery {
+        InvalidQuery {
+            position,
+            reason: reason.into(),
+        }
+    }
+
+    /// The byte offset in the query where the error was found.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for InvalidQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+impl error::Error for InvalidQuery {}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(i64),
+    Date(Date),
+    Text(Vec<u8>),
+}
+
+impl Value {
+    fn compare(&self, tag: RawTag<'_>) -> Option<Ordering> {
+        Some(match self {
+            Value::Number(number) => btoi::btoi::<i64>(tag.as_bytes()).ok()?.cmp(number),
+            Value::Date(date) => tag.date().ok()?.cmp(date),
+            Value::Text(text) => tag.decode().as_ref().cmp(text),
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    Regex,
+}
+
+impl Op {
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+            Op::Contains | Op::Regex => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(Vec<u8>),
+    Compare {
+        name: Vec<u8>,
+        op: Op,
+        value: Value,
+    },
+    Contains {
+        name: Vec<u8>,
+        needle: Vec<u8>,
+    },
+    #[cfg(feature = "regex")]
+    Regex {
+        name: Vec<u8>,
+        regex: regex::bytes::Regex,
+    },
+}
+
+impl Expr {
+    fn matches(&self, header: &Header) -> bool {
+        match self {
+            Expr::Or(left, right) => left.matches(header) || right.matches(header),
+            Expr::And(left, right) => left.matches(header) && right.matches(header),
+            Expr::Not(expr) => !expr.matches(header),
+            Expr::Exists(name) => header.tag(name).is_some(),
+            Expr::Compare { name, op, value } => header
+                .tags_named(name)
+                .any(|tag| value.compare(tag).is_some_and(|ordering| op.test(ordering))),
+            Expr::Contains { name, needle } => header
+                .tags_named(name)
+                .any(|tag| memchr::memmem::find(&tag.decode(), needle).is_some()),
+            #[cfg(feature = "regex")]
+            Expr::Regex { name, regex } => header
+                .tags_named(name)
+                .any(|tag| regex.is_match(&tag.decode())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    /// A tag name, or an unquoted value.
+    Word(Vec<u8>),
+    /// A quoted string, with escapes resolved.
+    Quoted(Vec<u8>),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn is_word(ch: u8) -> bool {
+    !ch.is_ascii_whitespace() && !b"()&|!<>=~\"".contains(&ch)
+}
+
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, InvalidQuery> {
+    let s = query.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < s.len() {
+        let start = pos;
+        let rest = &s[pos..];
+        let (token, len) = match rest {
+            [ch, ..] if ch.is_ascii_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            [b'&', b'&', ..] => (Token::And, 2),
+            [b'|', b'|', ..] => (Token::Or, 2),
+            [b'!', b'=', ..] => (Token::Op(Op::Ne), 2),
+            [b'!', ..] => (Token::Not, 1),
+            [b'(', ..] => (Token::Open, 1),
+            [b')', ..] => (Token::Close, 1),
+            [b'<', b'=', ..] => (Token::Op(Op::Le), 2),
+            [b'<', ..] => (Token::Op(Op::Lt), 1),
+            [b'>', b'=', ..] => (Token::Op(Op::Ge), 2),
+            [b'>', ..] => (Token::Op(Op::Gt), 1),
+            [b'=', b'~', ..] => (Token::Op(Op::Regex), 2),
+            [b'=', b'=', ..] => (Token::Op(Op::Eq), 2),
+            [b'=', ..] => (Token::Op(Op::Eq), 1),
+            [b'~', ..] => (Token::Op(Op::Contains), 1),
+            [b'"', ..] => {
+                let mut text = Vec::new();
+                let mut end = 1;
+                loop {
+                    match rest.get(end) {
+                        Some(b'"') => break,
+                        Some(b'\\') if matches!(rest.get(end + 1), Some(b'"' | b'\\')) => {
+                            text.push(rest[end + 1]);
+                            end += 2;
+                        }
+                        Some(&ch) => {
+                            text.push(ch);
+                            end += 1;
+                        }
+                        None => return Err(InvalidQuery::new(start, "unterminated string")),
+                    }
+                }
+                (Token::Quoted(text), end + 1)
+            }
+            [ch, ..] if is_word(*ch) => {
+                let len = rest
+                    .iter()
+                    .position(|&ch| !is_word(ch))
+                    .unwrap_or(rest.len());
+                (Token::Word(rest[..len].to_vec()), len)
+            }
+            _ => return Err(InvalidQuery::new(start, "unexpected character")),
+        };
+        tokens.push((start, token));
+        pos += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map_or(self.end, |&(pos, _)| pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).map(|(_, token)| token.clone());
+        self.index += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, InvalidQuery> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, InvalidQuery> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.index += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, InvalidQuery> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(InvalidQuery::new(position, "unclosed parenthesis")),
+                }
+            }
+            Some(Token::Word(name)) => self.comparison(name),
+            _ => Err(InvalidQuery::new(position, "expected tag name")),
+        }
+    }
+
+    fn comparison(&mut self, name: Vec<u8>) -> Result<Expr, InvalidQuery> {
+        let Some(&Token::Op(op)) = self.peek() else {
+            return Ok(Expr::Exists(name));
+        };
+        self.index += 1;
+
+        let position = self.position();
+        let (text, quoted) = match self.next() {
+            Some(Token::Word(word)) => (word, false),
+            Some(Token::Quoted(text)) => (text, true),
+            _ => return Err(InvalidQuery::new(position, "expected value")),
+        };
+
+        Ok(match op {
+            Op::Contains => Expr::Contains { name, needle: text },
+            #[cfg(feature = "regex")]
+            Op::Regex => {
+                let regex = std::str::from_utf8(&text)
+                    .ok()
+                    .and_then(|pattern| regex::bytes::Regex::new(pattern).ok())
+                    .ok_or_else(|| InvalidQuery::new(position, "invalid regex"))?;
+                Expr::Regex { name, regex }
+            }
+            #[cfg(not(feature = "regex"))]
+            Op::Regex => {
+                return Err(InvalidQuery::new(
+                    position,
+                    "regex matches require the regex feature",
+                ))
+            }
+            _ => {
+                let value = if quoted {
+                    Value::Text(text)
+                } else if let Ok(number) = btoi::btoi(&text) {
+                    Value::Number(number)
+                } else if let Ok(date) = RawTag(&text).date() {
+                    Value::Date(date)
+                } else {
+                    Value::Text(text)
+                };
+                Expr::Compare { name, op, value }
+            }
+        })
+    }
+}
+
+/// A compiled filter expression over the tags of a game.
+///
+/// # Syntax
+///
+/// * `Name` matches if the tag exists.
+/// * `Name=value`, `Name!=value`, `Name<value`, `Name<=value`,
+///   `Name>value` and `Name>=value` compare tag values. Unquoted integers
+///   like `2400` compare numerically, unquoted dates like `2022.01.01` or
+///   `2022.??.??` compare as dates (with unknown parts ordered first), and
+///   everything else, including quoted strings, compares byte by byte.
+///   `==` is the same as `=`.
+/// * `Name~"text"` matches if the value contains the given text.
+/// * `Name=~"regex"` matches if the value matches the given regular
+///   expression. Only with the `regex` feature.
+/// * `!`, `&&`, `||` and parentheses combine expressions, in order of
+///   precedence.
+///
+/// Values are decoded before comparing, and quoted strings may contain `\"`
+/// and `\\` escapes. A comparison matches if any tag with the given name
+/// matches. Missing tags, and values that can not be parsed as the type of
+/// the comparison, never match.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, HeaderVisitor, TagQuery};
+///
+/// let query: TagQuery = r#"WhiteElo>=2400 && Event~"Titled Arena" && Date>=2022.01.01"#.parse()?;
+///
+/// let pgn = b"[Event \"Titled Arena Jan '22\"]\n[Date \"2022.01.07\"]\n[WhiteElo \"2512\"]\n\n1. e4 *";
+///
+/// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let header = reader.read_game(&mut HeaderVisitor::new()).unwrap().expect("game");
+/// assert!(query.matches(&header));
+/// # Ok::<_, pgn_reader::InvalidQuery>(())
+/// ```
+///
+/// Use [`TagQuery::filter()`] to skip the movetext of games that do not
+/// match.
+#[derive(Debug, Clone)]
+pub struct TagQuery {
+    expr: Expr,
+}
+
+impl TagQuery {
+    /// Compiles a query.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the query is not syntactically valid, or if a regular
+    /// expression is invalid.
+    pub fn new(query: &str) -> Result<TagQuery, InvalidQuery> {
+        let mut parser = Parser {
+            tokens: tokenize(query)?,
+            index: 0,
+            end: query.len(),
+        };
+        let expr = parser.or()?;
+        if parser.index < parser.tokens.len() {
+            return Err(InvalidQuery::new(parser.position(), "unexpected token"));
+        }
+        Ok(TagQuery { expr })
+    }
+
+    /// Tests if the tags of a game match the query.
+    pub fn matches(&self, header: &Header) -> bool {
+        self.expr.matches(header)
+    }
+
+    /// Creates a visitor that passes only games matching the query on to
+    /// `visitor`, and skips over the movetext of all other games. See
+    /// [`HeaderFilter`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io;
+    /// use pgn_reader::{BufferedReader, GameBuilder, TagQuery};
+    ///
+    /// let pgn = b"[Result \"1-0\"]\n\n1. e4 1-0\n\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1";
+    ///
+    /// let mut filter = TagQuery::new("Result=0-1")?.filter(GameBuilder::new());
+    /// let reader = BufferedReader::new(io::Cursor::new(&pgn));
+    /// let games: Vec<_> = reader
+    ///     .into_iter(&mut filter)
+    ///     .filter_map(|game| game.expect("read").1)
+    ///     .collect();
+    ///
+    /// assert_eq!(games.len(), 1);
+    /// assert_eq!(games[0].root.mainline().count(), 4);
+    /// # Ok::<_, pgn_reader::InvalidQuery>(())
+    /// ```
+    pub fn filter<V: Visitor>(self, visitor: V) -> HeaderFilter<impl FnMut(&Header) -> bool, V> {
+        HeaderFilter::new(move |header: &Header| self.matches(header), visitor)
+    }
+}
+
+impl FromStr for TagQuery {
+    type Err = InvalidQuery;
+
+    fn from_str(s: &str) -> Result<TagQuery, InvalidQuery> {
+        TagQuery::new(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(tags: &[(&str, &str)]) -> Header {
+        let mut header = Header::new();
+        for (name, value) in tags {
+            header.push(name.as_bytes(), RawTag(value.as_bytes()));
+        }
+        header
+    }
+
+    fn matches(query: &str, header: &Header) -> bool {
+        TagQuery::new(query).unwrap().matches(header)
+    }
+
+    #[test]
+    fn test_matches() {
+        let game = header(&[
+            ("Event", "Rated Blitz game"),
+            ("Date", "2023.04.??"),
+            ("White", "Magnus \\\"DrNykterstein\\\""),
+            ("WhiteElo", "3200"),
+            ("BlackElo", "?"),
+            ("Result", "1/2-1/2"),
+            ("Annotator", "A"),
+            ("Annotator", "B"),
+        ]);
+
+        assert!(matches("WhiteElo>3000", &game));
+        assert!(matches("WhiteElo = 3200 && !(WhiteElo != 3200)", &game));
+        assert!(!matches("BlackElo<3000", &game));
+        assert!(!matches("BlackElo>=0", &game));
+        assert!(matches("BlackElo=\"?\"", &game));
+        assert!(matches("Date>=2023.01.01 && Date<2023.05.01", &game));
+        assert!(matches("Date>=2023.04.??", &game));
+        assert!(matches("Result=1/2-1/2", &game));
+        assert!(matches(r#"White~"\"DrNyk""#, &game));
+        assert!(matches("Event~Blitz || Event~Bullet", &game));
+        assert!(!matches("Event~Blitz && Event~Bullet", &game));
+        assert!(matches("Annotator=B", &game));
+        assert!(matches("Annotator && !Round", &game));
+        assert!(matches("Round || Event~Rated && WhiteElo>3000", &game));
+        assert!(!matches("(Round || Event~Rated) && WhiteElo<3000", &game));
+    }
+
+    #[test]
+    fn test_errors() {
+        for (query, position) in [
+            ("WhiteElo>", 9),
+            ("(WhiteElo>2400", 0),
+            ("WhiteElo>2400 &&", 16),
+            ("Event~\"Titled", 6),
+            ("WhiteElo>2400 Event", 14),
+            ("&& Event", 0),
+        ] {
+            assert_eq!(
+                TagQuery::new(query).unwrap_err().position(),
+                position,
+                "{query}"
+            );
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex() {
+        let game = header(&[("Event", "Titled Arena Jan '22")]);
+        assert!(matches(r#"Event=~"^Titled (Arena|Tuesday)""#, &game));
+        assert!(!matches(r#"Event=~"^Arena""#, &game));
+        assert_eq!(TagQuery::new(r#"Event=~"(""#).unwrap_err().position(), 7);
+    }
+}
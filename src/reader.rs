@@ -1,36 +1,177 @@
 use std::{
-    cmp::min,
+    cmp::{max, min},
     io::{self, Chain, Cursor, Read},
-};
-
-use shakmaty::{
-    san::{San, SanPlus, Suffix},
-    CastlingSide, Color, Outcome,
+    ops::Range,
 };
 
 // use slice_deque::SliceDeque;
 use crate::{
     buffer::Buffer,
-    types::{Nag, RawComment, RawTag, Skip},
+    error::{Error, SyntaxError, SyntaxErrorKind},
+    lexer::{self, SkipToken, TagToken, Token},
+    types::{RawComment, RawTag, Skip, TokenAction},
     visitor::{SkipVisitor, Visitor},
 };
 
+const DEFAULT_BUFFER_CAPACITY: usize = 1 << 14;
+const DEFAULT_MAX_TAG_LINE_LENGTH: usize = 1024;
+const DEFAULT_MAX_COMMENT_LENGTH: usize = 4096;
+/// Upper bound for the length limits, which determine the buffer size.
+const MAX_LENGTH_LIMIT: usize = 64 << 20;
+
+/// Configures and creates a [`BufferedReader`].
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, Visitor, RawComment};
+///
+/// struct LongestComment(usize);
+///
+/// impl Visitor for LongestComment {
+///     type Result = usize;
+///
+///     fn comment(&mut self, comment: RawComment<'_>) {
+///         self.0 = self.0.max(comment.as_bytes().len());
+///     }
+///
+///     fn end_game(&mut self) -> usize {
+///         self.0
+///     }
+/// }
+///
+/// let pgn = format!("1. e4 {{ {} }} *", "very long analysis ".repeat(1000));
+///
+/// let mut reader = BufferedReader::builder()
+///     .max_comment_length(1 << 16)
+///     .build(io::Cursor::new(pgn));
+///
+/// let longest = reader.read_game(&mut LongestComment(0))?;
+/// assert_eq!(longest, Some(19002));
+/// # Ok::<_, io::Error>(())
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct ReaderBuilder {
+    buffer_capacity: usize,
+    max_tag_line_length: usize,
+    pub(crate) max_comment_length: usize,
+    pub(crate) stream_long_comments: bool,
+}
+
+impl Default for ReaderBuilder {
+    fn default() -> ReaderBuilder {
+        ReaderBuilder::new()
+    }
+}
+
+impl ReaderBuilder {
+    /// Creates a builder with the default settings: a 16 KiB buffer,
+    /// tag lines of up to 1024 bytes, and comments of up to 4096 bytes.
+    pub fn new() -> ReaderBuilder {
+        ReaderBuilder {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            max_tag_line_length: DEFAULT_MAX_TAG_LINE_LENGTH,
+            max_comment_length: DEFAULT_MAX_COMMENT_LENGTH,
+            stream_long_comments: false,
+        }
+    }
+
+    /// Sets the capacity of the internal buffer in bytes. The capacity is
+    /// increased as required to hold the longest tag line or comment.
+    pub fn buffer_capacity(&mut self, capacity: usize) -> &mut ReaderBuilder {
+        self.buffer_capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum length of tag lines like `[Event "?"]` in bytes.
+    /// Longer tag lines are rejected with [`SyntaxErrorKind::TagTooLong`].
+    ///
+    /// The buffer must hold the longest tag line, so the length is clamped
+    /// to at most 64 MiB.
+    pub fn max_tag_line_length(&mut self, length: usize) -> &mut ReaderBuilder {
+        self.max_tag_line_length = min(length, MAX_LENGTH_LIMIT);
+        self
+    }
+
+    /// Sets the maximum length of `{ comments }` in bytes, excluding the
+    /// braces. Longer comments are rejected with [`SyntaxErrorKind::CommentTooLong`].
+    ///
+    /// The buffer must hold the longest comment, so the length is clamped
+    /// to at most 64 MiB. Use
+    /// [`stream_long_comments()`](ReaderBuilder::stream_long_comments) to
+    /// read comments of any length.
+    pub fn max_comment_length(&mut self, length: usize) -> &mut ReaderBuilder {
+        self.max_comment_length = min(length, MAX_LENGTH_LIMIT);
+        self
+    }
+
+    /// Enables streaming of comments that exceed the maximum comment length,
+    /// instead of rejecting them.
+    ///
+    /// Such comments are passed to the visitor in pieces, using
+    /// [`Visitor::begin_long_comment()`], [`Visitor::partial_comment()`]
+    /// and [`Visitor::end_long_comment()`]. This allows reading comments of
+    /// any length with a fixed-size buffer. Shorter comments are still
+    /// passed to [`Visitor::comment()`] at once.
+    pub fn stream_long_comments(&mut self, enabled: bool) -> &mut ReaderBuilder {
+        self.stream_long_comments = enabled;
+        self
+    }
+
+    /// Creates a reader with the configured settings.
+    pub fn build<R: Read>(&self, reader: R) -> BufferedReader<R> {
+        BufferedReader {
+            reader,
+            buffer: self.new_buffer(),
+            max_tag_line_length: self.effective_max_tag_line_length(),
+            max_comment_length: self.max_comment_length,
+            stream_long_comments: self.stream_long_comments,
+            games: 0,
+            last_game: None,
+            stopped: false,
+        }
+    }
+
+    pub(crate) fn effective_max_tag_line_length(&self) -> usize {
+        max(self.max_tag_line_length, 2)
+    }
+
+    pub(crate) fn new_buffer(&self) -> Buffer {
+        Buffer::with_capacity(max(
+            self.buffer_capacity,
+            max(
+                self.effective_max_tag_line_length(),
+                self.max_comment_length + 2,
+            ) + 1,
+        ))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BufferedReader<R> {
     reader: R,
     buffer: Buffer,
     max_tag_line_length: usize,
     max_comment_length: usize,
+    stream_long_comments: bool,
+    games: u64,
+    last_game: Option<Range<u64>>,
+    // Set when a `TryVisitor` stopped reading.
+    pub(crate) stopped: bool,
+}
+
+impl BufferedReader<()> {
+    /// Creates a [`ReaderBuilder`] to configure the buffer size and length
+    /// limits.
+    pub fn builder() -> ReaderBuilder {
+        ReaderBuilder::new()
+    }
 }
 
 impl<R: Read> BufferedReader<R> {
     pub fn new(reader: R) -> BufferedReader<R> {
-        BufferedReader {
-            reader,
-            buffer: Buffer::with_capacity(1 << 14),
-            max_tag_line_length: 1024,
-            max_comment_length: 4096,
-        }
+        ReaderBuilder::new().build(reader)
     }
 
     /// Converts a [`Read`] value along with the internal [`Buffer`] to a [`BufferedReader`].
@@ -38,6 +179,9 @@ impl<R: Read> BufferedReader<R> {
     /// Since [`Buffer`] is private, you can only use use this to create a [`BufferedReader`]
     /// from [`BufferedReader::into_inner`].
     ///
+    /// The length limits are clamped to what fits into the buffer, and a
+    /// buffer of fewer than 4 bytes is grown.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -68,15 +212,57 @@ impl<R: Read> BufferedReader<R> {
     ///
     /// assert_eq!(reader.read_game(&mut LastMove::default()).unwrap().unwrap(), Some(SanPlus::from_ascii(b"d4").unwrap()));
     /// ```
-    pub fn from_buffer(buffer: Buffer, reader: R) -> BufferedReader<R> {
+    pub fn from_buffer(mut buffer: Buffer, reader: R) -> BufferedReader<R> {
+        // Room for the lookahead of a byte order mark.
+        buffer.reserve(4);
+        let capacity = buffer.capacity();
         BufferedReader {
             reader,
             buffer,
-            max_tag_line_length: 1024,
-            max_comment_length: 4096,
+            max_tag_line_length: min(DEFAULT_MAX_TAG_LINE_LENGTH, capacity.saturating_sub(1)),
+            max_comment_length: min(DEFAULT_MAX_COMMENT_LENGTH, capacity.saturating_sub(3)),
+            stream_long_comments: false,
+            games: 0,
+            last_game: None,
+            stopped: false,
         }
     }
 
+    /// Creates a syntax error for the token that started `n` bytes before
+    /// the current position.
+    fn syntax_error(&self, kind: SyntaxErrorKind, n: usize) -> Error {
+        let location = self.buffer.location_before(n);
+        Error::Syntax(SyntaxError::new(
+            kind,
+            location.offset,
+            location.line,
+            location.column,
+            self.games.saturating_sub(1),
+        ))
+    }
+
+    /// Creates a syntax error for a tag that started directly before the
+    /// current position and was not closed within the buffered data.
+    fn tag_error(&self) -> Error {
+        // The buffer holds the complete line up to the limit, unless the end
+        // of the input was reached.
+        let kind = if self.buffer.data().len() + 1 >= self.max_tag_line_length {
+            SyntaxErrorKind::TagTooLong
+        } else {
+            SyntaxErrorKind::UnterminatedTag
+        };
+        self.syntax_error(kind, 1)
+    }
+
+    /// Skips over the remaining tags and movetext of the current game, to
+    /// recover from a syntax error.
+    fn skip_to_next_game(&mut self) -> io::Result<()> {
+        while let &[b'[' | b'%', ..] = self.buffer.ensure_bytes(1, &mut self.reader)? {
+            self.skip_line()?;
+        }
+        self.skip_movetext()
+    }
+
     fn skip_bom(&mut self) -> io::Result<()> {
         if self
             .buffer
@@ -106,33 +292,46 @@ impl<R: Read> BufferedReader<R> {
         Ok(())
     }
 
-    fn skip_whitespace(&mut self) -> io::Result<()> {
+    /// Passes the rest of the line to `f`, up to the maximum comment length,
+    /// and skips to the line break.
+    fn read_line(&mut self, f: impl FnOnce(&[u8])) -> io::Result<()> {
+        let data = self
+            .buffer
+            .ensure_bytes(self.max_comment_length + 1, &mut self.reader)?;
+        let end = memchr::memchr(b'\n', &data[..min(data.len(), self.max_comment_length + 1)])
+            .unwrap_or(min(data.len(), self.max_comment_length));
+        f(&data[..end]);
+        self.buffer.consume(end);
+        self.skip_until(b'\n')
+    }
+
+    fn read_escape_line<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
+        self.buffer.bump();
+        self.read_line(|line| visitor.escape_line(line))?;
+        self.buffer.bump();
+        Ok(())
+    }
+
+    fn skip_whitespace<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
         while let &[ch, ..] = self.buffer.ensure_bytes(1, &mut self.reader)? {
             match ch {
                 b' ' | b'\t' | b'\r' | b'\n' => {
                     self.buffer.bump();
                 }
-                b'%' => {
-                    self.buffer.bump();
-                    self.skip_line()?;
-                }
+                b'%' => self.read_escape_line(visitor)?,
                 _ => return Ok(()),
             }
         }
         Ok(())
     }
 
-    fn skip_ket(&mut self) -> io::Result<()> {
+    fn skip_ket<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
         while let &[ch, ..] = self.buffer.ensure_bytes(1, &mut self.reader)? {
             match ch {
                 b' ' | b'\t' | b'\r' | b']' => {
                     self.buffer.bump();
                 }
-                b'%' => {
-                    self.buffer.bump();
-                    self.skip_line()?;
-                    return Ok(());
-                }
+                b'%' => return self.read_escape_line(visitor),
                 b'\n' => {
                     self.buffer.bump();
                     return Ok(());
@@ -146,397 +345,255 @@ impl<R: Read> BufferedReader<R> {
         Ok(())
     }
 
-    fn read_tags<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
-        while let &[ch, ..] = self
+    fn read_tags<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Error> {
+        while !self
             .buffer
             .ensure_bytes(self.max_tag_line_length, &mut self.reader)?
+            .is_empty()
         {
-            match ch {
-                b'[' => {
-                    self.buffer.bump();
-
-                    let left_quote = match memchr::memchr3(b'"', b'\n', b']', self.buffer.data()) {
-                        Some(left_quote) if self.buffer.data()[left_quote] == b'"' => left_quote,
-                        Some(eol) => {
-                            self.buffer.consume(eol + 1);
-                            self.skip_ket()?;
-                            continue;
-                        }
-                        None => {
-                            self.buffer.discard_data();
-                            self.skip_line()?;
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "unterminated tag",
-                            ));
-                        }
-                    };
-
-                    let space = if left_quote > 0 && self.buffer.data()[left_quote - 1] == b' ' {
-                        left_quote - 1
-                    } else {
-                        left_quote
-                    };
-
-                    let value_start = left_quote + 1;
-                    let mut right_quote = value_start;
-                    let consumed = loop {
-                        match memchr::memchr3(
-                            b'\\',
-                            b'"',
-                            b'\n',
-                            &self.buffer.data()[right_quote..],
-                        ) {
-                            Some(delta) if self.buffer.data()[right_quote + delta] == b'"' => {
-                                right_quote += delta;
-                                break right_quote + 1;
-                            }
-                            Some(delta) if self.buffer.data()[right_quote + delta] == b'\n' => {
-                                right_quote += delta;
-                                break right_quote;
-                            }
-                            Some(delta) => {
-                                // Skip escaped character.
-                                right_quote =
-                                    min(right_quote + delta + 2, self.buffer.data().len());
-                            }
-                            None => {
-                                self.buffer.discard_data();
-                                self.skip_line()?;
-                                return Err(io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    "unterminated tag",
-                                ));
-                            }
-                        }
-                    };
-
-                    visitor.tag(
-                        &self.buffer.data()[..space],
-                        RawTag(&self.buffer.data()[value_start..right_quote]),
-                    );
+            match lexer::tag_token(self.buffer.data(), self.max_tag_line_length) {
+                (TagToken::Tag { name, value }, consumed) => {
+                    visitor.tag(name, RawTag(value));
                     self.buffer.consume(consumed);
-                    self.skip_ket()?;
+                    self.skip_ket(visitor)?;
                 }
-                b'%' => self.skip_line()?,
-                _ => return Ok(()),
+                (TagToken::Malformed, consumed) => {
+                    self.buffer.consume(consumed);
+                    self.skip_ket(visitor)?;
+                }
+                (TagToken::Unclosed, consumed) => {
+                    self.buffer.consume(consumed);
+                    let err = self.tag_error();
+                    let window = min(self.buffer.data().len(), self.max_tag_line_length - 1);
+                    self.buffer.consume(window);
+                    self.skip_line()?;
+                    self.skip_to_next_game()?;
+                    return Err(err);
+                }
+                (TagToken::Escape, _) => self.read_escape_line(visitor)?,
+                (TagToken::End, _) => return Ok(()),
             }
         }
         Ok(())
     }
 
     fn skip_movetext(&mut self) -> io::Result<()> {
-        while let &[ch, ..] = self.buffer.ensure_bytes(3, &mut self.reader)? {
-            self.buffer.bump();
-
-            match ch {
-                b'{' => {
+        while let data @ &[_, ..] = self.buffer.ensure_bytes(3, &mut self.reader)? {
+            let (token, consumed) = lexer::skip_token(data);
+            self.buffer.consume(consumed);
+            match token {
+                SkipToken::Comment => {
                     self.skip_until(b'}')?;
                     self.buffer.bump();
                 }
-                b';' => {
-                    self.skip_until(b'\n')?;
-                }
-                b'\n' => match self.buffer.peek() {
-                    Some(b'%') => self.skip_until(b'\n')?,
-                    Some(b'\n') | Some(b'[') => break,
-                    Some(b'\r') => {
-                        self.buffer.bump();
-                        if let Some(b'\n') = self.buffer.peek() {
-                            break;
-                        }
-                    }
-                    _ => continue,
-                },
-                _ => {
-                    if let Some(consumed) = memchr::memchr3(b'\n', b'{', b';', self.buffer.data()) {
-                        self.buffer.consume(consumed);
-                    } else {
-                        self.buffer.discard_data();
-                    }
-                }
+                SkipToken::LineComment => self.skip_until(b'\n')?,
+                SkipToken::End => break,
+                SkipToken::BeginVariation | SkipToken::EndVariation | SkipToken::Ignored => (),
             }
         }
 
         Ok(())
     }
 
-    fn find_token_end(&mut self, start: usize) -> usize {
-        let mut end = start;
-        for &ch in &self.buffer.data()[start..] {
-            match ch {
-                b' ' | b'\t' | b'\n' | b'\r' | b'{' | b'}' | b'(' | b')' | b'!' | b'?' | b'$'
-                | b';' | b'.' => break,
-                _ => end += 1,
-            }
-        }
-        end
-    }
-
-    fn read_movetext<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
-        while let &[ch, ..] = self
+    fn read_movetext<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+        skip_movetext: &impl Fn(&V) -> bool,
+    ) -> Result<(), Error> {
+        while let data @ &[_, ..] = self
             .buffer
-            .ensure_bytes(self.max_comment_length, &mut self.reader)?
+            .ensure_bytes(self.max_comment_length + 2, &mut self.reader)?
         {
-            match ch {
-                b'{' => {
-                    self.buffer.bump();
-
-                    let right_brace =
-                        if let Some(right_brace) = memchr::memchr(b'}', self.buffer.data()) {
-                            right_brace
-                        } else {
-                            self.buffer.discard_data();
-                            self.skip_until(b'}')?;
-                            self.buffer.bump();
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "unterminated comment",
-                            ));
-                        };
-
-                    visitor.comment(RawComment(&self.buffer.data()[..right_brace]));
-                    self.buffer.consume(right_brace + 1);
-                }
-                b'\n' => {
-                    self.buffer.bump();
+            if skip_movetext(visitor) {
+                self.skip_movetext()?;
+                break;
+            }
 
-                    match self.buffer.peek() {
-                        Some(b'%') => {
-                            self.buffer.bump();
-                            self.skip_line()?;
-                        }
-                        Some(b'[') | Some(b'\n') => {
-                            break;
-                        }
-                        Some(b'\r') => {
-                            self.buffer.bump();
-                            if self.buffer.peek() == Some(b'\n') {
-                                break;
-                            }
-                        }
-                        _ => continue,
+            let (token, consumed) = lexer::token(data, self.max_comment_length);
+            match token {
+                Token::MoveNumber(move_number) => visitor.move_number(move_number),
+                Token::San(san_plus) => visitor.san(san_plus),
+                Token::Invalid(kind) => match visitor.invalid_token(&data[..consumed], kind) {
+                    TokenAction::Skip => (),
+                    TokenAction::Abort => {
+                        let err = self.syntax_error(SyntaxErrorKind::InvalidToken, 0);
+                        self.buffer.consume(consumed);
+                        self.skip_movetext()?;
+                        return Err(err);
                     }
-                }
-                b';' => {
-                    self.buffer.bump();
-                    self.skip_until(b'\n')?;
-                }
-                b'0' => {
-                    self.buffer.bump();
-                    if self.buffer.data().starts_with(b"-1") {
-                        self.buffer.consume(2);
-                        visitor.outcome(Some(Outcome::Decisive {
-                            winner: Color::Black,
-                        }));
-                    } else if self.buffer.data().starts_with(b"-0") {
-                        // Castling notation with zeros.
-                        self.buffer.consume(2);
-                        let side = if self.buffer.data().starts_with(b"-0") {
-                            self.buffer.consume(2);
-                            CastlingSide::QueenSide
-                        } else {
-                            CastlingSide::KingSide
-                        };
-                        let suffix = match self.buffer.peek() {
-                            Some(b'+') => Some(Suffix::Check),
-                            Some(b'#') => Some(Suffix::Checkmate),
-                            _ => None,
-                        };
-                        visitor.san(SanPlus {
-                            san: San::Castle(side),
-                            suffix,
-                        });
-                    } else {
-                        let token_end = self.find_token_end(0);
-                        self.buffer.consume(token_end);
+                    TokenAction::Replace(san_plus) => visitor.san(san_plus),
+                },
+                Token::Nag(nag) => visitor.nag(nag),
+                Token::Comment(comment) => visitor.comment(RawComment(comment)),
+                Token::UnclosedComment => {
+                    self.buffer.consume(consumed);
+
+                    // The buffer holds the complete comment up to the limit,
+                    // unless the end of the input was reached.
+                    let window = min(self.buffer.data().len(), self.max_comment_length + 1);
+                    if window > self.max_comment_length && self.stream_long_comments {
+                        self.read_long_comment(visitor)?;
+                        continue;
                     }
-                }
-                b'1' => {
-                    self.buffer.bump();
-                    if self.buffer.data().starts_with(b"-0") {
-                        self.buffer.consume(2);
-                        visitor.outcome(Some(Outcome::Decisive {
-                            winner: Color::White,
-                        }));
-                    } else if self.buffer.data().starts_with(b"/2-1/2") {
-                        self.buffer.consume(6);
-                        visitor.outcome(Some(Outcome::Draw));
+
+                    let kind = if window > self.max_comment_length {
+                        SyntaxErrorKind::CommentTooLong
                     } else {
-                        self.buffer.bump();
-                        while let Some(ch) = self.buffer.peek() {
-                            if b'0' <= ch && ch <= b'9' {
-                                self.buffer.bump();
-                            } else {
-                                break;
-                            }
-                        }
-                        while let Some(b'.') = self.buffer.peek() {
-                            self.buffer.bump();
-                        }
-                    }
-                }
-                b'2' | b'3' | b'4' | b'5' | b'6' | b'7' | b'8' | b'9' => {
+                        SyntaxErrorKind::UnterminatedComment
+                    };
+                    let err = self.syntax_error(kind, 1);
+                    self.buffer.consume(window);
+                    self.skip_until(b'}')?;
                     self.buffer.bump();
-                    while let Some(ch) = self.buffer.peek() {
-                        if b'0' <= ch && ch <= b'9' {
-                            self.buffer.bump();
-                        } else {
-                            break;
-                        }
-                    }
-                    while let Some(b'.') = self.buffer.peek() {
-                        self.buffer.bump();
-                    }
+                    self.skip_movetext()?;
+                    return Err(err);
                 }
-                b'(' => {
-                    self.buffer.bump();
+                Token::BeginVariation => {
+                    self.buffer.consume(consumed);
                     if let Skip(true) = visitor.begin_variation() {
                         self.skip_variation()?;
                     }
+                    continue;
                 }
-                b')' => {
-                    self.buffer.bump();
-                    visitor.end_variation();
-                }
-                b'$' => {
-                    self.buffer.bump();
-                    let token_end = self.find_token_end(0);
-                    if let Ok(nag) = btoi::btou(&self.buffer.data()[..token_end]) {
-                        visitor.nag(Nag(nag));
-                    }
-                    self.buffer.consume(token_end);
-                }
-                b'!' => {
-                    self.buffer.bump();
-                    match self.buffer.peek() {
-                        Some(b'!') => {
-                            self.buffer.bump();
-                            visitor.nag(Nag::BRILLIANT_MOVE);
-                        }
-                        Some(b'?') => {
-                            self.buffer.bump();
-                            visitor.nag(Nag::SPECULATIVE_MOVE);
-                        }
-                        _ => {
-                            visitor.nag(Nag::GOOD_MOVE);
-                        }
-                    }
-                }
-                b'?' => {
-                    self.buffer.bump();
-                    match self.buffer.peek() {
-                        Some(b'!') => {
-                            self.buffer.bump();
-                            visitor.nag(Nag::DUBIOUS_MOVE);
-                        }
-                        Some(b'?') => {
-                            self.buffer.bump();
-                            visitor.nag(Nag::BLUNDER);
-                        }
-                        _ => {
-                            visitor.nag(Nag::MISTAKE);
-                        }
-                    }
-                }
-                b'*' => {
-                    visitor.outcome(None);
-                    self.buffer.bump();
+                Token::EndVariation => visitor.end_variation(),
+                Token::Outcome(outcome) => visitor.outcome(outcome),
+                Token::LineComment => {
+                    self.buffer.consume(consumed);
+                    self.read_line(|comment| visitor.line_comment(RawComment(comment)))?;
+                    continue;
                 }
-                b'a' | b'b' | b'c' | b'd' | b'e' | b'f' | b'g' | b'h' | b'N' | b'B' | b'R'
-                | b'Q' | b'K' | b'@' | b'-' | b'O' => {
-                    let token_end = self.find_token_end(1);
-                    if let Ok(san) = SanPlus::from_ascii(&self.buffer.data()[..token_end]) {
-                        visitor.san(san);
-                    }
-                    self.buffer.consume(token_end);
+                Token::Escape => {
+                    // Leave the `%`.
+                    self.buffer.consume(consumed - 1);
+                    self.read_escape_line(visitor)?;
+                    continue;
                 }
-                _ => {
-                    self.buffer.bump();
+                Token::End => {
+                    self.buffer.consume(consumed);
+                    break;
                 }
+                Token::Ignored => (),
             }
+            self.buffer.consume(consumed);
         }
 
         Ok(())
     }
 
+    fn read_long_comment<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Error> {
+        let err = self.syntax_error(SyntaxErrorKind::UnterminatedComment, 1);
+
+        visitor.begin_long_comment();
+        loop {
+            let data = self.buffer.ensure_bytes(1, &mut self.reader)?;
+            if data.is_empty() {
+                visitor.end_long_comment();
+                return Err(err);
+            }
+
+            if let Some(right_brace) = memchr::memchr(b'}', data) {
+                visitor.partial_comment(RawComment(&data[..right_brace]));
+                self.buffer.consume(right_brace + 1);
+                break;
+            }
+
+            visitor.partial_comment(RawComment(data));
+            self.buffer.discard_data();
+        }
+        visitor.end_long_comment();
+
+        Ok(())
+    }
+
     fn skip_variation(&mut self) -> io::Result<()> {
         let mut depth = 0usize;
 
-        while let &[ch, ..] = self.buffer.ensure_bytes(3, &mut self.reader)? {
-            match ch {
-                b'(' => {
+        while let data @ &[_, ..] = self.buffer.ensure_bytes(3, &mut self.reader)? {
+            match lexer::skip_token(data) {
+                (SkipToken::BeginVariation, consumed) => {
+                    self.buffer.consume(consumed);
                     depth += 1;
-                    self.buffer.bump();
                 }
-                b')' => {
-                    if let Some(d) = depth.checked_sub(1) {
-                        self.buffer.bump();
+                (SkipToken::EndVariation, consumed) => match depth.checked_sub(1) {
+                    Some(d) => {
+                        self.buffer.consume(consumed);
                         depth = d;
-                    } else {
-                        break;
                     }
-                }
-                b'{' => {
-                    self.buffer.bump();
+                    None => break,
+                },
+                (SkipToken::Comment, consumed) => {
+                    self.buffer.consume(consumed);
                     self.skip_until(b'}')?;
                     self.buffer.bump();
                 }
-                b';' => {
-                    self.buffer.bump();
+                (SkipToken::LineComment, consumed) => {
+                    self.buffer.consume(consumed);
                     self.skip_until(b'\n')?;
                 }
-                b'\n' => {
-                    match self.buffer.data().get(1).cloned() {
-                        Some(b'%') => {
-                            self.buffer.consume(2);
-                            self.skip_until(b'\n')?;
-                        }
-                        Some(b'[') | Some(b'\n') => {
-                            // Do not consume the first or second line break.
-                            break;
-                        }
-                        Some(b'\r') => {
-                            // Do not consume the first or second line break.
-                            if self.buffer.data().get(2).cloned() == Some(b'\n') {
-                                break;
-                            }
-                        }
-                        _ => {
-                            self.buffer.bump();
-                        }
-                    }
-                }
-                _ => {
-                    self.buffer.bump();
-                }
+                (SkipToken::End, _) => break,
+                (SkipToken::Ignored, consumed) => self.buffer.consume(consumed),
             }
         }
 
         Ok(())
     }
 
-    pub fn read_game<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<Option<V::Result>> {
+    /// Read a single game, if any.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub fn read_game<V: Visitor>(&mut self, visitor: &mut V) -> Result<Option<V::Result>, Error> {
+        self.read_game_until(visitor, |_| false)
+    }
+
+    /// Like [`BufferedReader::read_game()`], but skips the rest of the
+    /// movetext as soon as `skip_movetext` returns `true`.
+    pub(crate) fn read_game_until<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+        skip_movetext: impl Fn(&V) -> bool,
+    ) -> Result<Option<V::Result>, Error> {
         self.skip_bom()?;
-        self.skip_whitespace()?;
+        self.skip_whitespace(visitor)?;
 
         if self.buffer.ensure_bytes(1, &mut self.reader)?.is_empty() {
             return Ok(None);
         }
 
+        self.games += 1;
+
+        let start = self.buffer.position();
+        let result = self.read_tags_and_movetext(visitor, &skip_movetext);
+        self.last_game = Some(start..self.buffer.position());
+        result?;
+
+        self.skip_whitespace(visitor)?;
+        Ok(Some(visitor.end_game()))
+    }
+
+    fn read_tags_and_movetext<V: Visitor>(
+        &mut self,
+        visitor: &mut V,
+        skip_movetext: &impl Fn(&V) -> bool,
+    ) -> Result<(), Error> {
         visitor.begin_tags();
         self.read_tags(visitor)?;
         if let Skip(false) = visitor.begin_movetext() {
-            self.read_movetext(visitor)?;
+            self.read_movetext(visitor, skip_movetext)
         } else {
-            self.skip_movetext()?;
+            Ok(self.skip_movetext()?)
         }
-
-        self.skip_whitespace()?;
-        Ok(Some(visitor.end_game()))
     }
 
-    pub fn skip_game(&mut self) -> io::Result<bool> {
+    /// Skip a single game, if any.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    pub fn skip_game(&mut self) -> Result<bool, Error> {
         self.read_game(&mut SkipVisitor).map(|r| r.is_some())
     }
 
@@ -546,7 +603,7 @@ impl<R: Read> BufferedReader<R> {
     ///
     /// * I/O error from the underlying reader.
     /// * Irrecoverable parser errors.
-    pub fn read_all<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
+    pub fn read_all<V: Visitor>(&mut self, visitor: &mut V) -> Result<(), Error> {
         while self.read_game(visitor)?.is_some() {}
         Ok(())
     }
@@ -569,15 +626,50 @@ impl<R: Read> BufferedReader<R> {
         Cursor::new(self.buffer).chain(self.reader)
     }
 
+    /// Absolute byte offset of the next unread byte, counted from the start
+    /// of the input.
+    pub fn position(&self) -> u64 {
+        self.buffer.position()
+    }
+
+    /// The byte range of the last game that was read or skipped, counted
+    /// from the start of the input. It begins at the first tag (or the
+    /// first movetext token) and ends after the end of the movetext,
+    /// possibly including a line break.
+    ///
+    /// Also available after a syntax error, so that the range of a
+    /// malformed game can be recorded as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::BufferedReader;
+    ///
+    /// let pgn = b"[Event \"A\"]\n\n1. e4 *\n\n[Event \"B\"]\n\n1. d4 *\n";
+    ///
+    /// let mut reader = BufferedReader::new(&pgn[..]);
+    /// assert_eq!(reader.last_game_span(), None);
+    ///
+    /// reader.skip_game()?;
+    /// reader.skip_game()?;
+    /// let span = reader.last_game_span().expect("game");
+    /// assert!(pgn[span.start as usize..span.end as usize].starts_with(b"[Event \"B\"]"));
+    /// # Ok::<_, pgn_reader::Error>(())
+    /// ```
+    pub fn last_game_span(&self) -> Option<Range<u64>> {
+        self.last_game.clone()
+    }
+
     /// Returns whether the reader has another game to parse, but does not
-    /// actually parse it.
+    /// actually parse it. Escape lines before the game are skipped and not
+    /// reported to any visitor.
     ///
     /// # Errors
     ///
     /// * I/O error from the underlying reader.
     pub fn has_more(&mut self) -> io::Result<bool> {
         self.skip_bom()?;
-        self.skip_whitespace()?;
+        self.skip_whitespace(&mut SkipVisitor)?;
         Ok(!self.buffer.ensure_bytes(1, &mut self.reader)?.is_empty())
     }
 }
@@ -592,7 +684,7 @@ pub struct IntoIter<'a, V: 'a, R> {
 }
 
 impl<'a, V: Visitor, R: Read> Iterator for IntoIter<'a, V, R> {
-    type Item = Result<V::Result, io::Error>;
+    type Item = Result<V::Result, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.reader.read_game(self.visitor) {
@@ -605,7 +697,10 @@ impl<'a, V: Visitor, R: Read> Iterator for IntoIter<'a, V, R> {
 
 #[cfg(test)]
 mod tests {
+    use shakmaty::san::{San, SanPlus};
+
     use super::*;
+    use crate::types::{InvalidTokenKind, Nag};
 
     struct _AssertObjectSafe<R>(Box<BufferedReader<R>>);
 
@@ -696,4 +791,337 @@ mod tests {
         assert_ne!(collector.sans[5], San::Null);
         Ok(())
     }
+
+    #[test]
+    fn test_skip_variation_with_carriage_return() -> Result<(), Error> {
+        struct Mainline {
+            sans: usize,
+        }
+
+        impl Visitor for Mainline {
+            type Result = usize;
+
+            fn san(&mut self, _san_plus: SanPlus) {
+                self.sans += 1;
+            }
+
+            fn begin_variation(&mut self) -> Skip {
+                Skip(true)
+            }
+
+            fn end_game(&mut self) -> usize {
+                self.sans
+            }
+        }
+
+        let mut reader = BufferedReader::new(io::Cursor::new(b"1. e4 (1. d4\n\r d5) e5 *"));
+        assert_eq!(reader.read_game(&mut Mainline { sans: 0 })?, Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let mut reader = BufferedReader::new(io::Cursor::new(
+            b"1. e4\n\n[Event \"x\"]\n1. d4 {unterminated",
+        ));
+        let mut counter = GameCounter::default();
+        reader.read_game(&mut counter).unwrap();
+        let err = match reader.read_game(&mut counter) {
+            Ok(_) => panic!("expected error"),
+            Err(Error::Syntax(err)) => err,
+            Err(err) => panic!("unexpected error: {err}"),
+        };
+        assert_eq!(err.kind(), SyntaxErrorKind::UnterminatedComment);
+        assert_eq!(err.offset(), 25);
+        assert_eq!(err.line(), 4);
+        assert_eq!(err.column(), 7);
+        assert_eq!(err.game(), 1);
+    }
+
+    #[test]
+    fn test_syntax_error_location_after_refill() -> Result<(), io::Error> {
+        let game = b"[Event \"?\"]\n[Site \"?\"]\n\n1. e4 e5 2. Nf3 Nc6 *\n\n";
+        let mut pgn = game.repeat(2000);
+        pgn.extend_from_slice(b"[Event \"?\"]\n[Site \"");
+
+        let mut counter = GameCounter::default();
+        let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+        let err = match reader.read_all(&mut counter) {
+            Err(Error::Syntax(err)) => err,
+            _ => panic!("expected syntax error"),
+        };
+        assert_eq!(counter.count, 2000);
+        assert_eq!(err.kind(), SyntaxErrorKind::UnterminatedTag);
+        assert_eq!(err.offset(), game.len() as u64 * 2000 + 12);
+        assert_eq!(err.line(), 5 * 2000 + 2);
+        assert_eq!(err.column(), 1);
+        assert_eq!(err.game(), 2000);
+
+        let err = io::Error::from(Error::Syntax(err));
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test]
+    fn test_comment_too_long() {
+        let pgn = format!(
+            "1. e4 {{ {} }} 1... e5 *\n\n1. d4 *\n\n1. c4 {{ unterminated",
+            "x".repeat(100)
+        );
+        let mut counter = GameCounter::default();
+        let mut reader = BufferedReader::builder()
+            .buffer_capacity(64)
+            .max_comment_length(32)
+            .build(io::Cursor::new(pgn));
+
+        match reader.read_game(&mut counter) {
+            Err(Error::Syntax(err)) => {
+                assert_eq!(err.kind(), SyntaxErrorKind::CommentTooLong);
+                assert_eq!(err.column(), 7);
+            }
+            _ => panic!("expected syntax error"),
+        }
+        assert_eq!(counter.count, 0);
+
+        assert!(reader.read_game(&mut counter).unwrap().is_some());
+        assert_eq!(counter.count, 1);
+
+        match reader.read_game(&mut counter) {
+            Err(Error::Syntax(err)) => {
+                assert_eq!(err.kind(), SyntaxErrorKind::UnterminatedComment);
+            }
+            _ => panic!("expected syntax error"),
+        }
+        assert!(reader.read_game(&mut counter).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_length_limits_clamped() -> Result<(), Error> {
+        let mut counter = GameCounter::default();
+        let mut reader = BufferedReader::builder()
+            .max_tag_line_length(usize::MAX)
+            .max_comment_length(usize::MAX)
+            .build(io::Cursor::new(b"[Event \"?\"]\n\n1. e4 { comment } *"));
+        reader.read_all(&mut counter)?;
+        assert_eq!(counter.count, 1);
+
+        let mut reader = BufferedReader::from_buffer(Buffer::with_capacity(2), io::empty());
+        assert_eq!(
+            (reader.max_tag_line_length, reader.max_comment_length),
+            (3, 1)
+        );
+        assert!(reader.read_game(&mut counter)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_too_long() {
+        let pgn = format!(
+            "[Event \"{}\"]\n[Site \"?\"]\n\n1. e4 *\n\n[Event \"?\"]\n\n1. d4 *",
+            "x".repeat(100)
+        );
+        let mut counter = GameCounter::default();
+        let mut reader = BufferedReader::builder()
+            .max_tag_line_length(64)
+            .build(io::Cursor::new(pgn));
+
+        match reader.read_game(&mut counter) {
+            Err(Error::Syntax(err)) => {
+                assert_eq!(err.kind(), SyntaxErrorKind::TagTooLong);
+                assert_eq!(err.line(), 1);
+            }
+            _ => panic!("expected syntax error"),
+        }
+        assert_eq!(counter.count, 0);
+
+        assert!(reader.read_game(&mut counter).unwrap().is_some());
+        assert!(reader.read_game(&mut counter).unwrap().is_none());
+        assert_eq!(counter.count, 1);
+    }
+
+    #[test]
+    fn test_stream_long_comments() -> Result<(), Error> {
+        #[derive(Default)]
+        struct CommentCollector {
+            comments: Vec<Vec<u8>>,
+            chunks: usize,
+            long_comment: Option<Vec<u8>>,
+        }
+
+        impl Visitor for CommentCollector {
+            type Result = ();
+
+            fn comment(&mut self, comment: RawComment<'_>) {
+                self.comments.push(comment.as_bytes().to_owned());
+            }
+
+            fn begin_long_comment(&mut self) {
+                assert!(self.long_comment.is_none());
+                self.long_comment = Some(Vec::new());
+            }
+
+            fn partial_comment(&mut self, chunk: RawComment<'_>) {
+                self.chunks += 1;
+                self.long_comment
+                    .as_mut()
+                    .expect("begin_long_comment")
+                    .extend_from_slice(chunk.as_bytes());
+            }
+
+            fn end_long_comment(&mut self) {
+                let comment = self.long_comment.take().expect("begin_long_comment");
+                self.comments.push(comment);
+            }
+
+            fn end_game(&mut self) {}
+        }
+
+        let long = "0123456789".repeat(100);
+        let pgn = format!("1. e4 {{short}} e5 {{{long}}} 2. Nf3 {{{long}");
+
+        let mut collector = CommentCollector::default();
+        let mut reader = BufferedReader::builder()
+            .buffer_capacity(64)
+            .max_tag_line_length(32)
+            .max_comment_length(32)
+            .stream_long_comments(true)
+            .build(io::Cursor::new(pgn));
+
+        match reader.read_game(&mut collector) {
+            Err(Error::Syntax(err)) => {
+                assert_eq!(err.kind(), SyntaxErrorKind::UnterminatedComment);
+                assert_eq!(err.column(), 28 + long.len() as u64);
+            }
+            _ => panic!("expected syntax error"),
+        }
+        assert_eq!(
+            collector.comments,
+            vec![
+                b"short".to_vec(),
+                long.as_bytes().to_vec(),
+                long.as_bytes().to_vec()
+            ]
+        );
+        assert!(collector.chunks > 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_comments_and_escapes() -> Result<(), Error> {
+        #[derive(Default)]
+        struct Lines {
+            lines: Vec<String>,
+        }
+
+        impl Visitor for Lines {
+            type Result = ();
+
+            fn begin_tags(&mut self) {
+                self.lines.push("begin_tags".to_owned());
+            }
+
+            fn line_comment(&mut self, comment: RawComment<'_>) {
+                self.lines
+                    .push(format!("; {}", String::from_utf8_lossy(comment.as_bytes())));
+            }
+
+            fn escape_line(&mut self, line: &[u8]) {
+                self.lines
+                    .push(format!("% {}", String::from_utf8_lossy(line)));
+            }
+
+            fn end_game(&mut self) {
+                self.lines.push("end_game".to_owned());
+            }
+        }
+
+        let pgn = b"%first\n[Event \"?\"] %ket\n%tags\n\n1. e4 ; king's pawn\n%escape\ne5 ;0123456789\n\n%after\n";
+
+        let mut lines = Lines::default();
+        let mut reader = BufferedReader::builder()
+            .max_comment_length(8)
+            .build(&pgn[..]);
+        reader.read_all(&mut lines)?;
+
+        assert_eq!(
+            lines.lines,
+            [
+                "% first",
+                "begin_tags",
+                "% ket",
+                "% tags",
+                ";  king's ",
+                "% escape",
+                "; 01234567",
+                "% after",
+                "end_game",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_token() -> Result<(), Error> {
+        #[derive(Default)]
+        struct Tokens {
+            tokens: Vec<String>,
+            abort: bool,
+        }
+
+        impl Visitor for Tokens {
+            type Result = ();
+
+            fn san(&mut self, san_plus: SanPlus) {
+                self.tokens.push(san_plus.to_string());
+            }
+
+            fn invalid_token(&mut self, token: &[u8], kind: InvalidTokenKind) -> TokenAction {
+                self.tokens
+                    .push(format!("{kind}: {}", String::from_utf8_lossy(token)));
+                match token {
+                    b"O-Ox" => TokenAction::Replace("O-O".parse().unwrap()),
+                    _ if self.abort => TokenAction::Abort,
+                    _ => TokenAction::Skip,
+                }
+            }
+
+            fn end_game(&mut self) {}
+        }
+
+        let pgn = b"1. e4 & e5 2. Nf3x $x Nc6 3. O-Ox \xc3\xa4 *\n\n1. d4 ?? d5 &\n";
+
+        let mut tokens = Tokens::default();
+        let mut reader = BufferedReader::new(&pgn[..]);
+        reader.read_game(&mut tokens)?;
+        assert_eq!(
+            tokens.tokens,
+            [
+                "e4",
+                "unknown token: &",
+                "e5",
+                "invalid san: Nf3x",
+                "invalid nag: $x",
+                "Nc6",
+                "invalid san: O-Ox",
+                "O-O",
+                "unknown token: \u{e4}",
+            ]
+        );
+
+        tokens.tokens.clear();
+        tokens.abort = true;
+        match reader.read_game(&mut tokens) {
+            Err(Error::Syntax(err)) => {
+                assert_eq!(err.kind(), SyntaxErrorKind::InvalidToken);
+                assert_eq!(err.line(), 3);
+                assert_eq!(err.column(), 13);
+            }
+            _ => panic!("expected syntax error"),
+        }
+        assert_eq!(tokens.tokens, ["d4", "d5", "unknown token: &"]);
+        assert!(reader.read_game(&mut tokens)?.is_none());
+
+        Ok(())
+    }
 }
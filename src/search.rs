@@ -0,0 +1,313 @@
+use std::{collections::HashMap, mem, num::NonZeroU32};
+
+use shakmaty::{
+    san::SanPlus,
+    variant::{Variant, VariantPosition},
+    zobrist::{Zobrist64, ZobristHash},
+    ByColor, ByRole, Color, EnPassantMode, Move, Position, Setup,
+};
+
+use crate::{
+    position::{forward_visitor, ply, PositionAdapter, PositionVisitor},
+    types::Skip,
+};
+
+/// A position found by a [`PositionSearch`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PositionHit {
+    /// Number of the game, counting from 0 for the first game visited by
+    /// the search. Games are counted over all readers that the search is
+    /// used with, until [`PositionSearch::reset_games()`].
+    pub game: usize,
+    /// Index of the matching target, in the order in which targets were
+    /// added.
+    pub target: usize,
+    /// Ply of the position, i.e., the number of half-moves played to reach
+    /// it, respecting the move number of the starting position. 0 for the
+    /// standard starting position.
+    pub ply: u32,
+    /// Path through variations to the line that reached the position,
+    /// empty for the mainline. Each entry is the index of a variation among
+    /// all variations of its parent line, counting from 0.
+    pub variations: Vec<usize>,
+}
+
+/// Setup without move counters, so that transpositions compare equal.
+fn key(pos: &VariantPosition) -> Setup {
+    let mut setup = pos.to_setup(EnPassantMode::Legal);
+    setup.halfmoves = 0;
+    setup.fullmoves = NonZeroU32::MIN;
+    setup
+}
+
+/// Tests if the material `to` can possibly be reached from `from`. Captures
+/// and promotions never increase the number of pieces or pawns of a side,
+/// except in variants with drops, which are not considered here.
+pub(crate) fn material_reachable(from: &ByColor<ByRole<u8>>, to: &ByColor<ByRole<u8>>) -> bool {
+    Color::ALL.into_iter().all(|color| {
+        let (from, to) = (from.get(color), to.get(color));
+        let count = |material: &ByRole<u8>| material.iter().map(|&n| u32::from(n)).sum::<u32>();
+        count(from) >= count(to) && from.pawn >= to.pawn
+    })
+}
+
+#[derive(Debug, Clone)]
+struct Target {
+    variant: Variant,
+    setup: Setup,
+}
+
+impl Target {
+    /// Tests if the target can possibly be reached from `pos`.
+    fn reachable_from(&self, pos: &VariantPosition) -> bool {
+        self.variant == pos.variant()
+            && (self.variant == Variant::Crazyhouse
+                || material_reachable(&pos.board().material(), &self.setup.board.material()))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Search {
+    targets: Vec<Target>,
+    by_hash: HashMap<Zobrist64, Vec<usize>>,
+    variations: bool,
+    games: usize,
+    hits: Vec<PositionHit>,
+    path: Vec<usize>,
+    // Number of variations seen so far in each line of the current path.
+    counters: Vec<usize>,
+}
+
+impl Search {
+    fn check(&mut self, pos: &VariantPosition) {
+        let Some(candidates) = self.by_hash.get(&pos.zobrist_hash(EnPassantMode::Legal)) else {
+            return;
+        };
+        let key = key(pos);
+        for &target in candidates {
+            if self.targets[target].variant == pos.variant() && self.targets[target].setup == key {
+                self.hits.push(PositionHit {
+                    game: self.games - 1,
+                    target,
+                    ply: ply(pos),
+                    variations: self.path.clone(),
+                });
+            }
+        }
+    }
+}
+
+impl PositionVisitor for Search {
+    type Result = Vec<PositionHit>;
+
+    fn begin_tags(&mut self) {
+        self.games += 1;
+        self.path.clear();
+        self.counters.clear();
+        self.counters.push(0);
+    }
+
+    fn begin_movetext(&mut self, pos: &VariantPosition) -> Skip {
+        if !self.targets.iter().any(|target| target.reachable_from(pos)) {
+            return Skip(true);
+        }
+        self.check(pos);
+        Skip(false)
+    }
+
+    fn play(
+        &mut self,
+        _before: &VariantPosition,
+        _san_plus: SanPlus,
+        _m: Move,
+        after: &VariantPosition,
+    ) {
+        self.check(after);
+    }
+
+    fn begin_variation(&mut self, _pos: &VariantPosition) -> Skip {
+        if !self.variations {
+            return Skip(true);
+        }
+        let counter = self.counters.last_mut().expect("counter of line");
+        self.path.push(*counter);
+        *counter += 1;
+        self.counters.push(0);
+        Skip(false)
+    }
+
+    fn end_variation(&mut self) {
+        if self.counters.len() > 1 {
+            self.path.pop();
+            self.counters.pop();
+        }
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        mem::take(&mut self.hits)
+    }
+}
+
+/// A [`Visitor`](crate::Visitor) that finds games reaching any of the given target
+/// positions, including through transpositions.
+///
+/// Positions are tracked by a [`PositionAdapter`], so games start from the
+/// `Variant` and `FEN` tags. Each position is looked up by its Zobrist
+/// hash, and compared exactly on a hash hit. Move counters are ignored, but
+/// the side to move, castling rights and legal en passant squares must
+/// match.
+///
+/// Games are skipped before reading the movetext if their starting
+/// position rules out all targets, because it is a different variant, or
+/// because it has fewer pieces or pawns of a side than every target.
+///
+/// The result for each game is the list of hits, in the order in which
+/// they occur in the movetext.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, PositionSearch};
+/// use shakmaty::{fen::Fen, CastlingMode, Chess};
+///
+/// // Queen's Gambit Declined, reached by different move orders.
+/// let fen: Fen = "rnbqkbnr/ppp2ppp/4p3/3p4/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3".parse()?;
+/// let target: Chess = fen.into_position(CastlingMode::Standard)?;
+///
+/// let pgn = b"1. d4 d5 2. c4 e6 *\n\n1. c4 e6 2. d4 d5 *\n\n1. e4 *\n\n1. d4 d5 (1... e6 2. c4 d5) *";
+///
+/// let mut search = PositionSearch::new();
+/// search.add_target(target).search_variations(true);
+///
+/// let reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let hits: Vec<_> = reader
+///     .into_iter(&mut search)
+///     .collect::<Result<Vec<_>, _>>()?
+///     .into_iter()
+///     .flatten()
+///     .collect();
+///
+/// let games: Vec<_> = hits.iter().map(|hit| (hit.game, hit.ply, hit.variations.clone())).collect();
+/// assert_eq!(games, [(0, 4, vec![]), (1, 4, vec![]), (3, 4, vec![0])]);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct PositionSearch {
+    inner: PositionAdapter<Search>,
+}
+
+impl Default for PositionSearch {
+    fn default() -> PositionSearch {
+        PositionSearch::new()
+    }
+}
+
+impl PositionSearch {
+    /// Creates a new search without targets.
+    pub fn new() -> PositionSearch {
+        PositionSearch {
+            inner: PositionAdapter::new(Search::default()),
+        }
+    }
+
+    /// Adds a target position. Targets are numbered in the order in which
+    /// they are added.
+    pub fn add_target(&mut self, target: impl Into<VariantPosition>) -> &mut PositionSearch {
+        let target = target.into();
+        let search = self.inner.get_mut();
+        search
+            .by_hash
+            .entry(target.zobrist_hash(EnPassantMode::Legal))
+            .or_default()
+            .push(search.targets.len());
+        search.targets.push(Target {
+            variant: target.variant(),
+            setup: key(&target),
+        });
+        self
+    }
+
+    /// Starts counting games from 0 again, for example before searching
+    /// another file.
+    pub fn reset_games(&mut self) -> &mut PositionSearch {
+        self.inner.get_mut().games = 0;
+        self
+    }
+
+    /// Also search inside variations. Disabled by default, so that
+    /// variations are skipped.
+    pub fn search_variations(&mut self, variations: bool) -> &mut PositionSearch {
+        self.inner.get_mut().variations = variations;
+        self
+    }
+}
+
+forward_visitor!(PositionSearch, Vec<PositionHit>);
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::{fen::Fen, CastlingMode};
+
+    use super::*;
+    use crate::position::collect_hits as search;
+
+    fn target(fen: &str) -> VariantPosition {
+        let fen: Fen = fen.parse().unwrap();
+        VariantPosition::from_setup(Variant::Chess, fen.into_setup(), CastlingMode::Standard)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_variations() {
+        let mut s = PositionSearch::new();
+        s.add_target(target(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        ))
+        .add_target(target(
+            "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1",
+        ));
+
+        let pgn = b"1. d4 (1. e4 e5) (1. c4 (1. Nf3) 1... e5) 1... d5 2. Nf3 *\n\n1. e4 *";
+        let hits = search(&mut s, pgn);
+        assert_eq!(
+            hits,
+            [PositionHit {
+                game: 1,
+                target: 0,
+                ply: 1,
+                variations: vec![],
+            }]
+        );
+
+        s.search_variations(true);
+        let hits = search(&mut s, pgn);
+        let found: Vec<_> = hits
+            .iter()
+            .map(|hit| (hit.game, hit.target, hit.variations.clone()))
+            .collect();
+        assert_eq!(found, [(2, 0, vec![0]), (2, 1, vec![1, 0]), (3, 0, vec![])]);
+    }
+
+    #[test]
+    fn test_transposition() {
+        // The en passant square after 1... e5 is not legal, so it does not
+        // matter that it is missing after 2... e5.
+        let mut s = PositionSearch::new();
+        s.add_target(target(
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+        ));
+
+        let pgn = b"1. e3 e6 2. e4 e5 *\n\n[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. Kd2 *\n\n[Variant \"Atomic\"]\n\n1. e4 e5 *";
+        let hits = search(&mut s, pgn);
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].game, hits[0].ply), (0, 4));
+
+        let hits = search(&mut s, b"1. e4 e5 2. Nf3 *");
+        assert_eq!((hits[0].game, hits[0].ply), (3, 2));
+
+        s.reset_games();
+        let hits = search(&mut s, b"1. e4 e5 *");
+        assert_eq!((hits[0].game, hits[0].ply), (0, 2));
+    }
+}
@@ -0,0 +1,506 @@
+use std::cmp::min;
+
+use crate::{
+    error::{SyntaxError, SyntaxErrorKind},
+    lexer::{self, SkipToken, TagToken, Token},
+    types::{RawComment, RawTag, Skip, TokenAction},
+    visitor::{SkipVisitor, SliceVisitor},
+};
+
+/// Reads games directly from an in-memory byte slice, for example from a
+/// memory mapped file.
+///
+/// Tags and comments are passed to the [`SliceVisitor`] as borrows of the
+/// input, without copying. Unlike the
+/// [`BufferedReader`](crate::BufferedReader), there are no limits on the
+/// length of tag lines or comments.
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::{RawComment, SliceReader, SliceVisitor};
+///
+/// struct Comments<'a> {
+///     comments: Vec<&'a [u8]>,
+/// }
+///
+/// impl<'a> SliceVisitor<'a> for Comments<'a> {
+///     type Result = ();
+///
+///     fn comment(&mut self, comment: RawComment<'a>) {
+///         self.comments.push(comment.as_bytes());
+///     }
+///
+///     fn end_game(&mut self) {}
+/// }
+///
+/// let pgn = b"1. e4 { best by test } e5 { solid } *";
+///
+/// let mut comments = Comments { comments: Vec::new() };
+/// let mut reader = SliceReader::new(pgn);
+/// reader.read_all(&mut comments)?;
+///
+/// assert_eq!(comments.comments, [&b" best by test "[..], b" solid "]);
+/// # Ok::<_, pgn_reader::SyntaxError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SliceReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    games: u64,
+    // Set when a `TryVisitor` stopped reading.
+    pub(crate) stopped: bool,
+}
+
+impl<'a> SliceReader<'a> {
+    /// Creates a reader for the given input.
+    pub fn new(data: &'a [u8]) -> SliceReader<'a> {
+        SliceReader {
+            data,
+            pos: 0,
+            games: 0,
+            stopped: false,
+        }
+    }
+
+    /// Gets the remaining input that has not been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.pos = min(self.pos + n, self.data.len());
+    }
+
+    fn bump(&mut self) {
+        self.consume(1);
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    /// Creates a syntax error for the token that started `n` bytes before
+    /// the current position.
+    fn syntax_error(&self, kind: SyntaxErrorKind, n: usize) -> SyntaxError {
+        let offset = self.pos.saturating_sub(n);
+        let before = &self.data[..offset];
+        let line_start = memchr::memrchr(b'\n', before).map_or(0, |newline| newline + 1);
+        SyntaxError::new(
+            kind,
+            offset as u64,
+            memchr::memchr_iter(b'\n', before).count() as u64 + 1,
+            (offset - line_start) as u64 + 1,
+            self.games.saturating_sub(1),
+        )
+    }
+
+    fn skip_bom(&mut self) {
+        if self.remaining().starts_with(b"\xef\xbb\xbf") {
+            self.consume(3);
+        }
+    }
+
+    fn skip_until(&mut self, needle: u8) {
+        let remaining = self.remaining();
+        self.consume(memchr::memchr(needle, remaining).unwrap_or(remaining.len()));
+    }
+
+    /// Returns the rest of the line, excluding the line break.
+    fn read_line(&mut self) -> &'a [u8] {
+        let remaining = self.remaining();
+        let end = memchr::memchr(b'\n', remaining).unwrap_or(remaining.len());
+        self.consume(end);
+        &remaining[..end]
+    }
+
+    fn read_escape_line<V: SliceVisitor<'a>>(&mut self, visitor: &mut V) {
+        self.bump();
+        visitor.escape_line(self.read_line());
+        self.bump();
+    }
+
+    fn skip_whitespace<V: SliceVisitor<'a>>(&mut self, visitor: &mut V) {
+        while let Some(ch) = self.peek() {
+            match ch {
+                b' ' | b'\t' | b'\r' | b'\n' => {
+                    self.bump();
+                }
+                b'%' => self.read_escape_line(visitor),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_ket<V: SliceVisitor<'a>>(&mut self, visitor: &mut V) {
+        while let Some(ch) = self.peek() {
+            match ch {
+                b' ' | b'\t' | b'\r' | b']' => {
+                    self.bump();
+                }
+                b'%' => return self.read_escape_line(visitor),
+                b'\n' => {
+                    self.bump();
+                    return;
+                }
+                _ => {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn read_tags<V: SliceVisitor<'a>>(&mut self, visitor: &mut V) -> Result<(), SyntaxError> {
+        while let data @ &[_, ..] = self.remaining() {
+            match lexer::tag_token(data, usize::MAX) {
+                (TagToken::Tag { name, value }, consumed) => {
+                    visitor.tag(name, RawTag(value));
+                    self.consume(consumed);
+                    self.skip_ket(visitor);
+                }
+                (TagToken::Malformed, consumed) => {
+                    self.consume(consumed);
+                    self.skip_ket(visitor);
+                }
+                (TagToken::Unclosed, consumed) => {
+                    // Not closed before the end of the input.
+                    self.consume(consumed);
+                    let err = self.syntax_error(SyntaxErrorKind::UnterminatedTag, 1);
+                    self.pos = self.data.len();
+                    return Err(err);
+                }
+                (TagToken::Escape, _) => self.read_escape_line(visitor),
+                (TagToken::End, _) => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_movetext(&mut self) {
+        while let data @ &[_, ..] = self.remaining() {
+            let (token, consumed) = lexer::skip_token(data);
+            self.consume(consumed);
+            match token {
+                SkipToken::Comment => {
+                    self.skip_until(b'}');
+                    self.bump();
+                }
+                SkipToken::LineComment => self.skip_until(b'\n'),
+                SkipToken::End => break,
+                SkipToken::BeginVariation | SkipToken::EndVariation | SkipToken::Ignored => (),
+            }
+        }
+    }
+
+    fn read_movetext<V: SliceVisitor<'a>>(
+        &mut self,
+        visitor: &mut V,
+        skip_movetext: impl Fn(&V) -> bool,
+    ) -> Result<(), SyntaxError> {
+        while let data @ &[_, ..] = self.remaining() {
+            if skip_movetext(visitor) {
+                self.skip_movetext();
+                break;
+            }
+
+            let (token, consumed) = lexer::token(data, usize::MAX);
+            self.consume(consumed);
+            match token {
+                Token::MoveNumber(move_number) => visitor.move_number(move_number),
+                Token::San(san_plus) => visitor.san(san_plus),
+                Token::Invalid(kind) => match visitor.invalid_token(&data[..consumed], kind) {
+                    TokenAction::Skip => (),
+                    TokenAction::Abort => {
+                        let err = self.syntax_error(SyntaxErrorKind::InvalidToken, consumed);
+                        self.skip_movetext();
+                        return Err(err);
+                    }
+                    TokenAction::Replace(san_plus) => visitor.san(san_plus),
+                },
+                Token::Nag(nag) => visitor.nag(nag),
+                Token::Comment(comment) => visitor.comment(RawComment(comment)),
+                Token::UnclosedComment => {
+                    // Not closed before the end of the input.
+                    let err = self.syntax_error(SyntaxErrorKind::UnterminatedComment, 1);
+                    self.pos = self.data.len();
+                    return Err(err);
+                }
+                Token::BeginVariation => {
+                    if let Skip(true) = visitor.begin_variation() {
+                        self.skip_variation();
+                    }
+                }
+                Token::EndVariation => visitor.end_variation(),
+                Token::Outcome(outcome) => visitor.outcome(outcome),
+                Token::LineComment => visitor.line_comment(RawComment(self.read_line())),
+                Token::Escape => {
+                    // Leave the `%`.
+                    self.pos -= 1;
+                    self.read_escape_line(visitor);
+                }
+                Token::End => break,
+                Token::Ignored => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn skip_variation(&mut self) {
+        let mut depth = 0usize;
+
+        while let data @ &[_, ..] = self.remaining() {
+            match lexer::skip_token(data) {
+                (SkipToken::BeginVariation, consumed) => {
+                    self.consume(consumed);
+                    depth += 1;
+                }
+                (SkipToken::EndVariation, consumed) => match depth.checked_sub(1) {
+                    Some(d) => {
+                        self.consume(consumed);
+                        depth = d;
+                    }
+                    None => break,
+                },
+                (SkipToken::Comment, consumed) => {
+                    self.consume(consumed);
+                    self.skip_until(b'}');
+                    self.bump();
+                }
+                (SkipToken::LineComment, consumed) => {
+                    self.consume(consumed);
+                    self.skip_until(b'\n');
+                }
+                (SkipToken::End, _) => break,
+                (SkipToken::Ignored, consumed) => self.consume(consumed),
+            }
+        }
+    }
+
+    /// Read a single game, if any.
+    ///
+    /// # Errors
+    ///
+    /// Syntax error in the input. The reader recovers by skipping to the
+    /// next game.
+    pub fn read_game<V: SliceVisitor<'a>>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<Option<V::Result>, SyntaxError> {
+        self.read_game_until(visitor, |_| false)
+    }
+
+    /// Like [`SliceReader::read_game()`], but skips the rest of the
+    /// movetext as soon as `skip_movetext` returns `true`.
+    pub(crate) fn read_game_until<V: SliceVisitor<'a>>(
+        &mut self,
+        visitor: &mut V,
+        skip_movetext: impl Fn(&V) -> bool,
+    ) -> Result<Option<V::Result>, SyntaxError> {
+        self.skip_bom();
+        self.skip_whitespace(visitor);
+
+        if self.remaining().is_empty() {
+            return Ok(None);
+        }
+
+        self.games += 1;
+
+        visitor.begin_tags();
+        self.read_tags(visitor)?;
+        if let Skip(false) = visitor.begin_movetext() {
+            self.read_movetext(visitor, skip_movetext)?;
+        } else {
+            self.skip_movetext();
+        }
+
+        self.skip_whitespace(visitor);
+        Ok(Some(visitor.end_game()))
+    }
+
+    /// Skip a single game, if any.
+    ///
+    /// # Errors
+    ///
+    /// Syntax error in the input.
+    pub fn skip_game(&mut self) -> Result<bool, SyntaxError> {
+        self.read_game(&mut SkipVisitor).map(|r| r.is_some())
+    }
+
+    /// Read all games.
+    ///
+    /// # Errors
+    ///
+    /// Syntax error in the input.
+    pub fn read_all<V: SliceVisitor<'a>>(&mut self, visitor: &mut V) -> Result<(), SyntaxError> {
+        while self.read_game(visitor)?.is_some() {}
+        Ok(())
+    }
+
+    /// Returns whether the reader has another game to parse, but does not
+    /// actually parse it. Escape lines before the game are skipped and not
+    /// reported to any visitor.
+    pub fn has_more(&mut self) -> bool {
+        self.skip_bom();
+        self.skip_whitespace(&mut SkipVisitor);
+        !self.remaining().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use shakmaty::{san::SanPlus, Outcome};
+
+    use super::*;
+    use crate::{error::Error, reader::BufferedReader, types::Nag, visitor::Visitor};
+
+    #[derive(Default)]
+    struct Events {
+        events: Vec<String>,
+        skip: usize,
+    }
+
+    impl Visitor for Events {
+        type Result = ();
+
+        fn begin_tags(&mut self) {
+            self.events.push("begin_tags".to_owned());
+        }
+
+        fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+            self.events
+                .push(format!("tag {:?} {:?}", name, value.as_bytes()));
+        }
+
+        fn begin_movetext(&mut self) -> Skip {
+            self.events.push("begin_movetext".to_owned());
+            Skip(false)
+        }
+
+        fn san(&mut self, san_plus: SanPlus) {
+            self.events.push(san_plus.to_string());
+        }
+
+        fn nag(&mut self, nag: Nag) {
+            self.events.push(nag.to_string());
+        }
+
+        fn comment(&mut self, comment: RawComment<'_>) {
+            self.events.push(format!("{:?}", comment.as_bytes()));
+        }
+
+        fn begin_variation(&mut self) -> Skip {
+            self.events.push("(".to_owned());
+            self.skip += 1;
+            Skip(self.skip & 1 == 0)
+        }
+
+        fn end_variation(&mut self) {
+            self.events.push(")".to_owned());
+        }
+
+        fn outcome(&mut self, outcome: Option<Outcome>) {
+            self.events.push(format!("{outcome:?}"));
+        }
+
+        fn end_game(&mut self) {
+            self.events.push("end_game".to_owned());
+        }
+    }
+
+    fn events(pgn: &[u8]) -> Vec<String> {
+        let mut visitor = Events::default();
+        let mut reader = SliceReader::new(pgn);
+        loop {
+            match reader.read_game(&mut visitor) {
+                Ok(Some(())) => (),
+                Ok(None) => break,
+                Err(err) => visitor.events.push(err.to_string()),
+            }
+        }
+        visitor.events
+    }
+
+    fn buffered_events(pgn: &[u8]) -> Vec<String> {
+        let mut visitor = Events::default();
+        let mut reader = BufferedReader::builder()
+            .max_tag_line_length(1 << 20)
+            .max_comment_length(1 << 20)
+            .build(io::Cursor::new(pgn));
+        loop {
+            match reader.read_game(&mut visitor) {
+                Ok(Some(())) => (),
+                Ok(None) => break,
+                Err(Error::Syntax(err)) => visitor.events.push(err.to_string()),
+                Err(err) => panic!("{err}"),
+            }
+        }
+        visitor.events
+    }
+
+    #[test]
+    fn test_same_as_buffered_reader() {
+        for pgn in [
+            &include_bytes!("../fuzz/corpus/pgn/anastasian-lewis.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/antichess-programfox.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/bare")[..],
+            &include_bytes!("../fuzz/corpus/pgn/cutechess-fischerrandom.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/kasparov-deep-blue-1997.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/knightvuillaume-jannlee-zh-lichess.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/molinari-bordais-1979.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/saturs-jannlee-zh-lichess.pgn")[..],
+            b"\xef\xbb\xbf[Event \"x\"]\n%escape\n1. e4 ; rest of line\ne5 (1... c5 (1... d5) (1... e6 ( ( ) )) 2. Nf3) 0-0-0+ 1/2-1/2\n\n",
+            b"[Event \"unterminated\n\n1. e4 *",
+            b"1. e4 (1. d4 {unterminated",
+        ] {
+            assert_eq!(events(pgn), buffered_events(pgn));
+        }
+    }
+
+    #[test]
+    fn test_borrowed_tags() {
+        struct Players<'a> {
+            white: Option<&'a [u8]>,
+        }
+
+        impl<'a> SliceVisitor<'a> for Players<'a> {
+            type Result = Option<&'a [u8]>;
+
+            fn tag(&mut self, name: &'a [u8], value: RawTag<'a>) {
+                if name == b"White" {
+                    self.white = Some(value.as_bytes());
+                }
+            }
+
+            fn end_game(&mut self) -> Self::Result {
+                self.white.take()
+            }
+        }
+
+        let pgn = b"[White \"Deep Blue\"]\n\n1. e4 *\n\n[White \"Kasparov\"]\n\n1. d4 *";
+        let mut reader = SliceReader::new(pgn);
+        let mut players = Players { white: None };
+        let first = reader.read_game(&mut players).unwrap().unwrap();
+        let second = reader.read_game(&mut players).unwrap().unwrap();
+        assert_eq!(first, Some(&b"Deep Blue"[..]));
+        assert_eq!(second, Some(&b"Kasparov"[..]));
+        assert!(!reader.has_more());
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let mut reader = SliceReader::new(b"1. e4\n\n[Event \"x\"]\n1. d4 {unterminated");
+        let mut visitor = Events::default();
+        assert!(reader.read_game(&mut visitor).unwrap().is_some());
+        let err = reader
+            .read_game(&mut visitor)
+            .expect_err("unterminated comment");
+        assert_eq!(err.kind(), SyntaxErrorKind::UnterminatedComment);
+        assert_eq!(err.offset(), 25);
+        assert_eq!(err.line(), 4);
+        assert_eq!(err.column(), 7);
+        assert_eq!(err.game(), 1);
+    }
+}
@@ -0,0 +1,605 @@
+use std::{error, fmt, time::Duration};
+
+use shakmaty::{fen::Fen, Color, Outcome};
+
+use crate::types::RawTag;
+
+/// Error when a tag value does not have the expected format.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct InvalidTag {
+    expected: &'static str,
+    value: String,
+}
+
+impl InvalidTag {
+    fn new(expected: &'static str, value: &[u8]) -> InvalidTag {
+        InvalidTag {
+            expected,
+            value: String::from_utf8_lossy(value).into_owned(),
+        }
+    }
+
+    /// The tag value that could not be parsed.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for InvalidTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid tag value {:?}, expected {}",
+            self.value, self.expected
+        )
+    }
+}
+
+impl error::Error for InvalidTag {}
+
+/// A date from a `Date`, `EventDate` or `UTCDate` tag, like `2024.03.??`.
+/// Unknown parts are `None`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Date {
+    /// The year.
+    pub year: Option<u16>,
+    /// The month, from 1 to 12.
+    pub month: Option<u8>,
+    /// The day of the month, from 1 to 31.
+    pub day: Option<u8>,
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.year {
+            Some(year) => write!(f, "{year:04}.")?,
+            None => f.write_str("????.")?,
+        }
+        match self.month {
+            Some(month) => write!(f, "{month:02}.")?,
+            None => f.write_str("??.")?,
+        }
+        match self.day {
+            Some(day) => write!(f, "{day:02}"),
+            None => f.write_str("??"),
+        }
+    }
+}
+
+/// The value of a `Round` tag.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Round {
+    /// `?`
+    Unknown,
+    /// `-`, for games that are not part of a round.
+    Inapplicable,
+    /// A round, followed by sub-rounds, like `3.2` for the second game of
+    /// the third round.
+    Numbered(Vec<u32>),
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Round::Unknown => f.write_str("?"),
+            Round::Inapplicable => f.write_str("-"),
+            Round::Numbered(numbers) => {
+                for (i, number) in numbers.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    write!(f, "{number}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A period of a time control.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TimePeriod {
+    /// Number of moves to be played in the period, like `40` in
+    /// `40/7200`. `None` for the rest of the game.
+    pub moves: Option<u32>,
+    /// Time for the period.
+    pub time: Duration,
+    /// Time added after each move, like `2` seconds in `300+2`.
+    pub increment: Duration,
+}
+
+impl fmt::Display for TimePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(moves) = self.moves {
+            write!(f, "{moves}/")?;
+        }
+        write!(f, "{}", self.time.as_secs())?;
+        if !self.increment.is_zero() {
+            write!(f, "+{}", self.increment.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+/// The value of a `TimeControl` tag.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TimeControl {
+    /// `?`
+    Unknown,
+    /// `-`, for games without time control.
+    Unlimited,
+    /// A sandclock with the given time, like `*180`.
+    Sandclock(Duration),
+    /// Consecutive periods separated by `:`, like `40/7200:3600` or
+    /// `300+2`.
+    Periods(Vec<TimePeriod>),
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeControl::Unknown => f.write_str("?"),
+            TimeControl::Unlimited => f.write_str("-"),
+            TimeControl::Sandclock(time) => write!(f, "*{}", time.as_secs()),
+            TimeControl::Periods(periods) => {
+                for (i, period) in periods.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(":")?;
+                    }
+                    period.fmt(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The value of a `Termination` tag.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Termination {
+    /// `abandoned`
+    Abandoned,
+    /// `adjudication`
+    Adjudication,
+    /// `death`
+    Death,
+    /// `emergency`
+    Emergency,
+    /// `normal`
+    Normal,
+    /// `rules infraction`
+    RulesInfraction,
+    /// `time forfeit`
+    TimeForfeit,
+    /// `unterminated`
+    Unterminated,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Termination::Abandoned => "abandoned",
+            Termination::Adjudication => "adjudication",
+            Termination::Death => "death",
+            Termination::Emergency => "emergency",
+            Termination::Normal => "normal",
+            Termination::RulesInfraction => "rules infraction",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Unterminated => "unterminated",
+        })
+    }
+}
+
+/// An opening code from the Encyclopaedia of Chess Openings, like `B12`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Eco {
+    /// The volume, from `A` to `E`.
+    pub volume: char,
+    /// The number within the volume, from 0 to 99.
+    pub number: u8,
+}
+
+impl fmt::Display for Eco {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:02}", self.volume, self.number)
+    }
+}
+
+/// A time of day from a `Time` or `UTCTime` tag, like `14:05:00`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Time {
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 60 (for leap seconds).
+    pub second: u8,
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+/// Parses a number of exactly `digits` digits, or `None` if all of them are
+/// `?`.
+fn date_part(part: &[u8], digits: usize) -> Option<Option<u16>> {
+    if part.len() != digits {
+        None
+    } else if part.iter().all(|&ch| ch == b'?') {
+        Some(None)
+    } else {
+        btoi::btou(part).ok().map(Some)
+    }
+}
+
+fn seconds(s: &[u8]) -> Option<Duration> {
+    btoi::btou(s).ok().map(Duration::from_secs)
+}
+
+fn time_period(s: &[u8]) -> Option<TimePeriod> {
+    let (moves, s) = match memchr::memchr(b'/', s) {
+        Some(slash) => (Some(btoi::btou(&s[..slash]).ok()?), &s[slash + 1..]),
+        None => (None, s),
+    };
+    let (time, increment) = match memchr::memchr(b'+', s) {
+        Some(plus) => (seconds(&s[..plus])?, seconds(&s[plus + 1..])?),
+        None => (seconds(s)?, Duration::ZERO),
+    };
+    Some(TimePeriod {
+        moves,
+        time,
+        increment,
+    })
+}
+
+impl RawTag<'_> {
+    fn parse<T>(
+        &self,
+        expected: &'static str,
+        f: impl FnOnce(&[u8]) -> Option<T>,
+    ) -> Result<T, InvalidTag> {
+        f(self.as_bytes()).ok_or_else(|| InvalidTag::new(expected, self.as_bytes()))
+    }
+
+    /// Parses the value of a `Date`, `EventDate` or `UTCDate` tag, like
+    /// `2024.03.17` or `2024.??.??`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::{Date, RawTag};
+    ///
+    /// let date = RawTag(b"2024.03.??").date()?;
+    /// assert_eq!(date, Date { year: Some(2024), month: Some(3), day: None });
+    ///
+    /// assert!(RawTag(b"2024.13.01").date().is_err());
+    /// # Ok::<_, pgn_reader::InvalidTag>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not of the form `YYYY.MM.DD`, where each
+    /// part is either a number in range or all `?`.
+    pub fn date(&self) -> Result<Date, InvalidTag> {
+        self.parse("date like YYYY.MM.DD or ????.??.??", |s| {
+            let mut parts = s.split(|&ch| ch == b'.');
+            let year = date_part(parts.next()?, 4)?;
+            let month = date_part(parts.next()?, 2)?.map(|month| month as u8);
+            let day = date_part(parts.next()?, 2)?.map(|day| day as u8);
+            if parts.next().is_some()
+                || month.is_some_and(|month| !(1..=12).contains(&month))
+                || day.is_some_and(|day| !(1..=31).contains(&day))
+            {
+                return None;
+            }
+            Some(Date { year, month, day })
+        })
+    }
+
+    /// Parses the value of a `Round` tag, like `3`, `3.2`, `?` or `-`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is neither `?`, `-`, nor numbers separated by
+    /// `.`.
+    pub fn round(&self) -> Result<Round, InvalidTag> {
+        self.parse("round like 3 or 3.2, ? or -", |s| match s {
+            b"?" => Some(Round::Unknown),
+            b"-" => Some(Round::Inapplicable),
+            _ => s
+                .split(|&ch| ch == b'.')
+                .map(|part| btoi::btou(part).ok())
+                .collect::<Option<_>>()
+                .map(Round::Numbered),
+        })
+    }
+
+    /// Parses the value of a `WhiteElo` or `BlackElo` tag. Returns `None`
+    /// for unrated players, given as `?`, `-` or an empty value.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not a number.
+    pub fn elo(&self) -> Result<Option<u16>, InvalidTag> {
+        self.parse("rating like 2800, or ?", |s| match s {
+            b"" | b"?" | b"-" => Some(None),
+            _ => btoi::btou(s).ok().map(Some),
+        })
+    }
+
+    /// Parses the value of a `Result` tag. Returns `None` for `*`, an
+    /// unknown or ongoing game.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::{Color, Outcome, RawTag};
+    ///
+    /// assert_eq!(
+    ///     RawTag(b"0-1").result()?,
+    ///     Some(Outcome::Decisive { winner: Color::Black })
+    /// );
+    /// assert_eq!(RawTag(b"*").result()?, None);
+    /// # Ok::<_, pgn_reader::InvalidTag>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not one of `1-0`, `0-1`, `1/2-1/2` or `*`.
+    pub fn result(&self) -> Result<Option<Outcome>, InvalidTag> {
+        self.parse("1-0, 0-1, 1/2-1/2 or *", |s| match s {
+            b"1-0" => Some(Some(Outcome::Decisive {
+                winner: Color::White,
+            })),
+            b"0-1" => Some(Some(Outcome::Decisive {
+                winner: Color::Black,
+            })),
+            b"1/2-1/2" => Some(Some(Outcome::Draw)),
+            b"*" => Some(None),
+            _ => None,
+        })
+    }
+
+    /// Parses the value of a `TimeControl` tag, with times in seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use pgn_reader::{RawTag, TimeControl, TimePeriod};
+    ///
+    /// assert_eq!(
+    ///     RawTag(b"40/7200:3600").time_control()?,
+    ///     TimeControl::Periods(vec![
+    ///         TimePeriod {
+    ///             moves: Some(40),
+    ///             time: Duration::from_secs(7200),
+    ///             increment: Duration::ZERO,
+    ///         },
+    ///         TimePeriod {
+    ///             moves: None,
+    ///             time: Duration::from_secs(3600),
+    ///             increment: Duration::ZERO,
+    ///         },
+    ///     ])
+    /// );
+    /// assert_eq!(RawTag(b"-").time_control()?, TimeControl::Unlimited);
+    /// # Ok::<_, pgn_reader::InvalidTag>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is neither `?`, `-`, a sandclock like `*180`,
+    /// nor periods like `40/7200`, `300+2` or `3600`, separated by `:`.
+    pub fn time_control(&self) -> Result<TimeControl, InvalidTag> {
+        self.parse(
+            "time control like 40/7200:3600, 300+2, ? or -",
+            |s| match s {
+                b"?" => Some(TimeControl::Unknown),
+                b"-" => Some(TimeControl::Unlimited),
+                [b'*', time @ ..] => seconds(time).map(TimeControl::Sandclock),
+                _ => s
+                    .split(|&ch| ch == b':')
+                    .map(time_period)
+                    .collect::<Option<_>>()
+                    .map(TimeControl::Periods),
+            },
+        )
+    }
+
+    /// Parses the value of a `Termination` tag. The comparison is
+    /// case-insensitive, so that `Time forfeit` is also accepted.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not one of the terminations defined by the
+    /// PGN standard.
+    pub fn termination(&self) -> Result<Termination, InvalidTag> {
+        self.parse("termination like normal or time forfeit", |s| {
+            Some(match s.to_ascii_lowercase().as_slice() {
+                b"abandoned" => Termination::Abandoned,
+                b"adjudication" => Termination::Adjudication,
+                b"death" => Termination::Death,
+                b"emergency" => Termination::Emergency,
+                b"normal" => Termination::Normal,
+                b"rules infraction" => Termination::RulesInfraction,
+                b"time forfeit" => Termination::TimeForfeit,
+                b"unterminated" => Termination::Unterminated,
+                _ => return None,
+            })
+        })
+    }
+
+    /// Parses the value of an `ECO` tag, like `B12`. Returns `None` for `?`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not a letter from `A` to `E`, followed by two
+    /// digits.
+    pub fn eco(&self) -> Result<Option<Eco>, InvalidTag> {
+        self.parse("opening code like B12, or ?", |s| match *s {
+            [b'?'] => Some(None),
+            [volume @ b'A'..=b'E', tens @ b'0'..=b'9', ones @ b'0'..=b'9'] => Some(Some(Eco {
+                volume: char::from(volume),
+                number: (tens - b'0') * 10 + (ones - b'0'),
+            })),
+            _ => None,
+        })
+    }
+
+    /// Parses the value of a `PlyCount` tag.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not a number.
+    pub fn ply_count(&self) -> Result<u32, InvalidTag> {
+        self.parse("number of plies", |s| btoi::btou(s).ok())
+    }
+
+    /// Parses the value of a `SetUp` tag, `0` or `1`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is neither `0` nor `1`.
+    pub fn setup(&self) -> Result<bool, InvalidTag> {
+        self.parse("0 or 1", |s| match s {
+            b"0" => Some(false),
+            b"1" => Some(true),
+            _ => None,
+        })
+    }
+
+    /// Parses the value of a `FEN` tag.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not syntactically valid FEN. Whether the
+    /// position is legal is not checked.
+    pub fn fen(&self) -> Result<Fen, InvalidTag> {
+        self.parse("fen", |s| Fen::from_ascii(s).ok())
+    }
+
+    /// Parses the value of a `Time` or `UTCTime` tag, like `14:05:00`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the value is not of the form `HH:MM:SS` with each part in
+    /// range.
+    pub fn time(&self) -> Result<Time, InvalidTag> {
+        self.parse("time like HH:MM:SS", |s| match *s {
+            [h1, h2, b':', m1, m2, b':', s1, s2] => {
+                let hour = btoi::btou(&[h1, h2]).ok()?;
+                let minute = btoi::btou(&[m1, m2]).ok()?;
+                let second = btoi::btou(&[s1, s2]).ok()?;
+                (hour < 24 && minute < 60 && second <= 60).then_some(Time {
+                    hour,
+                    minute,
+                    second,
+                })
+            }
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(
+            RawTag(b"????.??.??").date(),
+            Ok(Date {
+                year: None,
+                month: None,
+                day: None
+            })
+        );
+        assert_eq!(
+            RawTag(b"1997.05.11").date().unwrap().to_string(),
+            "1997.05.11"
+        );
+        for invalid in [
+            &b"1997.5.11"[..],
+            b"1997.05.32",
+            b"1997-05-11",
+            b"1997.05.11.",
+            b"",
+        ] {
+            assert!(RawTag(invalid).date().is_err());
+        }
+    }
+
+    #[test]
+    fn test_round_and_numbers() {
+        assert_eq!(RawTag(b"3.2").round(), Ok(Round::Numbered(vec![3, 2])));
+        assert_eq!(RawTag(b"-").round(), Ok(Round::Inapplicable));
+        assert!(RawTag(b"3.").round().is_err());
+
+        assert_eq!(RawTag(b"2851").elo(), Ok(Some(2851)));
+        assert_eq!(RawTag(b"?").elo(), Ok(None));
+        assert!(RawTag(b"2851?").elo().is_err());
+
+        assert_eq!(RawTag(b"B12").eco().unwrap().unwrap().to_string(), "B12");
+        assert!(RawTag(b"F00").eco().is_err());
+
+        assert_eq!(RawTag(b"87").ply_count(), Ok(87));
+        assert_eq!(RawTag(b"1").setup(), Ok(true));
+        assert!(RawTag(b"yes").setup().is_err());
+    }
+
+    #[test]
+    fn test_time_control() {
+        assert_eq!(
+            RawTag(b"300+2").time_control(),
+            Ok(TimeControl::Periods(vec![TimePeriod {
+                moves: None,
+                time: Duration::from_secs(300),
+                increment: Duration::from_secs(2),
+            }]))
+        );
+        assert_eq!(
+            RawTag(b"*180").time_control(),
+            Ok(TimeControl::Sandclock(Duration::from_secs(180)))
+        );
+        for tc in ["?", "-", "40/7200:3600", "40/5400+30:1800+30"] {
+            assert_eq!(
+                RawTag(tc.as_bytes()).time_control().unwrap().to_string(),
+                tc
+            );
+        }
+        for invalid in [&b"300+"[..], b"40/", b"5 min", b":", b""] {
+            assert!(RawTag(invalid).time_control().is_err());
+        }
+    }
+
+    #[test]
+    fn test_misc() {
+        assert_eq!(
+            RawTag(b"Time forfeit").termination(),
+            Ok(Termination::TimeForfeit)
+        );
+        assert!(RawTag(b"resigned").termination().is_err());
+
+        assert_eq!(
+            RawTag(b"23:59:60").time(),
+            Ok(Time {
+                hour: 23,
+                minute: 59,
+                second: 60
+            })
+        );
+        assert!(RawTag(b"24:00:00").time().is_err());
+
+        assert!(RawTag(b"8/8/8/8/8/8/8/8 w - - 0 1").fen().is_ok());
+        let err = RawTag(b"1/2").result().unwrap_err();
+        assert_eq!(err.value(), "1/2");
+        assert_eq!(
+            err.to_string(),
+            "invalid tag value \"1/2\", expected 1-0, 0-1, 1/2-1/2 or *"
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use std::{cmp::min, mem, ops::Range};
+
+use crate::lexer::{self, Token};
+
+/// The kind of a [`SyntaxToken`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum TokenKind {
+    /// A UTF-8 byte order mark at the start of the input.
+    Bom,
+    /// A run of spaces, tabs and line breaks.
+    Whitespace,
+    /// A `%` escape line, excluding the line break.
+    Escape,
+    /// A `;` comment, excluding the line break.
+    LineComment,
+    /// The `[` that opens a tag pair, at the start of a game or after
+    /// another tag pair.
+    TagOpen,
+    /// The name of a tag pair.
+    TagName,
+    /// The value of a tag pair, including the quotes.
+    TagValue,
+    /// The `]` that closes a tag pair.
+    TagClose,
+    /// A move number like `12.` or `12...`.
+    MoveNumber,
+    /// A move in standard algebraic notation, including `+` or `#`.
+    San,
+    /// A numeric annotation glyph like `$12`, or a move suffix like `!?`.
+    Nag,
+    /// A `{ comment }`, including the braces. An unclosed comment extends
+    /// to the end of the input.
+    Comment,
+    /// `(`
+    BeginVariation,
+    /// `)`
+    EndVariation,
+    /// `1-0`, `0-1`, `1/2-1/2` or `*`.
+    Outcome,
+    /// Anything that is not recognized, and ignored by the readers.
+    Junk,
+}
+
+/// A token with its byte range in the input.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SyntaxToken<'a> {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+    pub text: &'a [u8],
+}
+
+/// A lossless tokenizer for in-memory input, for tools like formatters and
+/// linters that need to preserve everything the readers skip.
+///
+/// Every byte of the input belongs to exactly one token, including move
+/// numbers, whitespace, line comments, escape lines and junk. Joining the
+/// tokens reproduces the input byte for byte.
+///
+/// Game boundaries are found the same way the readers find them, so a `[`
+/// in the movetext is [`TokenKind::Junk`], not [`TokenKind::TagOpen`].
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::{Tokenizer, TokenKind};
+///
+/// let pgn = b"1. e4 ; king's pawn\n1... e5 *";
+///
+/// let kinds: Vec<_> = Tokenizer::new(pgn).map(|token| token.kind).collect();
+/// assert_eq!(kinds, [
+///     TokenKind::MoveNumber,
+///     TokenKind::Whitespace,
+///     TokenKind::San,
+///     TokenKind::Whitespace,
+///     TokenKind::LineComment,
+///     TokenKind::Whitespace,
+///     TokenKind::MoveNumber,
+///     TokenKind::Whitespace,
+///     TokenKind::San,
+///     TokenKind::Whitespace,
+///     TokenKind::Outcome,
+/// ]);
+///
+/// let text: Vec<u8> = Tokenizer::new(pgn).flat_map(|token| token.text.iter().copied()).collect();
+/// assert_eq!(text, pgn);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    section: Section,
+    // Between `[` and the end of the tag pair.
+    in_tag: bool,
+    // The line break after an escape line belongs to the escape line.
+    after_escape: bool,
+}
+
+/// Where the readers would be in the current game, to tell tag pairs from
+/// `[` in the movetext.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Section {
+    /// Before the first tag pair of a game.
+    GameStart,
+    /// After a tag pair, on the same line.
+    Ket,
+    /// At the start of a line in the tag section.
+    TagLine,
+    /// In the movetext, until the next game boundary.
+    Movetext,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Creates a tokenizer for the given input.
+    pub fn new(data: &'a [u8]) -> Tokenizer<'a> {
+        Tokenizer {
+            data,
+            pos: 0,
+            section: Section::GameStart,
+            in_tag: false,
+            after_escape: false,
+        }
+    }
+
+    /// Follows the readers through a run of whitespace starting at `pos`.
+    fn skip_whitespace(&mut self, pos: usize, len: usize) {
+        for i in pos..pos + len {
+            let ch = self.data[i];
+            if ch == b'\n' && mem::take(&mut self.after_escape) {
+                continue;
+            }
+            if ch == b'\n' && self.in_tag {
+                self.in_tag = false;
+                self.section = Section::Ket;
+            }
+            match self.section {
+                Section::GameStart => (),
+                Section::Ket if ch == b'\n' => self.section = Section::TagLine,
+                Section::Ket => (),
+                Section::TagLine | Section::Movetext => {
+                    // Whitespace ends the tag section. A line break followed
+                    // by `[` or a blank line ends the movetext.
+                    self.section = Section::Movetext;
+                    let rest = &self.data[i + 1..];
+                    if ch == b'\n'
+                        && (rest.starts_with(b"[")
+                            || rest.starts_with(b"\n")
+                            || rest.starts_with(b"\r\n"))
+                    {
+                        self.section = Section::GameStart;
+                    }
+                }
+            }
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.pos == 0 || self.data[self.pos - 1] == b'\n'
+    }
+
+    fn rest_of_line(data: &[u8]) -> usize {
+        memchr::memchr(b'\n', data).unwrap_or(data.len())
+    }
+
+    fn tag_token(&mut self, data: &[u8]) -> (TokenKind, usize) {
+        match data[0] {
+            b'"' => {
+                let mut pos = 1;
+                let len = loop {
+                    match memchr::memchr3(b'\\', b'"', b'\n', &data[pos..]) {
+                        Some(delta) if data[pos + delta] == b'\\' => {
+                            // Skip escaped character.
+                            pos = min(pos + delta + 2, data.len());
+                        }
+                        Some(delta) if data[pos + delta] == b'"' => break pos + delta + 1,
+                        Some(delta) => break pos + delta,
+                        None => break data.len(),
+                    }
+                };
+                (TokenKind::TagValue, len)
+            }
+            b']' => {
+                self.in_tag = false;
+                self.section = Section::Ket;
+                (TokenKind::TagClose, 1)
+            }
+            _ => (
+                TokenKind::TagName,
+                data.iter()
+                    .position(|&ch| matches!(ch, b' ' | b'\t' | b'\r' | b'\n' | b'"' | b']'))
+                    .unwrap_or(data.len()),
+            ),
+        }
+    }
+
+    fn movetext_token(&self, data: &[u8]) -> (TokenKind, usize) {
+        let (token, consumed) = lexer::token(data, usize::MAX);
+        match token {
+            Token::MoveNumber(_) => (TokenKind::MoveNumber, consumed),
+            Token::San(_) => (TokenKind::San, consumed),
+            Token::Nag(_) => (TokenKind::Nag, consumed),
+            Token::Comment(_) => (TokenKind::Comment, consumed),
+            Token::UnclosedComment => (TokenKind::Comment, data.len()),
+            Token::BeginVariation => (TokenKind::BeginVariation, consumed),
+            Token::EndVariation => (TokenKind::EndVariation, consumed),
+            Token::Outcome(_) => (TokenKind::Outcome, consumed),
+            Token::Invalid(_) => (TokenKind::Junk, consumed),
+            Token::LineComment => (TokenKind::LineComment, Self::rest_of_line(data)),
+            Token::Escape | Token::End | Token::Ignored if data[0].is_ascii_digit() => {
+                (TokenKind::MoveNumber, lexer::skip_move_number(data, 0))
+            }
+            Token::Escape | Token::End | Token::Ignored => (TokenKind::Junk, consumed.max(1)),
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = SyntaxToken<'a>;
+
+    fn next(&mut self) -> Option<SyntaxToken<'a>> {
+        let data = &self.data[self.pos..];
+        let first = *data.first()?;
+
+        let (kind, len) = if self.pos == 0 && data.starts_with(b"\xef\xbb\xbf") {
+            (TokenKind::Bom, 3)
+        } else if matches!(first, b' ' | b'\t' | b'\r' | b'\n') {
+            let len = data
+                .iter()
+                .position(|&ch| !matches!(ch, b' ' | b'\t' | b'\r' | b'\n'))
+                .unwrap_or(data.len());
+            self.skip_whitespace(self.pos, len);
+            (TokenKind::Whitespace, len)
+        } else if first == b'%' && self.at_line_start() {
+            if self.in_tag {
+                self.in_tag = false;
+                self.section = Section::TagLine;
+            }
+            self.after_escape = true;
+            (TokenKind::Escape, Self::rest_of_line(data))
+        } else if self.in_tag {
+            self.tag_token(data)
+        } else if first == b'[' && self.section != Section::Movetext {
+            self.in_tag = true;
+            (TokenKind::TagOpen, 1)
+        } else if first == b']' && self.section == Section::Ket {
+            (TokenKind::Junk, 1)
+        } else {
+            self.section = Section::Movetext;
+            self.movetext_token(data)
+        };
+
+        let span = self.pos..self.pos + len;
+        self.pos += len;
+        Some(SyntaxToken {
+            kind,
+            text: &self.data[span.clone()],
+            span,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn tokens(data: &[u8]) -> Vec<(TokenKind, &str)> {
+        Tokenizer::new(data)
+            .map(|token| (token.kind, std::str::from_utf8(token.text).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lossless() {
+        let mut inputs: Vec<Vec<u8>> = fs::read_dir("fuzz/corpus/pgn")
+            .unwrap()
+            .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+            .collect();
+        inputs.push(b"\xef\xbb\xbf[A \"unterminated\n% escape\n[B \"\\\"\"".to_vec());
+        inputs.push(b"1. e4 { unclosed".to_vec());
+
+        for data in inputs {
+            let mut joined = Vec::new();
+            let mut end = 0;
+            for token in Tokenizer::new(&data) {
+                assert!(!token.text.is_empty());
+                assert_eq!(token.span.start, end);
+                end = token.span.end;
+                joined.extend_from_slice(token.text);
+            }
+            assert_eq!(joined, data);
+        }
+    }
+
+    #[test]
+    fn test_kinds() {
+        use TokenKind::*;
+
+        assert_eq!(
+            tokens(b"[Event \"A \\\"B\\\"\"]]\n%x\n\n12... Nxe4+!? $5 (0-0-0) ?? {c} 1/2-1/2 &"),
+            [
+                (TagOpen, "["),
+                (TagName, "Event"),
+                (Whitespace, " "),
+                (TagValue, "\"A \\\"B\\\"\""),
+                (TagClose, "]"),
+                (Junk, "]"),
+                (Whitespace, "\n"),
+                (Escape, "%x"),
+                (Whitespace, "\n\n"),
+                (MoveNumber, "12..."),
+                (Whitespace, " "),
+                (San, "Nxe4+"),
+                (Nag, "!?"),
+                (Whitespace, " "),
+                (Nag, "$5"),
+                (Whitespace, " "),
+                (BeginVariation, "("),
+                (San, "0-0-0"),
+                (EndVariation, ")"),
+                (Whitespace, " "),
+                (Nag, "??"),
+                (Whitespace, " "),
+                (Comment, "{c}"),
+                (Whitespace, " "),
+                (Outcome, "1/2-1/2"),
+                (Whitespace, " "),
+                (Junk, "&"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bracket_in_movetext() {
+        use TokenKind::*;
+
+        assert_eq!(
+            tokens(b"[A \"1\"]\n1. e4 [%clk 0:01]\n[B \"2\"]\n\n1. d4 [x] *"),
+            [
+                (TagOpen, "["),
+                (TagName, "A"),
+                (Whitespace, " "),
+                (TagValue, "\"1\""),
+                (TagClose, "]"),
+                (Whitespace, "\n"),
+                (MoveNumber, "1."),
+                (Whitespace, " "),
+                (San, "e4"),
+                (Whitespace, " "),
+                (Junk, "["),
+                (Junk, "%"),
+                (Junk, "clk"),
+                (Whitespace, " "),
+                (Junk, "0:01]"),
+                (Whitespace, "\n"),
+                (TagOpen, "["),
+                (TagName, "B"),
+                (Whitespace, " "),
+                (TagValue, "\"2\""),
+                (TagClose, "]"),
+                (Whitespace, "\n\n"),
+                (MoveNumber, "1."),
+                (Whitespace, " "),
+                (San, "d4"),
+                (Whitespace, " "),
+                (Junk, "["),
+                (Junk, "x"),
+                (Junk, "]"),
+                (Whitespace, " "),
+                (Outcome, "*"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,548 @@
+use std::{error, fmt, io::Read, iter::FusedIterator, ops::ControlFlow};
+
+use shakmaty::{san::SanPlus, Outcome};
+
+use crate::{
+    error::Error,
+    reader::BufferedReader,
+    slice::SliceReader,
+    types::{InvalidTokenKind, MoveNumber, Nag, RawComment, RawTag, Skip, TokenAction},
+    visitor::Visitor,
+};
+
+/// Tell the reader to stop visiting the current game. See [`TryVisitor`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum Break<E> {
+    /// Skip the rest of the current game. The rest of the movetext is not
+    /// parsed, and the game is still finished with
+    /// [`TryVisitor::end_game()`].
+    SkipGame,
+    /// Skip the rest of the current game without calling
+    /// [`TryVisitor::end_game()`], and stop reading, as if the input ended
+    /// after the game. All further attempts to read games with a
+    /// [`TryVisitor`] from the same reader return `None`.
+    Stop,
+    /// Skip the rest of the current game without calling
+    /// [`TryVisitor::end_game()`], and return the error. Reading can
+    /// continue with the next game.
+    Error(E),
+}
+
+/// Error when reading games with a [`TryVisitor`].
+#[derive(Debug)]
+pub enum TryError<E> {
+    /// Error from the reader.
+    Reader(Error),
+    /// Error from the visitor.
+    Visitor(E),
+}
+
+impl<E: fmt::Display> fmt::Display for TryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryError::Reader(err) => err.fmt(f),
+            TryError::Visitor(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for TryError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TryError::Reader(err) => Some(err),
+            TryError::Visitor(err) => Some(err),
+        }
+    }
+}
+
+impl<E> From<Error> for TryError<E> {
+    fn from(err: Error) -> TryError<E> {
+        TryError::Reader(err)
+    }
+}
+
+/// Like [`Visitor`], but each method can break out of the current game,
+/// stop reading, or fail with an error.
+///
+/// Use with [`BufferedReader::try_read_game()`],
+/// [`BufferedReader::try_read_all()`],
+/// [`BufferedReader::try_into_iter()`], or
+/// [`SliceReader::try_read_game()`].
+///
+/// After a break, no further methods are called for the current game
+/// (except [`TryVisitor::end_game()`] for [`Break::SkipGame`]), and the
+/// reader skips to the end of the game without parsing the rest of the
+/// movetext.
+///
+/// # Examples
+///
+/// ```
+/// use std::ops::ControlFlow;
+/// use pgn_reader::{Break, BufferedReader, TryError, TryVisitor};
+///
+/// struct Limit {
+///     games: usize,
+/// }
+///
+/// impl TryVisitor for Limit {
+///     type Result = ();
+///     type Error = &'static str;
+///
+///     fn begin_tags(&mut self) -> ControlFlow<Break<&'static str>> {
+///         if self.games >= 2 {
+///             return ControlFlow::Break(Break::Error("too many games"));
+///         }
+///         self.games += 1;
+///         ControlFlow::Continue(())
+///     }
+///
+///     fn end_game(&mut self) -> Result<(), &'static str> {
+///         Ok(())
+///     }
+/// }
+///
+/// let pgn = b"1. e4 *\n\n1. d4 *\n\n1. c4 *\n";
+///
+/// let mut reader = BufferedReader::new(&pgn[..]);
+/// let mut limit = Limit { games: 0 };
+/// assert!(matches!(
+///     reader.try_read_all(&mut limit),
+///     Err(TryError::Visitor("too many games"))
+/// ));
+/// assert_eq!(limit.games, 2);
+/// ```
+pub trait TryVisitor {
+    /// Value produced by the visitor after reading a game.
+    type Result;
+    /// Error produced by the visitor.
+    type Error;
+
+    /// See [`Visitor::begin_tags()`].
+    fn begin_tags(&mut self) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::tag()`].
+    fn tag(&mut self, _name: &[u8], _value: RawTag<'_>) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::begin_movetext()`].
+    fn begin_movetext(&mut self) -> ControlFlow<Break<Self::Error>, Skip> {
+        ControlFlow::Continue(Skip(false))
+    }
+    /// See [`Visitor::move_number()`].
+    fn move_number(&mut self, _move_number: MoveNumber) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::san()`].
+    fn san(&mut self, _san_plus: SanPlus) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::invalid_token()`].
+    fn invalid_token(
+        &mut self,
+        _token: &[u8],
+        _kind: InvalidTokenKind,
+    ) -> ControlFlow<Break<Self::Error>, TokenAction> {
+        ControlFlow::Continue(TokenAction::Skip)
+    }
+    /// See [`Visitor::nag()`].
+    fn nag(&mut self, _nag: Nag) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::comment()`].
+    fn comment(&mut self, _comment: RawComment<'_>) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::begin_long_comment()`].
+    fn begin_long_comment(&mut self) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::partial_comment()`].
+    fn partial_comment(&mut self, _chunk: RawComment<'_>) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::end_long_comment()`].
+    fn end_long_comment(&mut self) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::line_comment()`].
+    fn line_comment(&mut self, _comment: RawComment<'_>) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::escape_line()`].
+    fn escape_line(&mut self, _line: &[u8]) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::begin_variation()`].
+    fn begin_variation(&mut self) -> ControlFlow<Break<Self::Error>, Skip> {
+        ControlFlow::Continue(Skip(false))
+    }
+    /// See [`Visitor::end_variation()`].
+    fn end_variation(&mut self) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+    /// See [`Visitor::outcome()`].
+    fn outcome(&mut self, _outcome: Option<Outcome>) -> ControlFlow<Break<Self::Error>> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after parsing a game, unless it was stopped. Can produce a
+    /// custom result, or fail.
+    fn end_game(&mut self) -> Result<Self::Result, Self::Error>;
+}
+
+/// How a game ended for a [`TryVisitor`].
+enum Finished<R, E> {
+    Game(Result<R, E>),
+    Stop,
+}
+
+/// Drives a [`TryVisitor`] from the [`Visitor`] callbacks of a reader.
+struct TryAdapter<'a, V: TryVisitor> {
+    visitor: &'a mut V,
+    broken: Option<Break<V::Error>>,
+}
+
+impl<'a, V: TryVisitor> TryAdapter<'a, V> {
+    fn new(visitor: &'a mut V) -> TryAdapter<'a, V> {
+        TryAdapter {
+            visitor,
+            broken: None,
+        }
+    }
+
+    fn skipping(&self) -> bool {
+        self.broken.is_some()
+    }
+
+    fn call<T>(
+        &mut self,
+        default: T,
+        f: impl FnOnce(&mut V) -> ControlFlow<Break<V::Error>, T>,
+    ) -> T {
+        if self.broken.is_some() {
+            return default;
+        }
+        match f(self.visitor) {
+            ControlFlow::Continue(value) => value,
+            ControlFlow::Break(broken) => {
+                self.broken = Some(broken);
+                default
+            }
+        }
+    }
+}
+
+impl<V: TryVisitor> Visitor for TryAdapter<'_, V> {
+    type Result = Finished<V::Result, V::Error>;
+
+    fn begin_tags(&mut self) {
+        self.call((), |v| v.begin_tags());
+    }
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        self.call((), |v| v.tag(name, value));
+    }
+    fn begin_movetext(&mut self) -> Skip {
+        self.call(Skip(true), |v| v.begin_movetext())
+    }
+    fn move_number(&mut self, move_number: MoveNumber) {
+        self.call((), |v| v.move_number(move_number));
+    }
+    fn san(&mut self, san_plus: SanPlus) {
+        self.call((), |v| v.san(san_plus));
+    }
+    fn invalid_token(&mut self, token: &[u8], kind: InvalidTokenKind) -> TokenAction {
+        self.call(TokenAction::Skip, |v| v.invalid_token(token, kind))
+    }
+    fn nag(&mut self, nag: Nag) {
+        self.call((), |v| v.nag(nag));
+    }
+    fn comment(&mut self, comment: RawComment<'_>) {
+        self.call((), |v| v.comment(comment));
+    }
+    fn begin_long_comment(&mut self) {
+        self.call((), |v| v.begin_long_comment());
+    }
+    fn partial_comment(&mut self, chunk: RawComment<'_>) {
+        self.call((), |v| v.partial_comment(chunk));
+    }
+    fn end_long_comment(&mut self) {
+        self.call((), |v| v.end_long_comment());
+    }
+    fn line_comment(&mut self, comment: RawComment<'_>) {
+        self.call((), |v| v.line_comment(comment));
+    }
+    fn escape_line(&mut self, line: &[u8]) {
+        self.call((), |v| v.escape_line(line));
+    }
+    fn begin_variation(&mut self) -> Skip {
+        self.call(Skip(true), |v| v.begin_variation())
+    }
+    fn end_variation(&mut self) {
+        self.call((), |v| v.end_variation());
+    }
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.call((), |v| v.outcome(outcome));
+    }
+    fn end_game(&mut self) -> Self::Result {
+        match self.broken.take() {
+            None | Some(Break::SkipGame) => Finished::Game(self.visitor.end_game()),
+            Some(Break::Stop) => Finished::Stop,
+            Some(Break::Error(err)) => Finished::Game(Err(err)),
+        }
+    }
+}
+
+/// Converts the result of a game, and latches `stopped` if the visitor
+/// stopped reading.
+fn finish<R, E>(
+    result: Result<Option<Finished<R, E>>, Error>,
+    stopped: &mut bool,
+) -> Result<Option<R>, TryError<E>> {
+    match result? {
+        Some(Finished::Game(Ok(result))) => Ok(Some(result)),
+        Some(Finished::Game(Err(err))) => Err(TryError::Visitor(err)),
+        Some(Finished::Stop) => {
+            *stopped = true;
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+impl<R: Read> BufferedReader<R> {
+    /// Read a single game, if any, with a [`TryVisitor`]. Returns `None`
+    /// at the end of the input, or once the visitor has stopped reading.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    /// * Error from the visitor.
+    pub fn try_read_game<V: TryVisitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<Option<V::Result>, TryError<V::Error>> {
+        if self.stopped {
+            return Ok(None);
+        }
+        let result = self.read_game_until(&mut TryAdapter::new(visitor), TryAdapter::skipping);
+        finish(result, &mut self.stopped)
+    }
+
+    /// Read all games with a [`TryVisitor`], until the end of the input or
+    /// until the visitor stops reading.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    /// * Error from the visitor.
+    pub fn try_read_all<V: TryVisitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<(), TryError<V::Error>> {
+        while self.try_read_game(visitor)?.is_some() {}
+        Ok(())
+    }
+
+    /// Create an iterator over all games, using a [`TryVisitor`]. The
+    /// iterator ends when the visitor stops reading.
+    pub fn try_into_iter<V: TryVisitor>(self, visitor: &mut V) -> TryIntoIter<'_, V, R> {
+        TryIntoIter {
+            reader: self,
+            visitor,
+        }
+    }
+}
+
+/// Iterator returned by [`BufferedReader::try_into_iter()`].
+#[derive(Debug)]
+#[must_use]
+pub struct TryIntoIter<'a, V: 'a, R> {
+    visitor: &'a mut V,
+    reader: BufferedReader<R>,
+}
+
+impl<V: TryVisitor, R: Read> Iterator for TryIntoIter<'_, V, R> {
+    type Item = Result<V::Result, TryError<V::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.try_read_game(self.visitor).transpose()
+    }
+}
+
+impl<V: TryVisitor, R: Read> FusedIterator for TryIntoIter<'_, V, R> {}
+
+impl SliceReader<'_> {
+    /// Read a single game, if any, with a [`TryVisitor`]. Returns `None`
+    /// at the end of the input, or once the visitor has stopped reading.
+    ///
+    /// # Errors
+    ///
+    /// * Syntax error in the input. The reader recovers by skipping to the
+    ///   next game.
+    /// * Error from the visitor.
+    pub fn try_read_game<V: TryVisitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<Option<V::Result>, TryError<V::Error>> {
+        if self.stopped {
+            return Ok(None);
+        }
+        let result = self
+            .read_game_until(&mut TryAdapter::new(visitor), TryAdapter::skipping)
+            .map_err(Error::Syntax);
+        finish(result, &mut self.stopped)
+    }
+
+    /// Read all games with a [`TryVisitor`], until the end of the input or
+    /// until the visitor stops reading.
+    ///
+    /// # Errors
+    ///
+    /// * Syntax error in the input.
+    /// * Error from the visitor.
+    pub fn try_read_all<V: TryVisitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<(), TryError<V::Error>> {
+        while self.try_read_game(visitor)?.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: futures_io::AsyncRead + Unpin> crate::AsyncBufferedReader<R> {
+    /// Read a single game, if any, with a [`TryVisitor`]. Returns `None`
+    /// at the end of the input, or once the visitor has stopped reading.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    /// * Error from the visitor.
+    pub async fn try_read_game<V: TryVisitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<Option<V::Result>, TryError<V::Error>> {
+        if self.stopped {
+            return Ok(None);
+        }
+        let result = self
+            .read_game_until(&mut TryAdapter::new(visitor), TryAdapter::skipping)
+            .await;
+        finish(result, &mut self.stopped)
+    }
+
+    /// Read all games with a [`TryVisitor`], until the end of the input or
+    /// until the visitor stops reading.
+    ///
+    /// # Errors
+    ///
+    /// * I/O error from the underlying reader.
+    /// * Irrecoverable parser errors.
+    /// * Error from the visitor.
+    pub async fn try_read_all<V: TryVisitor>(
+        &mut self,
+        visitor: &mut V,
+    ) -> Result<(), TryError<V::Error>> {
+        while self.try_read_game(visitor).await?.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Moves {
+        moves: Vec<String>,
+        games: usize,
+    }
+
+    impl TryVisitor for Moves {
+        type Result = Vec<String>;
+        type Error = String;
+
+        fn begin_tags(&mut self) -> ControlFlow<Break<String>> {
+            self.games += 1;
+            ControlFlow::Continue(())
+        }
+
+        fn san(&mut self, san_plus: SanPlus) -> ControlFlow<Break<String>> {
+            let san = san_plus.to_string();
+            let flow = match san.as_str() {
+                "Nf3" => ControlFlow::Break(Break::SkipGame),
+                "c4" => ControlFlow::Break(Break::Stop),
+                "Qh5" => ControlFlow::Break(Break::Error("early queen".to_owned())),
+                _ => ControlFlow::Continue(()),
+            };
+            self.moves.push(san);
+            flow
+        }
+
+        fn end_game(&mut self) -> Result<Vec<String>, String> {
+            Ok(std::mem::take(&mut self.moves))
+        }
+    }
+
+    #[test]
+    fn test_break() {
+        let pgn = b"1. e4 e5 2. Nf3 Nc6 *\n\n1. d4 d5 *\n\n1. c4 e5 *\n\n1. e4 e5 2. Qh5 Nc6 *\n\n1. f4 *";
+
+        let mut moves = Moves::default();
+        let reader = BufferedReader::new(&pgn[..]);
+        let games: Vec<_> = reader
+            .try_into_iter(&mut moves)
+            .map(|game| game.unwrap())
+            .collect();
+        assert_eq!(games, [vec!["e4", "e5", "Nf3"], vec!["d4", "d5"]]);
+        assert_eq!(moves.games, 3);
+
+        let mut moves = Moves::default();
+        let mut iter = BufferedReader::new(&pgn[..]).try_into_iter(&mut moves);
+        assert_eq!(iter.by_ref().count(), 2);
+        assert!(iter.next().is_none());
+
+        let mut moves = Moves::default();
+        let mut reader = SliceReader::new(pgn);
+        assert!(reader.try_read_all(&mut moves).is_ok());
+        assert_eq!(reader.try_read_game(&mut moves).unwrap(), None);
+        assert_eq!(moves.games, 3);
+
+        let mut moves = Moves::default();
+        let mut reader = SliceReader::new(&pgn[pgn.len() - 30..]);
+        match reader.try_read_all(&mut moves) {
+            Err(TryError::Visitor(err)) => assert_eq!(err, "early queen"),
+            _ => panic!("expected visitor error"),
+        }
+        moves.moves.clear();
+        assert_eq!(
+            reader.try_read_game(&mut moves).unwrap(),
+            Some(vec!["f4".to_owned()])
+        );
+        assert_eq!(reader.try_read_game(&mut moves).unwrap(), None);
+    }
+
+    #[test]
+    fn test_skip_game_stops_parsing() {
+        // The unterminated comment is not even parsed after the break.
+        let pgn = b"1. Nf3 { unterminated";
+        let mut moves = Moves::default();
+        assert_eq!(
+            BufferedReader::new(&pgn[..])
+                .try_read_game(&mut moves)
+                .unwrap(),
+            Some(vec!["Nf3".to_owned()])
+        );
+        assert_eq!(
+            SliceReader::new(pgn).try_read_game(&mut moves).unwrap(),
+            Some(vec!["Nf3".to_owned()])
+        );
+        assert!(SliceReader::new(b"1. e4 { unterminated")
+            .try_read_game(&mut moves)
+            .is_err());
+    }
+}
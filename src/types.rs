@@ -5,11 +5,93 @@ use std::{
     str::{self, FromStr, Utf8Error},
 };
 
+use shakmaty::{san::SanPlus, Color};
+
+use crate::comment::CommentParts;
+
 /// Tell the reader to skip over a game or variation.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
 #[must_use]
 pub struct Skip(pub bool);
 
+/// Tell the reader what to do with a movetext token that was not
+/// recognized. See [`Visitor::invalid_token()`](crate::Visitor::invalid_token).
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+#[must_use]
+pub enum TokenAction {
+    /// Ignore the token, as if it was not there.
+    Skip,
+    /// Stop reading the game. The rest of the movetext is skipped, and the
+    /// reader returns a [`SyntaxError`](crate::SyntaxError) with kind
+    /// [`InvalidToken`](crate::SyntaxErrorKind::InvalidToken).
+    Abort,
+    /// Use the given move instead of the token.
+    Replace(SanPlus),
+}
+
+/// Why a movetext token was not recognized.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum InvalidTokenKind {
+    /// The token looks like a move, but is not valid SAN, like `Nf3x` or
+    /// `Ke1g1`.
+    San,
+    /// The token starts with `$`, but is not a valid NAG.
+    Nag,
+    /// The token is not part of PGN syntax.
+    Unknown,
+}
+
+impl fmt::Display for InvalidTokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InvalidTokenKind::San => "invalid san",
+            InvalidTokenKind::Nag => "invalid nag",
+            InvalidTokenKind::Unknown => "unknown token",
+        })
+    }
+}
+
+/// A move number indication like `12.` or `12...`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct MoveNumber {
+    /// The move number.
+    pub number: u32,
+    /// The number of dots following the number.
+    pub dots: usize,
+}
+
+impl MoveNumber {
+    /// The side to move indicated by the dots: `12.` for White and `12...`
+    /// for Black. `None` for other numbers of dots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pgn_reader::{Color, MoveNumber};
+    ///
+    /// assert_eq!(MoveNumber { number: 12, dots: 1 }.turn(), Some(Color::White));
+    /// assert_eq!(MoveNumber { number: 12, dots: 3 }.turn(), Some(Color::Black));
+    /// assert_eq!(MoveNumber { number: 12, dots: 0 }.turn(), None);
+    /// ```
+    pub fn turn(self) -> Option<Color> {
+        match self.dots {
+            1 => Some(Color::White),
+            3 => Some(Color::Black),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MoveNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number)?;
+        for _ in 0..self.dots {
+            f.write_str(".")?;
+        }
+        Ok(())
+    }
+}
+
 /// A numeric annotation glyph like `?`, `!!` or `$42`.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct Nag(pub u8);
@@ -133,7 +215,7 @@ pub struct RawTag<'a>(pub &'a [u8]);
 
 impl<'a> RawTag<'a> {
     /// Returns the raw byte representation of the tag value.
-    pub fn as_bytes(&self) -> &[u8] {
+    pub fn as_bytes(&self) -> &'a [u8] {
         self.0
     }
 
@@ -194,9 +276,34 @@ pub struct RawComment<'a>(pub &'a [u8]);
 
 impl<'a> RawComment<'a> {
     /// Returns the raw byte representation of the comment.
-    pub fn as_bytes(&self) -> &[u8] {
+    pub fn as_bytes(&self) -> &'a [u8] {
         self.0
     }
+
+    /// Iterates over the free text and the embedded commands like
+    /// `[%clk 1:05:23]`, `[%eval 0.25]`, `[%csl Ga1]` or `[%cal Ra1a8]`.
+    ///
+    /// Unknown or malformed commands are returned as
+    /// [`CommentPart::Command`](crate::CommentPart::Command).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use pgn_reader::{CommentPart, RawComment, Score};
+    ///
+    /// let comment = RawComment(b" [%eval 0.17] [%clk 0:03:00] Book move ");
+    /// let parts: Vec<_> = comment.parts().collect();
+    ///
+    /// assert_eq!(parts, vec![
+    ///     CommentPart::Eval { score: Score::Centipawns(17), depth: None },
+    ///     CommentPart::Clock(Duration::from_secs(180)),
+    ///     CommentPart::Text(b"Book move"),
+    /// ]);
+    /// ```
+    pub fn parts(&self) -> CommentParts<'a> {
+        CommentParts::new(self.0)
+    }
 }
 
 impl<'a> fmt::Debug for RawComment<'a> {
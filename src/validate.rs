@@ -0,0 +1,531 @@
+use std::{error, fmt, mem};
+
+use shakmaty::{
+    fen::{Fen, ParseFenError},
+    san::{SanError, SanPlus},
+    variant::VariantPosition,
+    EnPassantMode, Position, PositionError,
+};
+
+use crate::{
+    position::{ply, PositionAdapter, PositionVisitor, SetupError},
+    types::{InvalidTokenKind, MoveNumber, RawTag, Skip, TokenAction},
+    visitor::Visitor,
+};
+
+/// Error found by a [`Validator`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// The `Variant` tag does not name a supported variant.
+    UnknownVariant,
+    /// The `FEN` tag is not syntactically valid.
+    InvalidFen(ParseFenError),
+    /// The `FEN` tag describes an illegal position.
+    IllegalFen(Box<PositionError<VariantPosition>>),
+    /// A move is illegal or ambiguous.
+    IllegalMove(Box<IllegalMove>),
+    /// A movetext token is not recognized. Only reported with
+    /// [`Validator::check_invalid_tokens()`].
+    InvalidToken(Box<InvalidToken>),
+    /// A move number does not match the position. Only if enabled with
+    /// [`Validator::check_move_numbers()`].
+    WrongMoveNumber(Box<WrongMoveNumber>),
+}
+
+impl From<SetupError> for ValidationError {
+    fn from(err: SetupError) -> ValidationError {
+        match err {
+            SetupError::UnknownVariant => ValidationError::UnknownVariant,
+            SetupError::InvalidFen(err) => ValidationError::InvalidFen(err),
+            SetupError::IllegalFen(err) => ValidationError::IllegalFen(err),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::UnknownVariant => f.write_str("unknown variant tag"),
+            ValidationError::InvalidFen(err) => write!(f, "invalid fen tag: {err}"),
+            ValidationError::IllegalFen(err) => write!(f, "illegal fen tag: {err}"),
+            ValidationError::IllegalMove(err) => err.fmt(f),
+            ValidationError::InvalidToken(err) => err.fmt(f),
+            ValidationError::WrongMoveNumber(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ValidationError::UnknownVariant => None,
+            ValidationError::InvalidFen(err) => Some(err),
+            ValidationError::IllegalFen(err) => Some(err.as_ref()),
+            ValidationError::IllegalMove(err) => Some(err.as_ref()),
+            ValidationError::InvalidToken(err) => Some(err.as_ref()),
+            ValidationError::WrongMoveNumber(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+/// An illegal or ambiguous move, along with the position in which it was
+/// played.
+#[derive(Debug, Clone)]
+pub struct IllegalMove {
+    /// Ply of the move, counting from 1 for the first move of White and
+    /// respecting the move number of the starting position.
+    pub ply: u32,
+    /// The move as found in the movetext.
+    pub san_plus: SanPlus,
+    /// The position before the move.
+    pub fen: Fen,
+    /// Whether the move is illegal or ambiguous.
+    pub error: SanError,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} at ply {} in position {}",
+            match self.error {
+                SanError::IllegalSan => "illegal move",
+                SanError::AmbiguousSan => "ambiguous move",
+            },
+            self.san_plus,
+            self.ply,
+            self.fen
+        )
+    }
+}
+
+impl error::Error for IllegalMove {}
+
+/// A movetext token that is not recognized, along with the position in
+/// which it was found.
+#[derive(Debug, Clone)]
+pub struct InvalidToken {
+    /// Ply of the move that the token replaces, counted like
+    /// [`IllegalMove::ply`].
+    pub ply: u32,
+    /// The token as found in the movetext.
+    pub token: Vec<u8>,
+    /// Why the token is not recognized.
+    pub kind: InvalidTokenKind,
+    /// The position in which the token was found.
+    pub fen: Fen,
+}
+
+impl fmt::Display for InvalidToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:?} at ply {} in position {}",
+            self.kind,
+            String::from_utf8_lossy(&self.token),
+            self.ply,
+            self.fen
+        )
+    }
+}
+
+impl error::Error for InvalidToken {}
+
+/// A move number that does not match the fullmove number or the side to
+/// move of the position in which it was found.
+#[derive(Debug, Clone)]
+pub struct WrongMoveNumber {
+    /// Ply of the following move, counted like [`IllegalMove::ply`].
+    pub ply: u32,
+    /// The move number as found in the movetext.
+    pub move_number: MoveNumber,
+    /// The position in which the move number was found.
+    pub fen: Fen,
+}
+
+impl fmt::Display for WrongMoveNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wrong move number {} at ply {} in position {}",
+            self.move_number, self.ply, self.fen
+        )
+    }
+}
+
+impl error::Error for WrongMoveNumber {}
+
+#[derive(Debug, Default)]
+struct Checks {
+    error: Option<ValidationError>,
+    move_numbers: bool,
+    invalid_tokens: bool,
+}
+
+impl PositionVisitor for Checks {
+    type Result = Result<(), ValidationError>;
+
+    fn setup_error(&mut self, error: SetupError) {
+        self.error = Some(error.into());
+    }
+
+    fn move_number(&mut self, pos: &VariantPosition, move_number: MoveNumber) {
+        if self.error.is_none()
+            && self.move_numbers
+            && (move_number.number != pos.fullmoves().get()
+                || move_number.turn().is_some_and(|turn| turn != pos.turn()))
+        {
+            self.error = Some(ValidationError::WrongMoveNumber(Box::new(
+                WrongMoveNumber {
+                    ply: ply(pos).saturating_add(1),
+                    move_number,
+                    fen: Fen::from_position(pos, EnPassantMode::Legal),
+                },
+            )));
+        }
+    }
+
+    fn illegal_move(&mut self, pos: &VariantPosition, san_plus: SanPlus, error: SanError) {
+        if self.error.is_none() {
+            self.error = Some(ValidationError::IllegalMove(Box::new(IllegalMove {
+                ply: ply(pos).saturating_add(1),
+                san_plus,
+                fen: Fen::from_position(pos, EnPassantMode::Legal),
+                error,
+            })));
+        }
+    }
+
+    fn invalid_token(
+        &mut self,
+        pos: &VariantPosition,
+        token: &[u8],
+        kind: InvalidTokenKind,
+    ) -> TokenAction {
+        if self.error.is_none() && self.invalid_tokens {
+            self.error = Some(ValidationError::InvalidToken(Box::new(InvalidToken {
+                ply: ply(pos).saturating_add(1),
+                token: token.to_owned(),
+                kind,
+                fen: Fen::from_position(pos, EnPassantMode::Legal),
+            })));
+        }
+        TokenAction::Skip
+    }
+
+    fn begin_variation(&mut self, _pos: &VariantPosition) -> Skip {
+        Skip(self.error.is_some())
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        match mem::take(&mut self.error) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A [`Visitor`] that checks the legality of all moves, including moves in
+/// variations.
+///
+/// Positions are tracked by a [`PositionAdapter`], so games start from the
+/// `Variant` and `FEN` tags. Each variation starts from the position before
+/// the preceding move, and the position is restored at the end of the
+/// variation. Validation stops at the first illegal or ambiguous move.
+///
+/// Optionally, move numbers like `12.` or `12...` can be checked against the
+/// fullmove number and side to move, to catch mis-numbered or spliced games.
+/// See [`Validator::check_move_numbers()`]. Movetext tokens that are not
+/// recognized are ignored, unless [`Validator::check_invalid_tokens()`] is
+/// enabled.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, ValidationError, Validator};
+///
+/// let pgn = b"1. e4 e5 (1... Nf6 2. Nf3) 2. Nf3 Nf3 *";
+///
+/// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let result = reader.read_game(&mut Validator::new())?.expect("game");
+///
+/// match result {
+///     Err(ValidationError::IllegalMove(illegal)) => {
+///         assert_eq!(illegal.ply, 4);
+///         assert_eq!(illegal.san_plus.to_string(), "Nf3");
+///         assert_eq!(
+///             illegal.fen.to_string(),
+///             "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+///         );
+///     }
+///     _ => panic!("expected illegal move"),
+/// }
+/// # Ok::<_, io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Validator {
+    inner: PositionAdapter<Checks>,
+}
+
+impl Default for Validator {
+    fn default() -> Validator {
+        Validator::new()
+    }
+}
+
+impl Validator {
+    /// Creates a new validator.
+    pub fn new() -> Validator {
+        Validator {
+            inner: PositionAdapter::new(Checks::default()),
+        }
+    }
+
+    /// Also check that move numbers match the position, within each
+    /// variation. `12.` must be followed by a move of White and `12...` by a
+    /// move of Black, in both cases with fullmove number 12. Numbers
+    /// without exactly one or three dots only need to match the fullmove
+    /// number. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io;
+    /// use pgn_reader::{BufferedReader, ValidationError, Validator};
+    ///
+    /// let pgn = b"1. e4 e5 2. Nf3 (3. Nc3) *";
+    ///
+    /// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+    /// let result = reader
+    ///     .read_game(Validator::new().check_move_numbers(true))?
+    ///     .expect("game");
+    ///
+    /// match result {
+    ///     Err(ValidationError::WrongMoveNumber(wrong)) => {
+    ///         assert_eq!(wrong.ply, 3);
+    ///         assert_eq!(wrong.move_number.to_string(), "3.");
+    ///     }
+    ///     _ => panic!("expected wrong move number"),
+    /// }
+    /// # Ok::<_, io::Error>(())
+    /// ```
+    pub fn check_move_numbers(&mut self, check: bool) -> &mut Validator {
+        self.inner.get_mut().move_numbers = check;
+        self
+    }
+
+    /// Also fail at the first movetext token that is not recognized, like
+    /// `Nf3x` or `&`. Disabled by default, so that such tokens are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io;
+    /// use pgn_reader::{BufferedReader, ValidationError, Validator};
+    ///
+    /// let pgn = b"1. e4 e5 2. Nf3x Nc6 *";
+    ///
+    /// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+    /// let result = reader
+    ///     .read_game(Validator::new().check_invalid_tokens(true))?
+    ///     .expect("game");
+    ///
+    /// match result {
+    ///     Err(ValidationError::InvalidToken(invalid)) => assert_eq!(invalid.token, b"Nf3x"),
+    ///     _ => panic!("expected invalid token"),
+    /// }
+    /// # Ok::<_, io::Error>(())
+    /// ```
+    pub fn check_invalid_tokens(&mut self, check: bool) -> &mut Validator {
+        self.inner.get_mut().invalid_tokens = check;
+        self
+    }
+
+    fn failed(&self) -> bool {
+        self.inner.get_ref().error.is_some()
+    }
+}
+
+impl Visitor for Validator {
+    type Result = Result<(), ValidationError>;
+
+    fn begin_tags(&mut self) {
+        self.inner.begin_tags();
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        self.inner.tag(name, value);
+    }
+
+    fn begin_movetext(&mut self) -> Skip {
+        self.inner.begin_movetext()
+    }
+
+    fn move_number(&mut self, move_number: MoveNumber) {
+        if !self.failed() {
+            self.inner.move_number(move_number);
+        }
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        if !self.failed() {
+            self.inner.san(san_plus);
+        }
+    }
+
+    fn invalid_token(&mut self, token: &[u8], kind: InvalidTokenKind) -> TokenAction {
+        if self.failed() {
+            TokenAction::Skip
+        } else {
+            self.inner.invalid_token(token, kind)
+        }
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        self.inner.begin_variation()
+    }
+
+    fn end_variation(&mut self) {
+        self.inner.end_variation();
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        self.inner.end_game()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::BufferedReader;
+
+    fn validate(pgn: &[u8]) -> Result<(), ValidationError> {
+        BufferedReader::new(io::Cursor::new(pgn))
+            .read_game(&mut Validator::new())
+            .expect("read")
+            .expect("game")
+    }
+
+    fn illegal_move(pgn: &[u8]) -> IllegalMove {
+        match validate(pgn) {
+            Err(ValidationError::IllegalMove(illegal)) => *illegal,
+            res => panic!("expected illegal move, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn test_variations() {
+        assert!(validate(b"1. e4 e5 (1... c5 2. Nf3 (2. Nc3 Nc6) 2... d6) 2. Nf3 Nc6 *").is_ok());
+
+        let illegal =
+            illegal_move(b"1. e4 e5 (1... c5 2. Nf3 (2. Nc3 Nc6) 2... d6 3. Nc3) 2. Nf3 Nf3 *");
+        assert_eq!(illegal.ply, 4);
+        assert_eq!(illegal.san_plus.to_string(), "Nf3");
+
+        let illegal = illegal_move(b"1. e4 e5 (1... c5 2. Nf3 (2. Nc3 Nc6) 2... e4) 2. Nf3 *");
+        assert_eq!(illegal.ply, 4);
+        assert_eq!(illegal.error, SanError::IllegalSan);
+        assert_eq!(
+            illegal.fen.to_string(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn test_ambiguous() {
+        let illegal = illegal_move(b"[FEN \"4k3/8/8/8/8/8/8/2N1K1N1 w - - 0 30\"]\n\n30. Ne2 *");
+        assert_eq!(illegal.ply, 59);
+        assert_eq!(illegal.error, SanError::AmbiguousSan);
+    }
+
+    #[test]
+    fn test_setup() {
+        assert!(
+            validate(b"[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K3 b - - 0 1\"]\n\n1... Kd7 *")
+                .is_ok()
+        );
+        assert!(
+            validate(b"[SetUp \"0\"]\n[FEN \"4k3/8/8/8/8/8/8/4K3 b - - 0 1\"]\n\n1. e4 *").is_ok()
+        );
+        assert!(matches!(
+            validate(b"[FEN \"4k3/8/8/8/8/8/8/4K3 x - - 0 1\"]\n\n1. e4 *"),
+            Err(ValidationError::InvalidFen(_))
+        ));
+        assert!(matches!(
+            validate(b"[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n*"),
+            Err(ValidationError::IllegalFen(_))
+        ));
+    }
+
+    #[test]
+    fn test_variants() {
+        assert!(validate(
+            b"[Variant \"Crazyhouse\"]\n\n1. e4 d5 2. exd5 Qxd5 3. Nc3 Qa5 4. P@d4 *"
+        )
+        .is_ok());
+        assert!(validate(b"[Variant \"Antichess\"]\n\n1. e3 b5 2. Bxb5 Bb7 3. Bxd7 *").is_ok());
+        assert_eq!(
+            illegal_move(b"[Variant \"Antichess\"]\n\n1. e3 b5 2. Nf3 *").error,
+            SanError::IllegalSan
+        );
+        assert!(matches!(
+            validate(b"[Variant \"Bughouse\"]\n\n1. e4 *"),
+            Err(ValidationError::UnknownVariant)
+        ));
+        assert!(validate(
+            b"[Variant \"Chess960\"]\n[FEN \"nrbbqkrn/pppppppp/8/8/8/8/PPPPPPPP/NRBBQKRN w GBgb - 0 1\"]\n\n1. O-O O-O *"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_move_numbers() {
+        let check = |pgn: &[u8]| {
+            BufferedReader::new(io::Cursor::new(pgn))
+                .read_game(Validator::new().check_move_numbers(true))
+                .expect("read")
+                .expect("game")
+        };
+
+        let pgn = b"1. e4 e5 (1... c5 2 Nf3 (2. Nc3) 2... d6) 2. Nf3 {x} 2... Nc6 3. Bb5 *";
+        assert!(check(pgn).is_ok());
+        assert!(check(b"[FEN \"4k3/8/8/8/8/8/8/4K3 b - - 0 30\"]\n\n30... Kd7 31. Kd2 *").is_ok());
+
+        assert!(validate(b"1. e4 e5 3. Nf3 *").is_ok());
+        match check(b"1. e4 e5 (1. c5) 2. Nf3 *") {
+            Err(ValidationError::WrongMoveNumber(wrong)) => {
+                assert_eq!(wrong.ply, 2);
+                assert_eq!(wrong.move_number, MoveNumber { number: 1, dots: 1 });
+            }
+            res => panic!("expected wrong move number, got {res:?}"),
+        }
+        match check(b"1. e4 e5 2. Nf3 Nc6 2. Bb5 *") {
+            Err(ValidationError::WrongMoveNumber(wrong)) => assert_eq!(wrong.ply, 5),
+            res => panic!("expected wrong move number, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_token() {
+        assert!(validate(b"1. e4 & e5 2. Nf3 && *").is_ok());
+
+        let mut validator = Validator::new();
+        validator.check_invalid_tokens(true);
+        let result = BufferedReader::new(io::Cursor::new(b"1. e4 e5 2. Nf3x Nc6 3. Bb5 *"))
+            .read_game(&mut validator)
+            .expect("read")
+            .expect("game");
+        match result {
+            Err(ValidationError::InvalidToken(invalid)) => {
+                assert_eq!(invalid.ply, 3);
+                assert_eq!(invalid.token, b"Nf3x");
+                assert_eq!(invalid.kind, InvalidTokenKind::San);
+            }
+            res => panic!("expected invalid token, got {res:?}"),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use shakmaty::{variant::Variant, CastlingMode};
+
+/// The chess variant and castling mode selected by a `Variant` tag.
+///
+/// # Examples
+///
+/// ```
+/// use pgn_reader::VariantTag;
+/// use shakmaty::{variant::Variant, CastlingMode};
+///
+/// let tag = VariantTag::from_ascii(b"King of the Hill").expect("known variant");
+/// assert_eq!(tag.variant, Variant::KingOfTheHill);
+///
+/// let tag = VariantTag::from_ascii(b"FRC").expect("known variant");
+/// assert_eq!(tag.variant, Variant::Chess);
+/// assert_eq!(tag.castling_mode, CastlingMode::Chess960);
+///
+/// assert_eq!(VariantTag::from_ascii(b"Bughouse"), None);
+/// ```
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct VariantTag {
+    /// The rules of the game.
+    pub variant: Variant,
+    /// [`CastlingMode::Chess960`] for Chess960 (Fischer Random Chess),
+    /// otherwise [`CastlingMode::Standard`].
+    pub castling_mode: CastlingMode,
+}
+
+impl Default for VariantTag {
+    fn default() -> VariantTag {
+        VariantTag {
+            variant: Variant::Chess,
+            castling_mode: CastlingMode::Standard,
+        }
+    }
+}
+
+impl VariantTag {
+    /// Parses the value of a `Variant` tag.
+    ///
+    /// Accepts the names used by lichess (like `Three-check` or `From
+    /// Position`), their camel case forms (like `threeCheck`), and common
+    /// ChessBase and other spellings (like `Fischerandom`, `FRC` or `ZH`).
+    /// The comparison is case-insensitive and ignores spaces, hyphens and
+    /// underscores.
+    ///
+    /// Returns `None` for unknown variants.
+    pub fn from_ascii(value: &[u8]) -> Option<VariantTag> {
+        let mut buf = [0; 32];
+        let mut len = 0;
+        for &ch in value {
+            if matches!(ch, b' ' | b'\t' | b'-' | b'_') {
+                continue;
+            }
+            *buf.get_mut(len)? = ch.to_ascii_lowercase();
+            len += 1;
+        }
+
+        let (variant, castling_mode) = match &buf[..len] {
+            b"" | b"chess" | b"standard" | b"normal" | b"fromposition" | b"fromfen" => {
+                (Variant::Chess, CastlingMode::Standard)
+            }
+            b"chess960"
+            | b"960"
+            | b"frc"
+            | b"fischerandom"
+            | b"fischerrandom"
+            | b"fischerandomchess"
+            | b"fischerrandomchess" => (Variant::Chess, CastlingMode::Chess960),
+            b"atomic" | b"atomicchess" => (Variant::Atomic, CastlingMode::Standard),
+            b"antichess" | b"giveaway" => (Variant::Antichess, CastlingMode::Standard),
+            b"kingofthehill" | b"koth" => (Variant::KingOfTheHill, CastlingMode::Standard),
+            b"threecheck" | b"3check" => (Variant::ThreeCheck, CastlingMode::Standard),
+            b"crazyhouse" | b"zh" | b"zhouse" => (Variant::Crazyhouse, CastlingMode::Standard),
+            b"racingkings" => (Variant::RacingKings, CastlingMode::Standard),
+            b"horde" => (Variant::Horde, CastlingMode::Standard),
+            _ => return None,
+        };
+
+        Some(VariantTag {
+            variant,
+            castling_mode,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_tag() {
+        for (value, variant) in [
+            (&b"Standard"[..], Variant::Chess),
+            (b"From Position", Variant::Chess),
+            (b"fromPosition", Variant::Chess),
+            (b"Atomic", Variant::Atomic),
+            (b"Antichess", Variant::Antichess),
+            (b"King of the Hill", Variant::KingOfTheHill),
+            (b"kingOfTheHill", Variant::KingOfTheHill),
+            (b"Three-check", Variant::ThreeCheck),
+            (b"3check", Variant::ThreeCheck),
+            (b"Crazyhouse", Variant::Crazyhouse),
+            (b"Racing Kings", Variant::RacingKings),
+            (b"racingKings", Variant::RacingKings),
+            (b"Horde", Variant::Horde),
+        ] {
+            assert_eq!(
+                VariantTag::from_ascii(value),
+                Some(VariantTag {
+                    variant,
+                    castling_mode: CastlingMode::Standard,
+                })
+            );
+        }
+
+        for value in [&b"Chess960"[..], b"chess960", b"FRC", b"Fischerandom"] {
+            assert_eq!(
+                VariantTag::from_ascii(value),
+                Some(VariantTag {
+                    variant: Variant::Chess,
+                    castling_mode: CastlingMode::Chess960,
+                })
+            );
+        }
+
+        assert_eq!(VariantTag::from_ascii(b"Bughouse"), None);
+        assert_eq!(VariantTag::from_ascii(&[b'x'; 100]), None);
+    }
+}
@@ -1,6 +1,6 @@
 use shakmaty::{san::SanPlus, Outcome};
 
-use crate::types::{Nag, RawComment, RawTag, Skip};
+use crate::types::{InvalidTokenKind, MoveNumber, Nag, RawComment, RawTag, Skip, TokenAction};
 
 /// Consumes games from a reader.
 ///
@@ -20,12 +20,40 @@ pub trait Visitor {
         Skip(false)
     }
 
+    /// Called for each move number, like `12.` or `12...`.
+    fn move_number(&mut self, _move_number: MoveNumber) {}
     /// Called for each move, like `Nf3+`.
     fn san(&mut self, _san_plus: SanPlus) {}
+    /// Called for each movetext token that is not recognized, like `Nf3x`
+    /// or a typo. The visitor can skip the token (default), abort the game,
+    /// or replace the token with a move.
+    fn invalid_token(&mut self, _token: &[u8], _kind: InvalidTokenKind) -> TokenAction {
+        TokenAction::Skip
+    }
     /// Called for each numeric annotation glyph like `!?` or `$7`.
     fn nag(&mut self, _nag: Nag) {}
     /// Called for each `{ comment }`.
     fn comment(&mut self, _comment: RawComment<'_>) {}
+    /// Called at the start of a `{ comment }` that exceeds the maximum
+    /// comment length, instead of
+    /// [`comment()`](trait.Visitor.html#method.comment). Only if enabled
+    /// with
+    /// [`ReaderBuilder::stream_long_comments()`](crate::ReaderBuilder::stream_long_comments).
+    fn begin_long_comment(&mut self) {}
+    /// Called with consecutive pieces of a long comment, after
+    /// [`begin_long_comment()`](trait.Visitor.html#method.begin_long_comment).
+    fn partial_comment(&mut self, _chunk: RawComment<'_>) {}
+    /// Called at the end of a long comment.
+    fn end_long_comment(&mut self) {}
+    /// Called for each `;` comment, with the rest of the line, excluding
+    /// the line break. Lines longer than the maximum comment length are
+    /// truncated.
+    fn line_comment(&mut self, _comment: RawComment<'_>) {}
+    /// Called for each `%` escape line, with the rest of the line after the
+    /// `%`, excluding the line break. Lines longer than the maximum comment
+    /// length are truncated. Escape lines after the movetext are reported
+    /// before [`end_game()`](trait.Visitor.html#tymethod.end_game).
+    fn escape_line(&mut self, _line: &[u8]) {}
     /// Called for each `(`. May skip over the following variation directly
     /// to [`end_variation()`](trait.Visitor.html#method.end_variation) (or to
     /// [`end_game()`](trait.Visitor.html#tymethod.end_game) if no matching `)`
@@ -43,6 +71,112 @@ pub trait Visitor {
     fn end_game(&mut self) -> Self::Result;
 }
 
+/// Consumes games from a [`SliceReader`](crate::SliceReader).
+///
+/// Like [`Visitor`], but tags and comments borrow from the input for its
+/// full lifetime `'a`, so that they can be kept without copying. Every
+/// [`Visitor`] is also a `SliceVisitor`.
+pub trait SliceVisitor<'a> {
+    /// Value produced by the visitor after reading a game.
+    type Result;
+
+    /// Called at the start of the game; directly before reading game tags.
+    fn begin_tags(&mut self) {}
+    /// Called when parsing a game tag pair like `[White "Deep Blue"]`.
+    fn tag(&mut self, _name: &'a [u8], _value: RawTag<'a>) {}
+    /// Called after reading the tags of a game; before reading the movetext.
+    /// May skip over the following movetext directly to
+    /// [`end_game()`](trait.SliceVisitor.html#tymethod.end_game).
+    fn begin_movetext(&mut self) -> Skip {
+        Skip(false)
+    }
+
+    /// Called for each move number, like `12.` or `12...`.
+    fn move_number(&mut self, _move_number: MoveNumber) {}
+    /// Called for each move, like `Nf3+`.
+    fn san(&mut self, _san_plus: SanPlus) {}
+    /// Called for each movetext token that is not recognized, like `Nf3x`
+    /// or a typo. The visitor can skip the token (default), abort the game,
+    /// or replace the token with a move.
+    fn invalid_token(&mut self, _token: &'a [u8], _kind: InvalidTokenKind) -> TokenAction {
+        TokenAction::Skip
+    }
+    /// Called for each numeric annotation glyph like `!?` or `$7`.
+    fn nag(&mut self, _nag: Nag) {}
+    /// Called for each `{ comment }`.
+    fn comment(&mut self, _comment: RawComment<'a>) {}
+    /// Called for each `;` comment, with the rest of the line, excluding
+    /// the line break.
+    fn line_comment(&mut self, _comment: RawComment<'a>) {}
+    /// Called for each `%` escape line, with the rest of the line after the
+    /// `%`, excluding the line break. Escape lines after the movetext are
+    /// reported before
+    /// [`end_game()`](trait.SliceVisitor.html#tymethod.end_game).
+    fn escape_line(&mut self, _line: &'a [u8]) {}
+    /// Called for each `(`. May skip over the following variation directly
+    /// to [`end_variation()`](trait.SliceVisitor.html#method.end_variation)
+    /// (or to [`end_game()`](trait.SliceVisitor.html#tymethod.end_game) if no
+    /// matching `)` follows before the end of the game.
+    fn begin_variation(&mut self) -> Skip {
+        Skip(false)
+    }
+    /// Called for each `)`. It is *not* guaranteed that there was a
+    /// matching `(`.
+    fn end_variation(&mut self) {}
+    /// Called for each game termination, like `*` or `1-0`.
+    fn outcome(&mut self, _outcome: Option<Outcome>) {}
+
+    /// Called after parsing a game. Can produce a custom result.
+    fn end_game(&mut self) -> Self::Result;
+}
+
+impl<'a, V: Visitor> SliceVisitor<'a> for V {
+    type Result = V::Result;
+
+    fn begin_tags(&mut self) {
+        Visitor::begin_tags(self);
+    }
+    fn tag(&mut self, name: &'a [u8], value: RawTag<'a>) {
+        Visitor::tag(self, name, value);
+    }
+    fn begin_movetext(&mut self) -> Skip {
+        Visitor::begin_movetext(self)
+    }
+    fn move_number(&mut self, move_number: MoveNumber) {
+        Visitor::move_number(self, move_number);
+    }
+    fn san(&mut self, san_plus: SanPlus) {
+        Visitor::san(self, san_plus);
+    }
+    fn invalid_token(&mut self, token: &'a [u8], kind: InvalidTokenKind) -> TokenAction {
+        Visitor::invalid_token(self, token, kind)
+    }
+    fn nag(&mut self, nag: Nag) {
+        Visitor::nag(self, nag);
+    }
+    fn comment(&mut self, comment: RawComment<'a>) {
+        Visitor::comment(self, comment);
+    }
+    fn line_comment(&mut self, comment: RawComment<'a>) {
+        Visitor::line_comment(self, comment);
+    }
+    fn escape_line(&mut self, line: &'a [u8]) {
+        Visitor::escape_line(self, line);
+    }
+    fn begin_variation(&mut self) -> Skip {
+        Visitor::begin_variation(self)
+    }
+    fn end_variation(&mut self) {
+        Visitor::end_variation(self);
+    }
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        Visitor::outcome(self, outcome);
+    }
+    fn end_game(&mut self) -> Self::Result {
+        Visitor::end_game(self)
+    }
+}
+
 pub(crate) struct SkipVisitor;
 
 impl Visitor for SkipVisitor {
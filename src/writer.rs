@@ -0,0 +1,408 @@
+use std::{io, mem};
+
+use shakmaty::{fen::Fen, san::SanPlus, Color, Outcome};
+
+use crate::{
+    types::{Nag, RawComment, RawTag, Skip},
+    visitor::Visitor,
+};
+
+const MAX_LINE_LENGTH: usize = 80;
+
+/// Writes games in PGN export format.
+///
+/// `Writer` is a [`Visitor`], so games can be piped directly from a
+/// [`BufferedReader`](crate::BufferedReader). Tag values are escaped,
+/// movetext is wrapped at 80 columns, and move numbers are inserted as
+/// required (including `N...` after comments and variations).
+///
+/// The writer emits exactly the tokens it is given. In particular, it does
+/// not add a game termination marker if none was visited.
+///
+/// Visitor methods can not fail, so the first I/O error is recorded and
+/// returned from [`end_game()`](Visitor::end_game). Further output for the
+/// game is suppressed after an error.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use pgn_reader::{BufferedReader, Writer};
+///
+/// let pgn = b"[White \"Deep Blue\"]\n\n1.e4 e5 (1...c5 2.Nf3) 2.Nf3 {Book} Nc6 *";
+///
+/// let mut reader = BufferedReader::new(io::Cursor::new(&pgn));
+/// let mut writer = Writer::new(Vec::new());
+/// reader.read_all(&mut writer)?;
+///
+/// assert_eq!(
+///     writer.into_inner(),
+///     b"[White \"Deep Blue\"]\n\n1. e4 e5 ( 1... c5 2. Nf3 ) 2. Nf3 {Book} 2... Nc6 *\n\n"
+/// );
+/// # Ok::<_, io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    writer: W,
+    error: Option<io::Error>,
+    has_tags: bool,
+    initial_ply: u32,
+    ply: u32,
+    stack: Vec<u32>,
+    needs_number: bool,
+    pending: Vec<u8>,
+    pending_glyph: bool,
+    line_length: usize,
+    long_comment: Vec<u8>,
+}
+
+impl<W: io::Write> Writer<W> {
+    /// Creates a new writer.
+    pub fn new(writer: W) -> Writer<W> {
+        Writer {
+            writer,
+            error: None,
+            has_tags: false,
+            initial_ply: 0,
+            ply: 0,
+            stack: Vec::new(),
+            needs_number: true,
+            pending: Vec::with_capacity(16),
+            pending_glyph: false,
+            line_length: 0,
+            long_comment: Vec::new(),
+        }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Unwraps the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_all(&mut self, buf: &[u8]) {
+        if self.error.is_none() {
+            if let Err(err) = self.writer.write_all(buf) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn write_token(&mut self, token: &[u8]) {
+        if self.line_length > 0 {
+            if self.line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                self.write_all(b"\n");
+                self.line_length = 0;
+            } else {
+                self.write_all(b" ");
+                self.line_length += 1;
+            }
+        }
+
+        self.write_all(token);
+        self.line_length = match memchr::memrchr(b'\n', token) {
+            Some(newline) => token.len() - newline - 1,
+            None => self.line_length + token.len(),
+        };
+    }
+
+    fn flush_pending(&mut self) {
+        if !self.pending.is_empty() {
+            let pending = mem::take(&mut self.pending);
+            self.write_token(&pending);
+            self.pending = pending;
+            self.pending.clear();
+        }
+    }
+
+    fn write_move_number(&mut self) {
+        let turn = Color::from_white(self.ply & 1 == 0);
+        if turn.is_white() || self.needs_number {
+            let number = self.ply / 2 + 1;
+            let token = if turn.is_white() {
+                format!("{number}.")
+            } else {
+                format!("{number}...")
+            };
+            self.write_token(token.as_bytes());
+        }
+    }
+}
+
+/// Appends `value` to `buf`, escaping quotes and backslashes. This is the
+/// inverse of [`RawTag::decode()`].
+fn escape_tag_value(value: &[u8], buf: &mut Vec<u8>) {
+    for &ch in value {
+        if ch == b'"' || ch == b'\\' {
+            buf.push(b'\\');
+        }
+        buf.push(ch);
+    }
+}
+
+fn nag_glyph(nag: Nag) -> Option<&'static [u8]> {
+    Some(match nag {
+        Nag::GOOD_MOVE => b"!",
+        Nag::MISTAKE => b"?",
+        Nag::BRILLIANT_MOVE => b"!!",
+        Nag::BLUNDER => b"??",
+        Nag::SPECULATIVE_MOVE => b"!?",
+        Nag::DUBIOUS_MOVE => b"?!",
+        _ => return None,
+    })
+}
+
+impl<W: io::Write> Visitor for Writer<W> {
+    type Result = io::Result<()>;
+
+    fn begin_tags(&mut self) {
+        self.has_tags = false;
+        self.initial_ply = 0;
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        if name == b"FEN" {
+            if let Ok(fen) = Fen::from_ascii(value.as_bytes()) {
+                let setup = fen.as_setup();
+                self.initial_ply = (setup.fullmoves.get() - 1)
+                    .saturating_mul(2)
+                    .saturating_add(setup.turn.fold_wb(0, 1));
+            }
+        }
+
+        let mut line = Vec::with_capacity(name.len() + value.as_bytes().len() + 6);
+        line.push(b'[');
+        line.extend_from_slice(name);
+        line.extend_from_slice(b" \"");
+        escape_tag_value(&value.decode(), &mut line);
+        line.extend_from_slice(b"\"]\n");
+        self.write_all(&line);
+        self.has_tags = true;
+    }
+
+    fn begin_movetext(&mut self) -> Skip {
+        if self.has_tags {
+            self.write_all(b"\n");
+        }
+        self.ply = self.initial_ply;
+        self.stack.clear();
+        self.needs_number = true;
+        self.pending.clear();
+        self.line_length = 0;
+        Skip(false)
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        self.flush_pending();
+        self.write_move_number();
+        san_plus.append_ascii_to(&mut self.pending);
+        self.pending_glyph = false;
+        self.needs_number = false;
+        self.ply = self.ply.saturating_add(1);
+    }
+
+    fn nag(&mut self, nag: Nag) {
+        match nag_glyph(nag) {
+            Some(glyph) if !self.pending.is_empty() && !self.pending_glyph => {
+                self.pending.extend_from_slice(glyph);
+                self.pending_glyph = true;
+            }
+            _ => {
+                self.flush_pending();
+                self.write_token(nag.to_string().as_bytes());
+            }
+        }
+    }
+
+    fn comment(&mut self, comment: RawComment<'_>) {
+        self.flush_pending();
+        let mut token = Vec::with_capacity(comment.as_bytes().len() + 2);
+        token.push(b'{');
+        token.extend_from_slice(comment.as_bytes());
+        token.push(b'}');
+        self.write_token(&token);
+        self.needs_number = true;
+    }
+
+    fn begin_long_comment(&mut self) {
+        self.long_comment.clear();
+    }
+
+    fn partial_comment(&mut self, chunk: RawComment<'_>) {
+        self.long_comment.extend_from_slice(chunk.as_bytes());
+    }
+
+    fn end_long_comment(&mut self) {
+        let long_comment = mem::take(&mut self.long_comment);
+        self.comment(RawComment(&long_comment));
+        self.long_comment = long_comment;
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        self.flush_pending();
+        self.write_token(b"(");
+        self.stack.push(self.ply);
+        self.ply = self.ply.saturating_sub(1);
+        self.needs_number = true;
+        Skip(false)
+    }
+
+    fn end_variation(&mut self) {
+        // Do not produce unbalanced parentheses.
+        if let Some(ply) = self.stack.pop() {
+            self.flush_pending();
+            self.write_token(b")");
+            self.ply = ply;
+            self.needs_number = true;
+        }
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.flush_pending();
+        self.write_token(match outcome {
+            Some(Outcome::Decisive {
+                winner: Color::White,
+            }) => b"1-0",
+            Some(Outcome::Decisive {
+                winner: Color::Black,
+            }) => b"0-1",
+            Some(Outcome::Draw) => b"1/2-1/2",
+            None => b"*",
+        });
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        self.flush_pending();
+        while self.stack.pop().is_some() {
+            self.write_token(b")");
+        }
+        if self.line_length > 0 {
+            self.write_all(b"\n");
+            self.line_length = 0;
+        }
+        self.write_all(b"\n");
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BufferedReader;
+
+    fn rewrite(pgn: &[u8]) -> Vec<u8> {
+        let mut reader = BufferedReader::new(io::Cursor::new(pgn));
+        let mut writer = Writer::new(Vec::new());
+        while let Some(result) = reader.read_game(&mut writer).expect("read") {
+            result.expect("write");
+        }
+        writer.into_inner()
+    }
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Visitor for Events {
+        type Result = ();
+
+        fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+            self.0
+                .push(format!("{} {:?}", String::from_utf8_lossy(name), value));
+        }
+        fn san(&mut self, san_plus: SanPlus) {
+            self.0.push(san_plus.to_string());
+        }
+        fn nag(&mut self, nag: Nag) {
+            self.0.push(nag.to_string());
+        }
+        fn comment(&mut self, comment: RawComment<'_>) {
+            self.0.push(format!("{comment:?}"));
+        }
+        fn begin_variation(&mut self) -> Skip {
+            self.0.push("(".to_owned());
+            Skip(false)
+        }
+        fn end_variation(&mut self) {
+            self.0.push(")".to_owned());
+        }
+        fn outcome(&mut self, outcome: Option<Outcome>) {
+            self.0.push(format!("{outcome:?}"));
+        }
+        fn end_game(&mut self) {
+            self.0.push("end".to_owned());
+        }
+    }
+
+    fn events(pgn: &[u8]) -> Vec<String> {
+        let mut events = Events::default();
+        BufferedReader::new(io::Cursor::new(pgn))
+            .read_all(&mut events)
+            .expect("read");
+        events.0
+    }
+
+    #[test]
+    fn test_escape_tag_value() {
+        let mut buf = Vec::new();
+        escape_tag_value(b"\\Hello \"world\\", &mut buf);
+        assert_eq!(buf, b"\\\\Hello \\\"world\\\\");
+        assert_eq!(RawTag(&buf).decode().as_ref(), b"\\Hello \"world\\");
+    }
+
+    #[test]
+    fn test_move_numbers() {
+        assert_eq!(
+            rewrite(b"1. e4 {A} e5 (1... c5 {B} 2. Nf3) (1... e6) 2. Nf3 Nc6 1-0"),
+            b"1. e4 {A} 1... e5 ( 1... c5 {B} 2. Nf3 ) ( 1... e6 ) 2. Nf3 Nc6 1-0\n\n"
+        );
+        assert_eq!(
+            rewrite(b"[FEN \"8/8/8/8/8/8/8/K1k5 b - - 0 41\"]\n\n41... Kc2 42. Ka2 *"),
+            b"[FEN \"8/8/8/8/8/8/8/K1k5 b - - 0 41\"]\n\n41... Kc2 42. Ka2 *\n\n"
+        );
+    }
+
+    #[test]
+    fn test_nags() {
+        assert_eq!(
+            rewrite(b"1. e4!? $14 e5 $2 $4 *"),
+            b"1. e4!? $14 e5? $4 *\n\n"
+        );
+    }
+
+    #[test]
+    fn test_line_wrapping() {
+        let pgn = rewrite(b"1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 *");
+        assert!(pgn.split(|&ch| ch == b'\n').all(|line| line.len() <= 80));
+        assert_eq!(
+            pgn,
+            &b"1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3\nO-O 9. h3 Nb8 *\n\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for pgn in [
+            &include_bytes!("../fuzz/corpus/pgn/kasparov-deep-blue-1997.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/molinari-bordais-1979.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/anastasian-lewis.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/saturs-jannlee-zh-lichess.pgn")[..],
+            &include_bytes!("../fuzz/corpus/pgn/cutechess-fischerrandom.pgn")[..],
+        ] {
+            let written = rewrite(pgn);
+            assert_eq!(events(&written), events(pgn));
+            assert_eq!(rewrite(&written), written);
+        }
+    }
+}